@@ -7,21 +7,22 @@ mod utils;
 
 use std::fmt::Debug;
 use std::{
-    borrow::Borrow,
     collections::HashSet,
     fmt::Display,
     sync::atomic::{AtomicBool, Ordering},
 };
 
 use driver_ipc::{
+    selector::Selector,
     sync::{DriverClient, EventsSubscription},
-    Dimen, EventCommand, Id, Mode, Monitor, RefreshRate,
+    BitDepth, Color, ColorFormat, ColorPrimaries, Dimen, EventCommand, Hdr, Id, Mode, Monitor,
+    Orientation, PhysicalSize, Position, RefreshRate, Topology,
 };
 use pyo3::prelude::*;
 use pyo3::{
-    exceptions::{PyIndexError, PyRuntimeError, PyTypeError},
+    exceptions::{PyIndexError, PyRuntimeError, PyTypeError, PyValueError},
     pyclass::boolean_struct::False,
-    types::{DerefToPyAny, PyList, PyLong},
+    types::{DerefToPyAny, PyDict, PyList, PyLong},
     DowncastIntoError, PyClass, PyTypeCheck,
 };
 
@@ -389,6 +390,58 @@ impl PyDriverClient {
         Ok(())
     }
 
+    /// Persist monitor configuration for the whole machine, which the driver
+    /// loads when it starts. Requires admin rights
+    /// Sig: persist_machine()
+    fn persist_machine(&mut self, py: Python) -> PyResult<()> {
+        let state = pytypedlist_to_state(py, &self.monitors)?;
+        self.client.set_monitors(&state).into_py_err()?;
+
+        self.client.persist_machine().into_py_err()?;
+
+        Ok(())
+    }
+
+    /// Save monitor configuration as a named profile
+    /// Sig: save_profile(name: str)
+    fn save_profile(&mut self, py: Python, name: &str) -> PyResult<()> {
+        let state = pytypedlist_to_state(py, &self.monitors)?;
+        self.client.set_monitors(&state).into_py_err()?;
+
+        self.client.save_profile(name).into_py_err()?;
+
+        Ok(())
+    }
+
+    /// Replace monitors with a named profile and send them to the driver
+    /// Sig: load_profile(name: str)
+    fn load_profile(&mut self, py: Python, name: &str) -> PyResult<()> {
+        self.client.load_profile(name).into_py_err()?;
+
+        self.monitors = state_to_pytypedlist(py, self.client.monitors())?;
+
+        Ok(())
+    }
+
+    /// List names of saved profiles
+    /// Sig: list_profiles() -> list[str]
+    fn list_profiles(&self) -> PyResult<Vec<String>> {
+        self.client.list_profiles().into_py_err()
+    }
+
+    /// Delete a named profile. Returns False if it did not exist
+    /// Sig: delete_profile(name: str) -> bool
+    fn delete_profile(&self, name: &str) -> PyResult<bool> {
+        self.client.delete_profile(name).into_py_err()
+    }
+
+    /// Set the profile applied when the user logs on, or remove it with None
+    /// Sig: set_default_profile(name: str | None)
+    #[pyo3(signature = (name))]
+    fn set_default_profile(&self, name: Option<&str>) -> PyResult<()> {
+        self.client.set_default_profile(name).into_py_err()
+    }
+
     /// Request a list of latest driver changes
     /// Sig: get_state() -> list[Monitor]
     fn get_state(&mut self, py: Python) -> PyResult<Py<PyList>> {
@@ -399,6 +452,32 @@ impl PyDriverClient {
         Ok(monitors)
     }
 
+    /// Request build and liveness information of the driver
+    /// Sig: health() -> dict
+    fn health<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyDict>> {
+        let health = self.client.health().into_py_err()?;
+
+        let swap_chains = PyList::empty_bound(py);
+        for swap_chain in health.swap_chains {
+            let dict = PyDict::new_bound(py);
+            dict.set_item("id", swap_chain.id)?;
+            dict.set_item("alive", swap_chain.alive)?;
+            swap_chains.append(dict)?;
+        }
+
+        let dict = PyDict::new_bound(py);
+        dict.set_item("version", health.version)?;
+        dict.set_item("git_sha", health.git_sha)?;
+        dict.set_item("iddcx_min_version", health.iddcx_min_version)?;
+        dict.set_item("uptime_ms", health.uptime_ms)?;
+        dict.set_item("adapter_initialized", health.adapter_initialized)?;
+        dict.set_item("clients", health.clients)?;
+        dict.set_item("pipe_server", health.pipe_server)?;
+        dict.set_item("swap_chains", swap_chains)?;
+
+        Ok(dict)
+    }
+
     /// Send notification to driver of changes
     /// Sig: notify()
     fn notify(&mut self, py: Python) -> PyResult<()> {
@@ -415,8 +494,9 @@ impl PyDriverClient {
     fn receive(&mut self, callback: PyObject) -> PyEventsSubscription {
         let event_subscription = self.client.add_event_receiver(move |data| match data {
             Ok(cmd) => {
+                // other events are only sent on request
                 let EventCommand::Changed(data) = cmd else {
-                    unreachable!()
+                    return;
                 };
 
                 Python::with_gil(|py| {
@@ -437,45 +517,55 @@ impl PyDriverClient {
         PyEventsSubscription(event_subscription)
     }
 
-    /// Find a monitor by Id
-    /// Sig: find_monitor(query: int | str) -> Optional[Monitor]
+    /// Find a monitor by id, or the first monitor matching a selector
+    /// Sig: find(query: int | str) -> Optional[Monitor]
     #[allow(clippy::needless_pass_by_value)]
     fn find(&self, py: Python, query: PyObject) -> PyResult<Option<Py<PyMonitor>>> {
-        let iter = self.monitors.iter_ref::<PyMonitor>(py);
-
         let query_b = query.bind(py);
-        let id = query_b.extract::<u32>();
-        let query = query_b.extract::<String>();
 
-        if id.is_err() && query.is_err() {
+        if let Ok(id) = query_b.extract::<u32>() {
+            for monitor in self.monitors.iter_ref::<PyMonitor>(py) {
+                let monitor = monitor?;
+                if monitor.id == id {
+                    return Ok(Some(monitor.into()));
+                }
+            }
+
+            return Ok(None);
+        }
+
+        let Ok(selector) = query_b.extract::<String>() else {
             let ty = query_b.get_type();
             return Err(PyTypeError::new_err(format!(
                 "expected u32|str, got {}",
                 ty.name()?,
             )));
-        }
+        };
 
-        for monitor in iter {
-            let monitor = monitor?;
-            let b = monitor.borrow();
+        Ok(self.find_all(py, &selector)?.into_iter().next())
+    }
 
-            if let Ok(id) = id {
-                if b.id == id {
-                    return Ok(Some(monitor.into()));
-                }
-            }
+    /// Find all monitors matching a selector, e.g. "name:test-*,enabled"
+    /// Sig: find_all(selector: str) -> list[Monitor]
+    fn find_all(&self, py: Python, selector: &str) -> PyResult<Vec<Py<PyMonitor>>> {
+        let selector = selector.parse::<Selector>().into_py_err()?;
 
-            if let Ok(query) = query.as_deref() {
-                if b.name.as_deref().is_some_and(|name| name == query) {
-                    return Ok(Some(monitor.into()));
-                }
+        let state = pytypedlist_to_state(py, &self.monitors)?;
+        let ids = selector.select(&state);
+
+        let mut monitors = Vec::new();
+        for monitor in self.monitors.iter_ref::<PyMonitor>(py) {
+            let monitor = monitor?;
+            if ids.contains(&monitor.id) {
+                monitors.push(monitor.into());
             }
         }
 
-        Ok(None)
+        Ok(monitors)
     }
 
-    /// Get the closest available free ID. Note that if internal state is stale, this may result in a duplicate ID
+    /// Get an available ID: the preferred ID if it is free, else None. Without a preferred ID, the
+    /// lowest free ID. Note that if internal state is stale, this may result in a duplicate ID
     /// which the driver will ignore when you notify it of changes
     /// Sig: new_id(preferred_id: Optional[int] = None) -> Optional[int]
     #[pyo3(signature = (preferred_id=None))]
@@ -601,6 +691,10 @@ struct PyMonitor {
     /// Sig: name: Optional[str]
     #[pyo3(get, set)]
     name: Option<String>,
+    /// Stable key of the monitor, e.g. a UUID. Must be unique from all other monitors
+    /// Sig: key: Optional[str]
+    #[pyo3(get, set)]
+    key: Option<String>,
     /// Whether the monitor is enabled or not
     /// Sig: enabled: bool
     #[pyo3(get, set)]
@@ -609,6 +703,58 @@ struct PyMonitor {
     /// Sig: modes: list[Mode]
     #[pyo3(get)]
     modes: Py<PyTypedList>,
+    /// Physical width and height in millimeters. Setting it clears `dpi`
+    /// Sig: size_mm: Optional[tuple[int, int]]
+    #[pyo3(get)]
+    size_mm: Option<(Dimen, Dimen)>,
+    /// Dots per inch of the first mode, the physical size follows from its resolution.
+    /// Setting it clears `size_mm`
+    /// Sig: dpi: Optional[int]
+    #[pyo3(get)]
+    dpi: Option<u32>,
+    /// Orientation of the desktop in degrees clockwise: 0, 90, 180 or 270. Modes are
+    /// as seen on the desktop, e.g. 1080x1920 for 90
+    /// Sig: orientation: int
+    #[pyo3(get)]
+    orientation: u32,
+    /// Position of the top left corner on the desktop, the primary display is at (0, 0).
+    /// Without a position, the monitor is placed to the right of the others
+    /// Sig: position: Optional[tuple[int, int]]
+    #[pyo3(get, set)]
+    position: Option<(i32, i32)>,
+    /// Whether the monitor should be the primary display
+    /// Sig: primary: bool
+    #[pyo3(get, set)]
+    primary: bool,
+    /// Either "extend" to show its own part of the desktop, or "duplicate" to show
+    /// the same as the primary display
+    /// Sig: topology: str
+    #[pyo3(get)]
+    topology: String,
+    /// Bits per color component: 6, 8, 10, 12, 14 or 16
+    /// Sig: bit_depth: int
+    #[pyo3(get)]
+    bit_depth: u8,
+    /// Color encodings supported besides RGB: "rgb", "ycbcr444" or "ycbcr422"
+    /// Sig: color_format: str
+    #[pyo3(get)]
+    color_format: String,
+    /// Whether the monitor reports HDR10 support, with the luminance and primaries below
+    /// Sig: hdr: bool
+    #[pyo3(get, set)]
+    hdr: bool,
+    /// Peak luminance in nits for HDR
+    /// Sig: max_luminance: float
+    #[pyo3(get, set)]
+    max_luminance: f32,
+    /// Black level in nits for HDR
+    /// Sig: min_luminance: float
+    #[pyo3(get, set)]
+    min_luminance: f32,
+    /// Gamut for HDR: "bt709", "dci-p3" or "bt2020"
+    /// Sig: color_primaries: str
+    #[pyo3(get)]
+    color_primaries: String,
 }
 
 impl Clone for PyMonitor {
@@ -616,8 +762,21 @@ impl Clone for PyMonitor {
         Python::with_gil(|py| Self {
             id: self.id,
             name: self.name.clone(),
+            key: self.key.clone(),
             enabled: self.enabled,
             modes: self.modes.clone_ref(py),
+            size_mm: self.size_mm,
+            dpi: self.dpi,
+            orientation: self.orientation,
+            position: self.position,
+            primary: self.primary,
+            topology: self.topology.clone(),
+            bit_depth: self.bit_depth,
+            color_format: self.color_format.clone(),
+            hdr: self.hdr,
+            max_luminance: self.max_luminance,
+            min_luminance: self.min_luminance,
+            color_primaries: self.color_primaries.clone(),
         })
     }
 }
@@ -626,11 +785,25 @@ impl Clone for PyMonitor {
 impl PyMonitor {
     #[new]
     fn new(py: Python) -> PyResult<PyMonitor> {
+        let hdr = Hdr::default();
         let inst = Self {
             id: 0,
             name: None,
+            key: None,
             enabled: false,
             modes: PyTypedList::new(py, ListType::Mode).try_into()?,
+            size_mm: None,
+            dpi: None,
+            orientation: 0,
+            position: None,
+            primary: false,
+            topology: Topology::Extend.to_string(),
+            bit_depth: BitDepth::default().bits(),
+            color_format: ColorFormat::default().to_string(),
+            hdr: false,
+            max_luminance: hdr.max_luminance,
+            min_luminance: hdr.min_luminance,
+            color_primaries: hdr.primaries.to_string(),
         };
 
         Ok(inst)
@@ -652,11 +825,90 @@ impl PyMonitor {
         Ok(())
     }
 
+    #[setter]
+    fn set_size_mm(&mut self, size_mm: Option<(Dimen, Dimen)>) {
+        self.size_mm = size_mm;
+        if size_mm.is_some() {
+            self.dpi = None;
+        }
+    }
+
+    #[setter]
+    fn set_dpi(&mut self, dpi: Option<u32>) {
+        self.dpi = dpi;
+        if dpi.is_some() {
+            self.size_mm = None;
+        }
+    }
+
+    #[setter]
+    fn set_orientation(&mut self, orientation: u32) -> PyResult<()> {
+        Orientation::try_from(orientation).map_err(PyValueError::new_err)?;
+        self.orientation = orientation;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_topology(&mut self, topology: String) -> PyResult<()> {
+        topology.parse::<Topology>().map_err(PyValueError::new_err)?;
+        self.topology = topology;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_bit_depth(&mut self, bit_depth: u8) -> PyResult<()> {
+        BitDepth::try_from(bit_depth).map_err(PyValueError::new_err)?;
+        self.bit_depth = bit_depth;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_color_format(&mut self, color_format: String) -> PyResult<()> {
+        color_format.parse::<ColorFormat>().map_err(PyValueError::new_err)?;
+        self.color_format = color_format;
+        Ok(())
+    }
+
+    #[setter]
+    fn set_color_primaries(&mut self, color_primaries: String) -> PyResult<()> {
+        color_primaries.parse::<ColorPrimaries>().map_err(PyValueError::new_err)?;
+        self.color_primaries = color_primaries;
+        Ok(())
+    }
+
     fn __repr__(&self) -> String {
         format!("{self:?}")
     }
 }
 
+impl PyMonitor {
+    fn physical_size(&self) -> Option<PhysicalSize> {
+        match (self.size_mm, self.dpi) {
+            (Some((width, height)), _) => Some(PhysicalSize::Millimeters { width, height }),
+            (None, Some(dpi)) => Some(PhysicalSize::Dpi(dpi)),
+            (None, None) => None,
+        }
+    }
+
+    fn color(&self) -> PyResult<Color> {
+        let hdr = if self.hdr {
+            Some(Hdr {
+                max_luminance: self.max_luminance,
+                min_luminance: self.min_luminance,
+                primaries: self.color_primaries.parse().map_err(PyValueError::new_err)?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Color {
+            bit_depth: BitDepth::try_from(self.bit_depth).map_err(PyValueError::new_err)?,
+            format: self.color_format.parse().map_err(PyValueError::new_err)?,
+            hdr,
+        })
+    }
+}
+
 impl TryFrom<PyMonitor> for Py<PyMonitor> {
     type Error = PyErr;
 
@@ -671,8 +923,21 @@ impl Debug for PyMonitor {
             let PyMonitor {
                 id,
                 name,
+                key,
                 enabled,
                 modes,
+                size_mm,
+                dpi,
+                orientation,
+                position,
+                primary,
+                topology,
+                bit_depth,
+                color_format,
+                hdr,
+                max_luminance,
+                min_luminance,
+                color_primaries,
             } = self;
 
             let modes = modes
@@ -683,8 +948,21 @@ impl Debug for PyMonitor {
             f.debug_struct("Monitor")
                 .field("id", &id)
                 .field("name", &name)
+                .field("key", &key)
                 .field("enabled", &enabled)
                 .field("modes", &modes)
+                .field("size_mm", &size_mm)
+                .field("dpi", &dpi)
+                .field("orientation", &orientation)
+                .field("position", &position)
+                .field("primary", &primary)
+                .field("topology", &topology)
+                .field("bit_depth", &bit_depth)
+                .field("color_format", &color_format)
+                .field("hdr", &hdr)
+                .field("max_luminance", &max_luminance)
+                .field("min_luminance", &min_luminance)
+                .field("color_primaries", &color_primaries)
                 .finish()
         })
     }
@@ -782,6 +1060,32 @@ impl PyMode {
         Ok(())
     }
 
+    /// Create a mode from a preset like "4k", "1440p" or "MacBook Pro 14". Without refresh rates, the preset's
+    /// default ones are used
+    /// Sig: Mode.preset(name: str, refresh_rates: Optional[list[int]] = None) -> Mode
+    #[staticmethod]
+    #[pyo3(signature = (name, refresh_rates=None))]
+    fn preset(
+        py: Python,
+        name: &str,
+        refresh_rates: Option<Vec<RefreshRate>>,
+    ) -> PyResult<PyMode> {
+        let mut mode = Mode::preset(name).into_py_err()?;
+        if let Some(refresh_rates) = refresh_rates {
+            mode = mode.with_refresh(&refresh_rates);
+        }
+
+        mode_to_pymode(py, &mode)
+    }
+
+    /// Parse a mode like "1920x1080", "1920x1080@60/120" or "4k@144"
+    /// Sig: Mode.parse(mode: str) -> Mode
+    #[staticmethod]
+    fn parse(py: Python, mode: &str) -> PyResult<PyMode> {
+        let mode = mode.parse::<Mode>().into_py_err()?;
+        mode_to_pymode(py, &mode)
+    }
+
     fn __repr__(&self) -> String {
         self.__str__()
     }
@@ -791,6 +1095,17 @@ impl PyMode {
     }
 }
 
+fn mode_to_pymode(py: Python, mode: &Mode) -> PyResult<PyMode> {
+    let py_refresh_rates = PyList::new_bound(py, &mode.refresh_rates);
+
+    Ok(PyMode {
+        width: mode.width,
+        height: mode.height,
+        refresh_rates: PyTypedList::new_from_list(py_refresh_rates.into(), ListType::RefreshRate)
+            .try_into()?,
+    })
+}
+
 fn state_to_pylist(py: Python, monitors: &[Monitor]) -> PyResult<Py<PyList>> {
     let py_state = PyList::empty_bound(py);
 
@@ -798,27 +1113,36 @@ fn state_to_pylist(py: Python, monitors: &[Monitor]) -> PyResult<Py<PyList>> {
         let modes = PyList::empty_bound(py);
 
         for mode in &monitor.modes {
-            let py_refresh_rates = PyList::new_bound(py, &mode.refresh_rates);
-
-            let mode: Py<PyMode> = PyMode {
-                width: mode.width,
-                height: mode.height,
-                refresh_rates: PyTypedList::new_from_list(
-                    py_refresh_rates.into(),
-                    ListType::RefreshRate,
-                )
-                .try_into()?,
-            }
-            .try_into()?;
+            let mode: Py<PyMode> = mode_to_pymode(py, mode)?.try_into()?;
 
             modes.append(mode)?;
         }
 
+        let hdr = monitor.color.hdr.unwrap_or_default();
         let monitor: Py<PyMonitor> = PyMonitor {
             id: monitor.id,
             name: monitor.name.clone(),
+            key: monitor.key.clone(),
             enabled: monitor.enabled,
             modes: PyTypedList::new_from_list(modes.into(), ListType::Mode).try_into()?,
+            size_mm: match monitor.physical_size {
+                Some(PhysicalSize::Millimeters { width, height }) => Some((width, height)),
+                _ => None,
+            },
+            dpi: match monitor.physical_size {
+                Some(PhysicalSize::Dpi(dpi)) => Some(dpi),
+                _ => None,
+            },
+            orientation: monitor.orientation.degrees(),
+            position: monitor.position.map(|Position { x, y }| (x, y)),
+            primary: monitor.primary,
+            topology: monitor.topology.to_string(),
+            bit_depth: monitor.color.bit_depth.bits(),
+            color_format: monitor.color.format.to_string(),
+            hdr: monitor.color.hdr.is_some(),
+            max_luminance: hdr.max_luminance,
+            min_luminance: hdr.min_luminance,
+            color_primaries: hdr.primaries.to_string(),
         }
         .try_into()?;
 
@@ -865,8 +1189,16 @@ fn pytypedlist_to_state(py: Python, monitors: &Py<PyTypedList>) -> PyResult<Vec<
         state.push(Monitor {
             id: py_monitor.id,
             name: py_monitor.name.clone(),
+            key: py_monitor.key.clone(),
             enabled: py_monitor.enabled,
             modes,
+            physical_size: py_monitor.physical_size(),
+            orientation: Orientation::try_from(py_monitor.orientation)
+                .map_err(PyValueError::new_err)?,
+            position: py_monitor.position.map(|(x, y)| Position { x, y }),
+            primary: py_monitor.primary,
+            topology: py_monitor.topology.parse().map_err(PyValueError::new_err)?,
+            color: py_monitor.color()?,
         });
     }
 
@@ -0,0 +1,977 @@
+//! Handling of driver commands, independent of IddCx.
+//!
+//! [DriverCore] keeps the monitors and the recording state the driver reports
+//! to its clients, and decides what a [DriverCommand] has to change. Making
+//! the changes is up to a [DisplayBackend]: the driver's backend talks to
+//! IddCx, while [MemoryBackend] only keeps track of what it was asked to do,
+//! so the command handling can be tested anywhere.
+
+mod memory;
+
+use std::{collections::BTreeSet, fmt};
+
+use log::{error, warn};
+
+use crate::{
+    Capabilities, Dimen, DriverCommand, EventCommand, Id, Mode, Monitor, RefreshRate, Rejection,
+    ReplyCommand, RequestCommand,
+};
+
+pub use memory::{BackendCall, MemoryBackend};
+
+/// What the driver does to the OS, see the [module documentation](self).
+pub trait DisplayBackend {
+    type Error: fmt::Display;
+
+    /// Create `monitor` and tell the OS it arrived.
+    fn arrive(&mut self, monitor: &Monitor) -> Result<(), Self::Error>;
+
+    /// Tell the OS the monitor `id` departed.
+    fn depart(&mut self, id: Id) -> Result<(), Self::Error>;
+
+    /// Replace the target modes of the connected `monitor` with its modes.
+    ///
+    /// If this fails, the monitor is departed and arrived again.
+    fn update_modes(&mut self, monitor: &Monitor) -> Result<(), Self::Error>;
+
+    /// Start capturing frames, replacing the previous capture.
+    fn start_capture(&mut self, capture: &Capture) -> Result<(), Self::Error>;
+
+    /// Stop capturing frames.
+    ///
+    /// Returns the recording written, if the capture had an output path.
+    fn stop_capture(&mut self) -> Option<Recording>;
+
+    /// Name of the shared memory the frames of monitor `id` are captured to.
+    fn shm_name(&self, id: Id) -> String;
+}
+
+/// Frames to capture, see [DisplayBackend::start_capture].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capture {
+    /// Monitors to capture, every monitor if empty.
+    pub monitor_ids: BTreeSet<Id>,
+    /// MP4 file to record the frames to, in addition to the shared memory.
+    pub output_path: Option<String>,
+    /// Frame rate of the recording, the backend's default if `None`.
+    pub fps: Option<u32>,
+}
+
+impl Capture {
+    /// Whether frames of monitor `id` are captured.
+    pub fn includes(&self, id: Id) -> bool {
+        self.monitor_ids.is_empty() || self.monitor_ids.contains(&id)
+    }
+}
+
+/// A finished recording, see [DisplayBackend::stop_capture].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Recording {
+    pub path: String,
+    pub frames: u64,
+    pub duration_ms: u64,
+}
+
+/// What handling a command results in.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Outcome {
+    /// Reply to the client which sent the command.
+    pub reply: Option<ReplyCommand>,
+    /// Event for all other clients.
+    pub event: Option<EventCommand>,
+}
+
+/// A monitor and what the OS knows about it
+#[derive(Debug, Clone)]
+struct MonitorState {
+    data: Monitor,
+    /// Whether the monitor arrived and did not depart since
+    connected: bool,
+    /// The modes which were reported in the monitor description on arrival
+    description_modes: Vec<Mode>,
+    /// The physical size in mm which was reported in the monitor description
+    /// on arrival
+    description_size: Option<(Dimen, Dimen)>,
+}
+
+/// Monitors and recording state of the driver, see the
+/// [module documentation](self).
+#[derive(Debug)]
+pub struct DriverCore<B> {
+    backend: B,
+    max_monitors: u32,
+    monitors: Vec<MonitorState>,
+    capture: Option<Capture>,
+}
+
+impl<B: DisplayBackend> DriverCore<B> {
+    /// Create a core without monitors, which allows at most `max_monitors` to
+    /// be enabled at the same time.
+    pub fn new(backend: B, max_monitors: u32) -> Self {
+        Self {
+            backend,
+            max_monitors,
+            monitors: Vec::new(),
+            capture: None,
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn backend_mut(&mut self) -> &mut B {
+        &mut self.backend
+    }
+
+    /// The current monitors, in the order they were notified.
+    pub fn monitors(&self) -> Vec<Monitor> {
+        self.monitors.iter().map(|m| m.data.clone()).collect()
+    }
+
+    /// Execute `command`.
+    pub fn handle(&mut self, command: DriverCommand) -> Outcome {
+        match command {
+            DriverCommand::Notify(monitors) => match self.notify(monitors) {
+                Ok(()) => Outcome {
+                    reply: Some(ReplyCommand::Notified),
+                    ..self.changed()
+                },
+                Err(rejection) => Outcome {
+                    reply: Some(ReplyCommand::Rejected(rejection)),
+                    event: None,
+                },
+            },
+
+            DriverCommand::Remove(ids) => {
+                self.remove(|monitor| ids.contains(&monitor.id));
+                self.changed()
+            }
+
+            DriverCommand::RemoveAll => {
+                self.remove(|_| true);
+                self.changed()
+            }
+
+            DriverCommand::StartRecording {
+                monitor_ids,
+                output_path,
+                fps,
+            } => {
+                let capture = Capture {
+                    monitor_ids: monitor_ids.into_iter().collect(),
+                    output_path,
+                    fps,
+                };
+
+                Outcome {
+                    reply: Some(self.start_recording(capture)),
+                    event: None,
+                }
+            }
+
+            DriverCommand::StopRecording => {
+                let recording = self
+                    .capture
+                    .take()
+                    .and_then(|_| self.backend.stop_capture())
+                    .unwrap_or_default();
+
+                Outcome {
+                    reply: Some(ReplyCommand::RecordingFinished {
+                        path: recording.path,
+                        frames: recording.frames,
+                        duration_ms: recording.duration_ms,
+                    }),
+                    event: None,
+                }
+            }
+
+            // not about displays, the driver handles it
+            DriverCommand::SetLogLevel { .. } => Outcome::default(),
+        }
+    }
+
+    /// Answer `request`.
+    ///
+    /// Returns `None` for requests which are not about monitors or recording.
+    pub fn request(&self, request: &RequestCommand) -> Option<ReplyCommand> {
+        let reply = match request {
+            RequestCommand::State => ReplyCommand::State(self.monitors()),
+
+            RequestCommand::RecordingState => {
+                let capture = self.capture.as_ref();
+                ReplyCommand::RecordingState {
+                    active: capture.is_some(),
+                    monitor_ids: capture
+                        .map(|c| c.monitor_ids.iter().copied().collect())
+                        .unwrap_or_default(),
+                    shm_names: self
+                        .monitors
+                        .iter()
+                        .filter(|m| capture.is_some_and(|c| c.includes(m.data.id)))
+                        .map(|m| self.backend.shm_name(m.data.id))
+                        .collect(),
+                }
+            }
+
+            RequestCommand::Capabilities => ReplyCommand::Capabilities(Capabilities {
+                max_monitors: self.max_monitors,
+            }),
+
+            _ => return None,
+        };
+
+        Some(reply)
+    }
+
+    fn changed(&self) -> Outcome {
+        Outcome {
+            reply: None,
+            event: Some(EventCommand::Changed(self.monitors())),
+        }
+    }
+
+    /// Adds, updates, or removes monitors as needed
+    ///
+    /// Mode changes are applied in place with [DisplayBackend::update_modes]
+    /// where possible. A monitor is only departed and arrived again if that is
+    /// required for the OS to see the changes, see [monitor_action].
+    ///
+    /// Nothing is changed if the monitors are rejected.
+    fn notify(&mut self, monitors: Vec<Monitor>) -> Result<(), Rejection> {
+        // Duplicated id's will not cause any issue, however duplicated resolutions/refresh rates are possible
+        // They should all be unique anyways. So reject the update if the sender sent incorrect data
+        if has_duplicates(&monitors) {
+            warn!("notify(): Duplicate data was detected; update aborted");
+            return Err(Rejection::Duplicates);
+        }
+
+        // creating a monitor fails for any monitor over the adapter limit, so
+        // reject the whole update instead of ending up with only some of the
+        // monitors connected
+        let enabled = monitors.iter().filter(|m| m.enabled).count();
+        if enabled > self.max_monitors as usize {
+            let rejection = Rejection::LimitExceeded {
+                enabled: u32::try_from(enabled).unwrap_or(u32::MAX),
+                max_monitors: self.max_monitors,
+            };
+            warn!("notify(): {rejection}; update aborted");
+            return Err(rejection);
+        }
+
+        // Remove monitors which are missing from the provided list
+        self.remove(|monitor| !monitors.iter().any(|m| m.id == monitor.id));
+
+        // keep the order the monitors were sent in
+        let mut previous = std::mem::take(&mut self.monitors);
+        let mut actions = Vec::with_capacity(monitors.len());
+        for monitor in monitors {
+            let index = previous.iter().position(|m| m.data.id == monitor.id);
+            let mut current = index.map(|i| previous.swap_remove(i));
+            let action = monitor_action(current.as_ref(), &monitor);
+
+            if let Some(current) = &mut current {
+                if matches!(action, MonitorAction::Depart | MonitorAction::Reattach) {
+                    depart(&mut self.backend, current);
+                }
+            }
+
+            self.monitors.push(match current {
+                Some(current) => MonitorState {
+                    data: monitor,
+                    ..current
+                },
+                None => MonitorState {
+                    data: monitor,
+                    connected: false,
+                    description_modes: Vec::new(),
+                    description_size: None,
+                },
+            });
+            actions.push(action);
+        }
+
+        // arrive only after every departure
+        for (monitor, action) in self.monitors.iter_mut().zip(actions) {
+            let id = monitor.data.id;
+
+            match action {
+                MonitorAction::Arrive | MonitorAction::Reattach => {
+                    arrive(&mut self.backend, monitor);
+                }
+
+                MonitorAction::UpdateModes => {
+                    if let Err(e) = self.backend.update_modes(&monitor.data) {
+                        // fall back to a full reattach
+                        warn!("Failed to update modes of monitor {id}, reattaching it: {e}");
+                        depart(&mut self.backend, monitor);
+                        arrive(&mut self.backend, monitor);
+                    }
+                }
+
+                MonitorAction::None | MonitorAction::Depart => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Depart and forget the monitors matching `f`
+    fn remove(&mut self, f: impl Fn(&Monitor) -> bool) {
+        let backend = &mut self.backend;
+        self.monitors.retain_mut(|monitor| {
+            if !f(&monitor.data) {
+                return true;
+            }
+
+            depart(backend, monitor);
+            false
+        });
+    }
+
+    fn start_recording(&mut self, capture: Capture) -> ReplyCommand {
+        // Stop any existing recording first
+        if self.capture.take().is_some() {
+            self.backend.stop_capture();
+        }
+
+        let active = match self.backend.start_capture(&capture) {
+            Ok(()) => true,
+            Err(e) => {
+                error!("Failed to start capture: {e}");
+                false
+            }
+        };
+
+        let reply = ReplyCommand::RecordingStarted {
+            active,
+            monitor_ids: capture.monitor_ids.iter().copied().collect(),
+            has_session: active && capture.output_path.is_some(),
+        };
+
+        if active {
+            self.capture = Some(capture);
+        }
+
+        reply
+    }
+}
+
+fn arrive(backend: &mut impl DisplayBackend, monitor: &mut MonitorState) {
+    if let Err(e) = backend.arrive(&monitor.data) {
+        error!("Failed to create monitor: {e}");
+        return;
+    }
+
+    monitor.connected = true;
+    monitor.description_modes.clone_from(&monitor.data.modes);
+    monitor.description_size = monitor.data.physical_size_mm();
+}
+
+fn depart(backend: &mut impl DisplayBackend, monitor: &mut MonitorState) {
+    if !monitor.connected {
+        return;
+    }
+
+    monitor.connected = false;
+    if let Err(e) = backend.depart(monitor.data.id) {
+        error!("Failed to remove monitor: {e}");
+    }
+}
+
+/// used to check the validity of a Vec<Monitor>
+/// the validity invariants are:
+/// 1. unique monitor ids
+/// 2. unique monitor modes (width+height must be unique per array element)
+/// 3. unique refresh rates per monitor mode
+fn has_duplicates(monitors: &[Monitor]) -> bool {
+    let mut monitor_iter = monitors.iter();
+    while let Some(monitor) = monitor_iter.next() {
+        let duplicate_id = monitor_iter.clone().any(|b| monitor.id == b.id);
+        if duplicate_id {
+            warn!("Found duplicate monitor id {}", monitor.id);
+            return true;
+        }
+
+        let mut mode_iter = monitor.modes.iter();
+        while let Some(mode) = mode_iter.next() {
+            let duplicate_mode = mode_iter
+                .clone()
+                .any(|m| mode.height == m.height && mode.width == m.width);
+            if duplicate_mode {
+                warn!(
+                    "Found duplicate mode {}x{} on monitor {}",
+                    mode.width, mode.height, monitor.id
+                );
+                return true;
+            }
+
+            let mut refresh_iter = mode.refresh_rates.iter().copied();
+            while let Some(rr) = refresh_iter.next() {
+                let duplicate_rr = refresh_iter.clone().any(|r| rr == r);
+                if duplicate_rr {
+                    warn!(
+                        "Found duplicate refresh rate {rr} on mode {}x{} for monitor {}",
+                        mode.width, mode.height, monitor.id
+                    );
+                    return true;
+                }
+            }
+        }
+    }
+
+    false
+}
+
+/// The action required to bring a monitor to its newly requested state
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum MonitorAction {
+    /// Nothing the OS can see has changed
+    None,
+    /// Create the monitor and tell the OS it arrived
+    Arrive,
+    /// Tell the OS the monitor departed
+    Depart,
+    /// Keep the monitor connected and replace its target modes
+    UpdateModes,
+    /// Depart the monitor and arrive it again
+    Reattach,
+}
+
+/// Decide which action is required to go from `current` to `new`
+///
+/// `current` is the existing entry for the monitor, or `None` if the monitor
+/// is new.
+///
+/// The OS only reads the monitor description (and with it the monitor modes)
+/// on arrival. Target modes can be updated in place, so a mode change only
+/// requires a reattach if it adds a mode which was not part of the monitor
+/// description. A changed physical size, orientation or color always requires
+/// a reattach.
+fn monitor_action(current: Option<&MonitorState>, new: &Monitor) -> MonitorAction {
+    let Some(current) = current else {
+        return if new.enabled {
+            MonitorAction::Arrive
+        } else {
+            MonitorAction::None
+        };
+    };
+
+    if !new.enabled {
+        return if current.connected {
+            MonitorAction::Depart
+        } else {
+            MonitorAction::None
+        };
+    }
+
+    // enabled, but currently disabled or disconnected
+    if !current.connected {
+        return MonitorAction::Arrive;
+    }
+
+    // the size and color are part of the EDID, and the orientation changes all
+    // described modes
+    if current.description_size != new.physical_size_mm()
+        || current.data.orientation != new.orientation
+        || current.data.color != new.color
+    {
+        return MonitorAction::Reattach;
+    }
+
+    if current.data.modes == new.modes {
+        return MonitorAction::None;
+    }
+
+    let described = current.description_modes.flatten().collect::<Vec<_>>();
+    let is_subset =
+        !new.modes.is_empty() && new.modes.flatten().all(|mode| described.contains(&mode));
+
+    if is_subset {
+        MonitorAction::UpdateModes
+    } else {
+        MonitorAction::Reattach
+    }
+}
+
+pub trait FlattenModes {
+    fn flatten(&self) -> impl Iterator<Item = ModeItem>;
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ModeItem {
+    pub width: Dimen,
+    pub height: Dimen,
+    pub refresh_rate: RefreshRate,
+}
+
+/// Takes a slice of modes and creates a flattened structure that can be iterated over
+impl FlattenModes for Vec<Mode> {
+    fn flatten(&self) -> impl Iterator<Item = ModeItem> {
+        self.iter().flat_map(|m| {
+            m.refresh_rates.iter().map(|&rr| ModeItem {
+                width: m.width,
+                height: m.height,
+                refresh_rate: rr,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitDepth, Color, Hdr, Orientation, PhysicalSize};
+
+    fn mode(width: Dimen, height: Dimen, refresh_rates: &[RefreshRate]) -> Mode {
+        Mode {
+            width,
+            height,
+            refresh_rates: refresh_rates.to_vec(),
+        }
+    }
+
+    fn monitor(enabled: bool, modes: Vec<Mode>) -> Monitor {
+        Monitor {
+            id: 0,
+            enabled,
+            modes,
+            ..Default::default()
+        }
+    }
+
+    fn with_id(id: Id, enabled: bool) -> Monitor {
+        Monitor {
+            id,
+            ..monitor(enabled, vec![mode(1920, 1080, &[60, 120])])
+        }
+    }
+
+    fn connected(data: Monitor) -> MonitorState {
+        MonitorState {
+            connected: true,
+            description_modes: data.modes.clone(),
+            description_size: data.physical_size_mm(),
+            data,
+        }
+    }
+
+    fn disconnected(data: Monitor) -> MonitorState {
+        MonitorState {
+            data,
+            connected: false,
+            description_modes: Vec::new(),
+            description_size: None,
+        }
+    }
+
+    fn core() -> DriverCore<MemoryBackend> {
+        DriverCore::new(MemoryBackend::new(), 4)
+    }
+
+    /// Notify `monitors` and return the backend calls it made
+    fn notify(core: &mut DriverCore<MemoryBackend>, monitors: Vec<Monitor>) -> Vec<BackendCall> {
+        let outcome = core.handle(DriverCommand::Notify(monitors.clone()));
+        assert_eq!(outcome.reply, Some(ReplyCommand::Notified));
+        assert_eq!(outcome.event, Some(EventCommand::Changed(monitors)));
+
+        core.backend_mut().take_calls()
+    }
+
+    fn start_recording(monitor_ids: Vec<Id>, output_path: Option<&str>) -> DriverCommand {
+        DriverCommand::StartRecording {
+            monitor_ids,
+            output_path: output_path.map(ToOwned::to_owned),
+            fps: None,
+        }
+    }
+
+    #[test]
+    fn new_monitor_arrives_only_when_enabled() {
+        let modes = vec![mode(1920, 1080, &[60])];
+
+        assert_eq!(
+            monitor_action(None, &monitor(true, modes.clone())),
+            MonitorAction::Arrive
+        );
+        assert_eq!(
+            monitor_action(None, &monitor(false, modes)),
+            MonitorAction::None
+        );
+    }
+
+    #[test]
+    fn enable_and_disable() {
+        let modes = vec![mode(1920, 1080, &[60])];
+
+        let current = disconnected(monitor(false, modes.clone()));
+        assert_eq!(
+            monitor_action(Some(&current), &monitor(true, modes.clone())),
+            MonitorAction::Arrive
+        );
+
+        let current = connected(monitor(true, modes.clone()));
+        assert_eq!(
+            monitor_action(Some(&current), &monitor(false, modes.clone())),
+            MonitorAction::Depart
+        );
+
+        let current = disconnected(monitor(false, modes.clone()));
+        assert_eq!(
+            monitor_action(Some(&current), &monitor(false, modes)),
+            MonitorAction::None
+        );
+    }
+
+    #[test]
+    fn name_change_is_not_visible() {
+        let modes = vec![mode(1920, 1080, &[60])];
+
+        let current = connected(monitor(true, modes.clone()));
+        let mut new = monitor(true, modes);
+        new.name = Some("renamed".to_owned());
+
+        assert_eq!(monitor_action(Some(&current), &new), MonitorAction::None);
+    }
+
+    #[test]
+    fn removing_modes_updates_in_place() {
+        let current = connected(monitor(
+            true,
+            vec![mode(1920, 1080, &[60, 120]), mode(1280, 720, &[60])],
+        ));
+
+        assert_eq!(
+            monitor_action(
+                Some(&current),
+                &monitor(true, vec![mode(1920, 1080, &[120])])
+            ),
+            MonitorAction::UpdateModes
+        );
+        assert_eq!(
+            monitor_action(Some(&current), &monitor(true, vec![mode(1280, 720, &[60])])),
+            MonitorAction::UpdateModes
+        );
+    }
+
+    #[test]
+    fn restoring_described_modes_updates_in_place() {
+        let mut current = connected(monitor(
+            true,
+            vec![mode(1920, 1080, &[60, 120]), mode(1280, 720, &[60])],
+        ));
+        // a previous update removed a mode
+        current.data.modes = vec![mode(1920, 1080, &[60])];
+
+        assert_eq!(
+            monitor_action(
+                Some(&current),
+                &monitor(
+                    true,
+                    vec![mode(1920, 1080, &[60, 120]), mode(1280, 720, &[60])]
+                )
+            ),
+            MonitorAction::UpdateModes
+        );
+    }
+
+    #[test]
+    fn adding_modes_reattaches() {
+        let current = connected(monitor(true, vec![mode(1920, 1080, &[60])]));
+
+        assert_eq!(
+            monitor_action(
+                Some(&current),
+                &monitor(true, vec![mode(1920, 1080, &[60, 120])])
+            ),
+            MonitorAction::Reattach
+        );
+        assert_eq!(
+            monitor_action(
+                Some(&current),
+                &monitor(true, vec![mode(1920, 1080, &[60]), mode(3840, 2160, &[60])])
+            ),
+            MonitorAction::Reattach
+        );
+    }
+
+    #[test]
+    fn removing_all_modes_reattaches() {
+        let current = connected(monitor(true, vec![mode(1920, 1080, &[60])]));
+
+        assert_eq!(
+            monitor_action(Some(&current), &monitor(true, Vec::new())),
+            MonitorAction::Reattach
+        );
+    }
+
+    #[test]
+    fn physical_size_change_reattaches() {
+        let modes = vec![mode(3840, 2160, &[60]), mode(1920, 1080, &[60])];
+        let mut data = monitor(true, modes.clone());
+        data.physical_size = Some(PhysicalSize::Dpi(163));
+        let current = connected(data.clone());
+
+        assert_eq!(monitor_action(Some(&current), &data), MonitorAction::None);
+
+        let mut new = data.clone();
+        new.physical_size = Some(PhysicalSize::Dpi(96));
+        assert_eq!(monitor_action(Some(&current), &new), MonitorAction::Reattach);
+
+        let mut new = data.clone();
+        new.physical_size = None;
+        assert_eq!(monitor_action(Some(&current), &new), MonitorAction::Reattach);
+
+        // with a DPI, the size follows the preferred mode
+        let mut new = data;
+        new.modes = vec![modes[1].clone()];
+        assert_eq!(monitor_action(Some(&current), &new), MonitorAction::Reattach);
+    }
+
+    #[test]
+    fn orientation_change_reattaches() {
+        let modes = vec![mode(1080, 1920, &[60])];
+        let current = connected(monitor(true, modes.clone()));
+
+        let mut new = monitor(true, modes);
+        new.orientation = Orientation::Portrait;
+        assert_eq!(monitor_action(Some(&current), &new), MonitorAction::Reattach);
+    }
+
+    #[test]
+    fn color_change_reattaches() {
+        let modes = vec![mode(3840, 2160, &[60])];
+        let current = connected(monitor(true, modes.clone()));
+
+        let mut new = monitor(true, modes);
+        new.color.bit_depth = BitDepth::Ten;
+        assert_eq!(monitor_action(Some(&current), &new), MonitorAction::Reattach);
+
+        new.color = Color::default();
+        new.color.hdr = Some(Hdr::default());
+        assert_eq!(monitor_action(Some(&current), &new), MonitorAction::Reattach);
+    }
+
+    #[test]
+    fn notify_diffs_against_current_state() {
+        let mut core = core();
+
+        let calls = notify(&mut core, vec![with_id(1, true), with_id(2, false)]);
+        assert_eq!(calls, [BackendCall::Arrive(1)]);
+
+        // enable 2, reattach 1 for a new mode, drop nothing
+        let mut one = with_id(1, true);
+        one.modes.push(mode(3840, 2160, &[60]));
+        let calls = notify(&mut core, vec![with_id(2, true), one.clone()]);
+        assert_eq!(
+            calls,
+            [
+                BackendCall::Depart(1),
+                BackendCall::Arrive(2),
+                BackendCall::Arrive(1)
+            ]
+        );
+
+        // removing a mode is done in place, a missing monitor departs
+        let calls = notify(&mut core, vec![with_id(1, true)]);
+        assert_eq!(calls, [BackendCall::Depart(2), BackendCall::UpdateModes(1)]);
+
+        // renaming is invisible to the OS
+        let mut renamed = with_id(1, true);
+        renamed.name = Some("renamed".to_owned());
+        assert_eq!(notify(&mut core, vec![renamed.clone()]), []);
+
+        assert_eq!(core.monitors(), [renamed]);
+        assert_eq!(core.backend().connected().keys().collect::<Vec<_>>(), [&1]);
+    }
+
+    #[test]
+    fn failed_mode_update_reattaches() {
+        let mut core = core();
+        notify(&mut core, vec![with_id(1, true)]);
+
+        core.backend_mut().fail_update_modes(true);
+        let mut one = with_id(1, true);
+        one.modes = vec![mode(1920, 1080, &[60])];
+        let calls = notify(&mut core, vec![one.clone()]);
+        assert_eq!(
+            calls,
+            [
+                BackendCall::UpdateModes(1),
+                BackendCall::Depart(1),
+                BackendCall::Arrive(1)
+            ]
+        );
+
+        // the monitor was described again with the reduced modes
+        core.backend_mut().fail_update_modes(false);
+        let calls = notify(&mut core, vec![with_id(1, true)]);
+        assert_eq!(calls, [BackendCall::Depart(1), BackendCall::Arrive(1)]);
+    }
+
+    #[test]
+    fn invalid_notify_is_rejected() {
+        let mut core = core();
+        notify(&mut core, vec![with_id(1, true)]);
+
+        let duplicate_ids = vec![with_id(2, true), with_id(2, false)];
+        let mut duplicate_modes = with_id(3, true);
+        duplicate_modes.modes.push(mode(1920, 1080, &[30]));
+        let mut duplicate_rates = with_id(3, true);
+        duplicate_rates.modes = vec![mode(1920, 1080, &[60, 60])];
+        let too_many = (1..=5).map(|id| with_id(id, true)).collect();
+
+        let limit = Rejection::LimitExceeded {
+            enabled: 5,
+            max_monitors: 4,
+        };
+
+        for (monitors, rejection) in [
+            (duplicate_ids, Rejection::Duplicates),
+            (vec![duplicate_modes], Rejection::Duplicates),
+            (vec![duplicate_rates], Rejection::Duplicates),
+            (too_many, limit),
+        ] {
+            let outcome = core.handle(DriverCommand::Notify(monitors));
+            assert_eq!(outcome.reply, Some(ReplyCommand::Rejected(rejection)));
+            assert_eq!(outcome.event, None);
+            assert_eq!(core.backend_mut().take_calls(), []);
+            assert_eq!(core.monitors(), [with_id(1, true)]);
+        }
+
+        // disabled monitors don't count towards the limit
+        let mut monitors = (1..=4).map(|id| with_id(id, true)).collect::<Vec<_>>();
+        monitors.push(with_id(5, false));
+        assert_eq!(notify(&mut core, monitors).len(), 3);
+    }
+
+    #[test]
+    fn remove_departs_connected_monitors() {
+        let mut core = core();
+        notify(
+            &mut core,
+            vec![with_id(1, true), with_id(2, false), with_id(3, true)],
+        );
+
+        let outcome = core.handle(DriverCommand::Remove(vec![2, 3, 4]));
+        assert_eq!(outcome.event, Some(EventCommand::Changed(vec![with_id(1, true)])));
+        assert_eq!(core.backend_mut().take_calls(), [BackendCall::Depart(3)]);
+
+        let outcome = core.handle(DriverCommand::RemoveAll);
+        assert_eq!(outcome.event, Some(EventCommand::Changed(Vec::new())));
+        assert_eq!(core.backend_mut().take_calls(), [BackendCall::Depart(1)]);
+        assert!(core.backend().connected().is_empty());
+    }
+
+    #[test]
+    fn recording_state_machine() {
+        let mut core = core();
+        notify(&mut core, vec![with_id(1, true), with_id(2, true)]);
+
+        let idle = ReplyCommand::RecordingState {
+            active: false,
+            monitor_ids: Vec::new(),
+            shm_names: Vec::new(),
+        };
+        assert_eq!(core.request(&RequestCommand::RecordingState), Some(idle.clone()));
+
+        // stopping without a recording still replies
+        let outcome = core.handle(DriverCommand::StopRecording);
+        assert_eq!(
+            outcome.reply,
+            Some(ReplyCommand::RecordingFinished {
+                path: String::new(),
+                frames: 0,
+                duration_ms: 0,
+            })
+        );
+        assert_eq!(core.backend_mut().take_calls(), []);
+
+        // capture all monitors to shared memory only
+        let outcome = core.handle(start_recording(Vec::new(), None));
+        assert_eq!(
+            outcome.reply,
+            Some(ReplyCommand::RecordingStarted {
+                active: true,
+                monitor_ids: Vec::new(),
+                has_session: false,
+            })
+        );
+        assert_eq!(
+            core.request(&RequestCommand::RecordingState),
+            Some(ReplyCommand::RecordingState {
+                active: true,
+                monitor_ids: Vec::new(),
+                shm_names: vec!["frames-1".to_owned(), "frames-2".to_owned()],
+            })
+        );
+
+        // starting again replaces the running capture
+        core.backend_mut().take_calls();
+        let outcome = core.handle(start_recording(vec![2, 2], Some("out.mp4")));
+        assert_eq!(
+            outcome.reply,
+            Some(ReplyCommand::RecordingStarted {
+                active: true,
+                monitor_ids: vec![2],
+                has_session: true,
+            })
+        );
+        let capture = Capture {
+            monitor_ids: [2].into(),
+            output_path: Some("out.mp4".to_owned()),
+            fps: None,
+        };
+        assert_eq!(
+            core.backend_mut().take_calls(),
+            [
+                BackendCall::StopCapture,
+                BackendCall::StartCapture(capture.clone())
+            ]
+        );
+        assert_eq!(core.backend().capture(), Some(&capture));
+        assert_eq!(
+            core.request(&RequestCommand::RecordingState),
+            Some(ReplyCommand::RecordingState {
+                active: true,
+                monitor_ids: vec![2],
+                shm_names: vec!["frames-2".to_owned()],
+            })
+        );
+
+        let outcome = core.handle(DriverCommand::StopRecording);
+        assert_eq!(
+            outcome.reply,
+            Some(ReplyCommand::RecordingFinished {
+                path: "out.mp4".to_owned(),
+                frames: 0,
+                duration_ms: 0,
+            })
+        );
+        assert_eq!(core.backend_mut().take_calls(), [BackendCall::StopCapture]);
+        assert_eq!(core.request(&RequestCommand::RecordingState), Some(idle));
+    }
+
+    #[test]
+    fn requests() {
+        let mut core = core();
+        notify(&mut core, vec![with_id(1, true), with_id(2, false)]);
+
+        assert_eq!(
+            core.request(&RequestCommand::State),
+            Some(ReplyCommand::State(vec![with_id(1, true), with_id(2, false)]))
+        );
+        assert_eq!(
+            core.request(&RequestCommand::Capabilities),
+            Some(ReplyCommand::Capabilities(Capabilities { max_monitors: 4 }))
+        );
+        assert_eq!(core.request(&RequestCommand::Config), None);
+
+        let outcome = core.handle(DriverCommand::SetLogLevel {
+            level: crate::LogLevel::Debug,
+            targets: std::collections::BTreeMap::new(),
+        });
+        assert_eq!(outcome, Outcome::default());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::BTreeMap;
+
+use super::{Capture, DisplayBackend, Recording};
+use crate::{Id, Monitor};
+
+/// A call made to a [MemoryBackend]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendCall {
+    Arrive(Id),
+    Depart(Id),
+    UpdateModes(Id),
+    StartCapture(Capture),
+    StopCapture,
+}
+
+/// A [DisplayBackend] which only records what it is asked to do.
+///
+/// Useful for tests and dry runs.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    calls: Vec<BackendCall>,
+    connected: BTreeMap<Id, Monitor>,
+    capture: Option<Capture>,
+    fail_update_modes: bool,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The calls made since the last time they were taken
+    pub fn take_calls(&mut self) -> Vec<BackendCall> {
+        std::mem::take(&mut self.calls)
+    }
+
+    /// The monitors which arrived and did not depart since
+    pub fn connected(&self) -> &BTreeMap<Id, Monitor> {
+        &self.connected
+    }
+
+    pub fn capture(&self) -> Option<&Capture> {
+        self.capture.as_ref()
+    }
+
+    /// Let [DisplayBackend::update_modes] fail, like the OS may
+    pub fn fail_update_modes(&mut self, fail: bool) {
+        self.fail_update_modes = fail;
+    }
+}
+
+impl DisplayBackend for MemoryBackend {
+    type Error = String;
+
+    fn arrive(&mut self, monitor: &Monitor) -> Result<(), Self::Error> {
+        self.calls.push(BackendCall::Arrive(monitor.id));
+
+        if self.connected.contains_key(&monitor.id) {
+            return Err(format!("monitor {} already arrived", monitor.id));
+        }
+
+        self.connected.insert(monitor.id, monitor.clone());
+        Ok(())
+    }
+
+    fn depart(&mut self, id: Id) -> Result<(), Self::Error> {
+        self.calls.push(BackendCall::Depart(id));
+
+        match self.connected.remove(&id) {
+            Some(_) => Ok(()),
+            None => Err(format!("monitor {id} is not connected")),
+        }
+    }
+
+    fn update_modes(&mut self, monitor: &Monitor) -> Result<(), Self::Error> {
+        self.calls.push(BackendCall::UpdateModes(monitor.id));
+
+        if self.fail_update_modes {
+            return Err("updating modes failed".to_owned());
+        }
+
+        let Some(connected) = self.connected.get_mut(&monitor.id) else {
+            return Err(format!("monitor {} is not connected", monitor.id));
+        };
+        connected.modes.clone_from(&monitor.modes);
+
+        Ok(())
+    }
+
+    fn start_capture(&mut self, capture: &Capture) -> Result<(), Self::Error> {
+        self.calls.push(BackendCall::StartCapture(capture.clone()));
+        self.capture = Some(capture.clone());
+
+        Ok(())
+    }
+
+    fn stop_capture(&mut self) -> Option<Recording> {
+        self.calls.push(BackendCall::StopCapture);
+
+        let path = self.capture.take()?.output_path?;
+        Some(Recording {
+            path,
+            ..Recording::default()
+        })
+    }
+
+    fn shm_name(&self, id: Id) -> String {
+        format!("frames-{id}")
+    }
+}
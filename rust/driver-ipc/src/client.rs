@@ -10,10 +10,11 @@ use tokio::{
 };
 use tokio_stream::{Stream, StreamExt};
 
+use crate::persist::Store;
 use crate::*;
 
-// EOF byte used to separate messages
-pub(crate) const EOF: u8 = 0x4;
+/// How long to wait for the reply to a request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
 
 /// Client for interacting with the Virtual Display Driver.
 ///
@@ -32,6 +33,7 @@ pub struct Client {
 #[derive(Debug)]
 struct _Shared {
     client: named_pipe::NamedPipeClient,
+    encoding: Encoding,
     abort_receiver: Notify,
     receive_error: RwLock<Option<Arc<io::Error>>>,
 }
@@ -52,16 +54,42 @@ impl Client {
     ///
     /// This method is async because it requires a running tokio reactor.
     pub async fn connect_to(name: &str) -> Result<Self, error::ConnectionError> {
+        Self::connect_with_encoding(name, Encoding::Json).await
+    }
+
+    /// Connect to driver on pipe with specified name, and ask it to encode
+    /// all messages with `encoding`, see [codec].
+    ///
+    /// Falls back to JSON if the driver does not support `encoding`. Returns
+    /// [error::ConnectionError::Timeout] if the driver does not answer within
+    /// 5 seconds, which drivers from before encodings were added never do.
+    ///
+    /// This method is async because it requires a running tokio reactor.
+    pub async fn connect_with_encoding(
+        name: &str,
+        encoding: Encoding,
+    ) -> Result<Self, error::ConnectionError> {
+        if !encoding.is_supported() {
+            return Err(error::ConnectionError::Unsupported(encoding));
+        }
+
         let client = named_pipe::ClientOptions::new()
             .read(true)
             .write(true)
             .pipe_mode(named_pipe::PipeMode::Byte)
             .open(format!(r"\\.\pipe\{name}"))?;
 
+        let (encoding, recv_buf) = if encoding == Encoding::Json {
+            (encoding, Vec::new())
+        } else {
+            negotiate(&client, encoding).await?
+        };
+
         let abort_receiver = Notify::new();
 
         let shared = Arc::new(_Shared {
             client,
+            encoding,
             abort_receiver,
             receive_error: RwLock::new(None),
         });
@@ -72,7 +100,14 @@ impl Client {
         {
             let shared = shared.clone();
             task::spawn(async move {
-                let r = receive_command(&shared.client, &command_tx, &shared.abort_receiver).await;
+                let r = receive_command(
+                    &shared.client,
+                    shared.encoding,
+                    recv_buf,
+                    &command_tx,
+                    &shared.abort_receiver,
+                )
+                .await;
                 if let Err(e) = r {
                     let error = Arc::new(e);
                     shared.receive_error.write().await.replace(error.clone());
@@ -84,19 +119,35 @@ impl Client {
         Ok(Self { shared, command_rx })
     }
 
-    /// Send new state to the driver.
-    pub async fn notify(&self, monitors: &[Monitor]) -> Result<(), error::SendError> {
+    /// Encoding of all messages on this connection.
+    pub fn encoding(&self) -> Encoding {
+        self.shared.encoding
+    }
+
+    /// Send new state to the driver, and wait until it applied it.
+    ///
+    /// Returns [error::RequestError::Rejected] if the driver kept the previous
+    /// state instead, e.g. because more monitors are enabled than
+    /// [Capabilities::max_monitors]. Returns [error::RequestError::Timeout] if
+    /// the driver does not answer within 5 seconds, which drivers from before
+    /// [ReplyCommand::Notified] was added never do.
+    pub async fn notify(&self, monitors: &[Monitor]) -> Result<(), error::RequestError> {
         let command = DriverCommand::Notify(monitors.to_owned());
 
-        send_command(&self.shared.client, &command).await?;
-        Ok(())
+        self.request(command, |reply| match reply {
+            ReplyCommand::Notified => Some(Ok(())),
+            ReplyCommand::Rejected(rejection) => Some(Err(rejection)),
+            _ => None,
+        })
+        .await?
+        .map_err(error::RequestError::Rejected)
     }
 
     /// Remove all monitors with the specified IDs.
     pub async fn remove(&self, ids: &[Id]) -> Result<(), error::SendError> {
         let command = DriverCommand::Remove(ids.to_owned());
 
-        send_command(&self.shared.client, &command).await?;
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
         Ok(())
     }
 
@@ -104,7 +155,7 @@ impl Client {
     pub async fn remove_all(&self) -> Result<(), error::SendError> {
         let command = DriverCommand::RemoveAll;
 
-        send_command(&self.shared.client, &command).await?;
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
         Ok(())
     }
 
@@ -114,7 +165,7 @@ impl Client {
     pub async fn start_recording(&self, monitor_ids: Vec<Id>) -> Result<(), error::SendError> {
         let command = DriverCommand::StartRecording { monitor_ids, output_path: None, fps: None };
 
-        send_command(&self.shared.client, &command).await?;
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
         Ok(())
     }
 
@@ -133,7 +184,7 @@ impl Client {
             fps: Some(fps),
         };
 
-        send_command(&self.shared.client, &command).await?;
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
         Ok(())
     }
 
@@ -141,7 +192,7 @@ impl Client {
     pub async fn stop_recording(&self) -> Result<(), error::SendError> {
         let command = DriverCommand::StopRecording;
 
-        send_command(&self.shared.client, &command).await?;
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
         Ok(())
     }
 
@@ -152,84 +203,197 @@ impl Client {
     pub async fn request_recording_state(
         &self,
     ) -> Result<(bool, Vec<Id>, Vec<String>), error::RequestError> {
-        use broadcast::error::RecvError;
+        self.request(RequestCommand::RecordingState, |reply| match reply {
+            ReplyCommand::RecordingState {
+                active,
+                monitor_ids,
+                shm_names,
+            } => Some((active, monitor_ids, shm_names)),
+            _ => None,
+        })
+        .await
+    }
 
-        let mut rx = self.command_rx.resubscribe();
+    /// Request the current state of the driver.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_state(&self) -> Result<Vec<Monitor>, error::RequestError> {
+        self.request(RequestCommand::State, |reply| match reply {
+            ReplyCommand::State(monitors) => Some(monitors),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Request the capabilities of the virtual display adapter.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_capabilities(&self) -> Result<Capabilities, error::RequestError> {
+        self.request(RequestCommand::Capabilities, |reply| match reply {
+            ReplyCommand::Capabilities(capabilities) => Some(capabilities),
+            _ => None,
+        })
+        .await
+    }
 
-        send_command(&self.shared.client, &RequestCommand::RecordingState).await?;
+    /// Request the effective configuration of the driver.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_config(&self) -> Result<DriverConfig, error::RequestError> {
+        self.request(RequestCommand::Config, |reply| match reply {
+            ReplyCommand::Config(config) => Some(config),
+            _ => None,
+        })
+        .await
+    }
 
-        let fut = async {
-            loop {
-                match rx.recv().await {
-                    Ok(Ok(ClientCommand::Reply(ReplyCommand::RecordingState {
-                        active,
-                        monitor_ids,
-                        shm_names,
-                    }))) => break Ok((active, monitor_ids, shm_names)),
-                    Ok(Err(e)) => break Err(error::RequestError::Receive(e.0.clone())),
-                    Ok(_) => continue,
-                    Err(RecvError::Lagged(_n)) => continue,
-                    Err(RecvError::Closed) => {
-                        match self.shared.receive_error.read().await.as_ref() {
-                            Some(e) => break Err(error::RequestError::Receive(e.clone())),
-                            None => {
-                                break Err(error::RequestError::Receive(Arc::new(io::Error::new(
-                                    io::ErrorKind::BrokenPipe,
-                                    "Pipe closed",
-                                ))))
-                            }
-                        }
-                    }
-                }
-            }
-        };
+    /// Request the frame and swap chain counters of the driver.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn request_stats(&self) -> Result<Stats, error::RequestError> {
+        self.request(RequestCommand::Stats, |reply| match reply {
+            ReplyCommand::Stats(stats) => Some(stats),
+            _ => None,
+        })
+        .await
+    }
 
-        match timeout(Duration::from_secs(5), fut).await {
-            Ok(result) => result,
-            Err(_) => Err(error::RequestError::Timeout(Duration::from_secs(5))),
-        }
+    /// Request build and liveness information of the driver. Use it to check
+    /// that the driver is up and responding.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub async fn health(&self) -> Result<Health, error::RequestError> {
+        self.request(RequestCommand::Health, |reply| match reply {
+            ReplyCommand::Health(health) => Some(health),
+            _ => None,
+        })
+        .await
     }
 
-    /// Request the current state of the driver.
+    /// Request the access this client has to the driver.
+    ///
+    /// Commands which need more are refused with
+    /// [ReplyCommand::PermissionDenied].
     ///
-    /// Returns [IpcError::Timeout] if the driver does not respond within 5
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
     /// seconds.
-    pub async fn request_state(&self) -> Result<Vec<Monitor>, error::RequestError> {
+    pub async fn access(&self) -> Result<Access, error::RequestError> {
+        self.request(RequestCommand::Access, |reply| match reply {
+            ReplyCommand::Access(access) => Some(access),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Request the recent records of the driver log, oldest first.
+    ///
+    /// Only records at least as severe as `level` and newer than the record
+    /// with sequence number `since` are returned. The driver keeps a limited
+    /// amount of records, older ones are lost.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds, and [error::RequestError::PermissionDenied] without
+    /// [Access::Control].
+    pub async fn request_logs(
+        &self,
+        since: Option<u64>,
+        level: Option<LogLevel>,
+    ) -> Result<Vec<LogRecord>, error::RequestError> {
+        self.request(RequestCommand::Logs { since, level }, |reply| match reply {
+            ReplyCommand::Logs(records) => Some(records),
+            _ => None,
+        })
+        .await
+    }
+
+    /// Send `command` and wait for the first reply `pick` accepts.
+    ///
+    /// Returns [error::RequestError::PermissionDenied] if the driver refuses
+    /// the command, and [error::RequestError::Timeout] if it does not respond
+    /// within [REQUEST_TIMEOUT].
+    async fn request<T>(
+        &self,
+        command: impl Into<ServerCommand>,
+        pick: impl Fn(ReplyCommand) -> Option<T>,
+    ) -> Result<T, error::RequestError> {
         use broadcast::error::RecvError;
 
+        let command = command.into();
+        let name = command.name();
+
         let mut rx = self.command_rx.resubscribe();
 
-        send_command(&self.shared.client, &RequestCommand::State).await?;
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
 
         let fut = async {
             loop {
-                match rx.recv().await {
-                    Ok(Ok(ClientCommand::Reply(ReplyCommand::State(monitors)))) => {
-                        break Ok(monitors)
-                    }
-                    Ok(Err(e)) => break Err(error::RequestError::Receive(e.0.clone())),
-                    Ok(_) => continue,
-                    Err(RecvError::Lagged(_n)) => continue,
-                    Err(RecvError::Closed) => match self.shared.receive_error.read().await.as_ref()
-                    {
-                        Some(e) => break Err(error::RequestError::Receive(e.clone())),
-                        None => {
-                            break Err(error::RequestError::Receive(Arc::new(io::Error::new(
+                let reply = match rx.recv().await {
+                    Ok(Ok(ClientCommand::Reply(reply))) => reply,
+                    Ok(Ok(_)) | Err(RecvError::Lagged(_)) => continue,
+                    Ok(Err(e)) => break Err(error::RequestError::Receive(e.0)),
+                    Err(RecvError::Closed) => {
+                        let e = match self.shared.receive_error.read().await.as_ref() {
+                            Some(e) => e.clone(),
+                            None => Arc::new(io::Error::new(
                                 io::ErrorKind::BrokenPipe,
                                 "Pipe closed",
-                            ))))
+                            )),
+                        };
+                        break Err(error::RequestError::Receive(e));
+                    }
+                };
+
+                match reply {
+                    ReplyCommand::PermissionDenied(denied) if denied.command == name => {
+                        break Err(error::RequestError::PermissionDenied(denied));
+                    }
+                    reply => {
+                        if let Some(value) = pick(reply) {
+                            break Ok(value);
                         }
-                    },
+                    }
                 }
             }
         };
 
-        match timeout(Duration::from_secs(5), fut).await {
+        match timeout(REQUEST_TIMEOUT, fut).await {
             Ok(result) => result,
-            Err(_) => Err(error::RequestError::Timeout(Duration::from_secs(5))),
+            Err(_) => Err(error::RequestError::Timeout(REQUEST_TIMEOUT)),
         }
     }
 
+    /// Stream new records of the driver log which are at least as severe as
+    /// `level`, or stop streaming them with `None`.
+    ///
+    /// The records arrive as [EventCommand::Log] in [Client::receive_events].
+    /// The subscription is shared between all copies of this client.
+    pub async fn subscribe_logs(&self, level: Option<LogLevel>) -> Result<(), error::SendError> {
+        let command = RequestCommand::SubscribeLogs(level);
+
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
+        Ok(())
+    }
+
+    /// Change which records the driver logs, both to its loggers and to
+    /// [Client::request_logs].
+    ///
+    /// The filter lasts until the driver restarts, unless
+    /// [DriverConfig::persist_log_filter] is set.
+    pub async fn set_log_filter(&self, filter: LogFilter) -> Result<(), error::SendError> {
+        let command = DriverCommand::SetLogLevel {
+            level: filter.level,
+            targets: filter.targets,
+        };
+
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
+        Ok(())
+    }
+
     /// Receive continuous events from the driver.
     ///
     /// Only new events after calling this method are received.
@@ -251,36 +415,49 @@ impl Client {
         })
     }
 
-    /// Write `monitors` to the registry for current user.
+    /// Only receive the events matching `filter` from the driver, and
+    /// receive them as a stream.
     ///
-    /// Next time the driver is started, it will load this state from the
-    /// registry. This might be after a reboot or a driver restart.
-    pub fn persist(monitors: &[Monitor]) -> Result<(), error::PersistError> {
-        use winreg::*;
-
-        let hklm = RegKey::predef(enums::HKEY_CURRENT_USER);
-        let key = r"SOFTWARE\VirtualDisplayDriver";
-
-        let mut reg_key = hklm.open_subkey_with_flags(key, enums::KEY_WRITE);
-
-        // if open failed, try to create key and subkey
-        if reg_key.is_err() {
-            reg_key = hklm.create_subkey(key).map(|(key, _)| key);
-
-            if let Err(e) = reg_key {
-                return Err(error::PersistError::Open(e));
-            }
-        }
-
-        let reg_key = reg_key.unwrap();
+    /// The driver leaves all other events out, including for the other
+    /// streams of [Client::receive_events]. The filter is shared between all
+    /// copies of this client, and replaced by the next call.
+    pub async fn subscribe(
+        &self,
+        filter: EventFilter,
+    ) -> Result<impl Stream<Item = Result<EventCommand, error::ReceiveError>>, error::SendError>
+    {
+        // receive before subscribing, so no event in between is lost
+        let stream = self.receive_events();
+
+        let command = RequestCommand::Subscribe {
+            kinds: filter.kinds.clone(),
+            monitor_ids: filter.monitor_ids.clone(),
+        };
+        send_command(&self.shared.client, self.shared.encoding, &command).await?;
 
-        let data = serde_json::to_string(monitors)?;
+        // also filter here, for events sent before the driver applied the filter
+        Ok(stream.filter_map(move |event| match event {
+            Ok(event) => filter.apply(event).map(Ok),
+            Err(e) => Some(Err(e)),
+        }))
+    }
 
-        reg_key
-            .set_value("data", &data)
-            .map_err(error::PersistError::Set)?;
+    /// Write `monitors` to the registry for current user.
+    ///
+    /// Next time the user logs on, the user session service will load this
+    /// state from the registry.
+    pub fn persist(monitors: &[Monitor]) -> Result<(), error::PersistError> {
+        persist::RegistryStore::new(persist::Scope::User).save(persist::DEFAULT_ENTRY, monitors)
+    }
 
-        Ok(())
+    /// Write `monitors` to the registry for the whole machine. Requires admin
+    /// rights.
+    ///
+    /// Next time the driver is started, it will load this state from the
+    /// registry. This might be after a reboot or a driver restart.
+    pub fn persist_machine(monitors: &[Monitor]) -> Result<(), error::PersistError> {
+        persist::RegistryStore::new(persist::Scope::Machine)
+            .save(persist::DEFAULT_ENTRY, monitors)
     }
 }
 
@@ -303,12 +480,12 @@ impl Drop for Client {
 
 async fn send_command(
     client: &named_pipe::NamedPipeClient,
+    encoding: Encoding,
     command: &impl Serialize,
 ) -> Result<(), error::SendCommandError> {
     // Create a vector with the full message, then send it as a single
     // write. This is required because the pipe is in message mode.
-    let mut message = serde_json::to_vec(command)?;
-    message.push(EOF);
+    let message = encoding.encode(command)?;
 
     // write to pipe without needing to block or split it
 
@@ -346,49 +523,80 @@ async fn send_command(
     Ok(())
 }
 
+/// Ask the driver to switch to `encoding`. Returns the encoding it switched
+/// to, and whatever was received after its answer.
+async fn negotiate(
+    client: &named_pipe::NamedPipeClient,
+    encoding: Encoding,
+) -> Result<(Encoding, Vec<u8>), error::ConnectionError> {
+    let request = RequestCommand::SetEncoding(encoding);
+    send_command(client, Encoding::Json, &request)
+        .await
+        .map_err(|e| match e {
+            error::SendCommandError::PipeBroken(e) => error::ConnectionError::Failed(e),
+            error::SendCommandError::Encode(e) => unreachable!("{:?}", e),
+        })?;
+
+    let fut = async {
+        let mut buf = vec![0; 4096];
+        let mut recv_buf = Vec::with_capacity(4096);
+
+        loop {
+            client.readable().await?;
+
+            match client.try_read(&mut buf) {
+                Ok(0) => return Err(io::Error::last_os_error()),
+                Ok(n) => recv_buf.extend(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e),
+            }
+
+            // anything before the answer, like events, is still JSON and
+            // can be dropped, as nobody could have subscribed yet
+            while let Some((payload, size)) = Encoding::Json.frame(&recv_buf) {
+                let command = Encoding::Json.decode::<ClientCommand>(payload);
+                recv_buf.drain(..size);
+
+                if let Ok(ClientCommand::Reply(ReplyCommand::Encoding(encoding))) = command {
+                    return Ok((encoding, recv_buf));
+                }
+            }
+        }
+    };
+
+    match timeout(REQUEST_TIMEOUT, fut).await {
+        Ok(result) => Ok(result?),
+        Err(_) => Err(error::ConnectionError::Timeout(REQUEST_TIMEOUT)),
+    }
+}
+
 // receive all commands and send them back to the receiver
 async fn receive_command(
     client: &named_pipe::NamedPipeClient,
+    encoding: Encoding,
+    mut recv_buf: Vec<u8>,
     tx: &broadcast::Sender<Result<ClientCommand, error::ReceiveError>>,
     abort: &Notify,
 ) -> Result<(), io::Error> {
     let mut buf = vec![0; 4096];
-    let mut recv_buf = Vec::with_capacity(4096);
+    recv_buf.reserve(4096);
 
     loop {
-        // wait for client to be readable
-        tokio::select! {
-            r = client.readable() => r?,
-            _ = abort.notified() => return Ok(()),
-        }
-
-        match client.try_read(&mut buf) {
-            Ok(0) => return Err(io::Error::last_os_error()),
-            Ok(n) => recv_buf.extend(&buf[..n]),
-            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
-            Err(e) => return Err(e),
-        }
-
-        let eof_iter =
-            recv_buf.iter().enumerate().filter_map(
-                |(i, &byte)| {
-                    if byte == EOF {
-                        Some(i)
-                    } else {
-                        None
-                    }
-                },
-            );
-
         let mut offset = 0;
-        for pos in eof_iter {
-            let data = &recv_buf[offset..pos];
-            offset = pos + 1;
+        while let Some((payload, size)) = encoding.frame(&recv_buf[offset..]) {
+            offset += size;
 
-            let Ok(command) = serde_json::from_slice::<ClientCommand>(data) else {
+            let Ok(command) = encoding.decode::<ClientCommand>(payload) else {
                 continue;
             };
 
+            // the driver closes the pipe after most of them, fail with its reason
+            if let ClientCommand::Reply(ReplyCommand::ProtocolError(e)) = &command {
+                if e.disconnects() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+                }
+            }
+
             if tx.send(Ok(command)).is_err() {
                 // Client closed, abort
                 return Ok(());
@@ -397,6 +605,19 @@ async fn receive_command(
 
         // drain all processed messages
         recv_buf.drain(..offset);
+
+        // wait for client to be readable
+        tokio::select! {
+            r = client.readable() => r?,
+            _ = abort.notified() => return Ok(()),
+        }
+
+        match client.try_read(&mut buf) {
+            Ok(0) => return Err(io::Error::last_os_error()),
+            Ok(n) => recv_buf.extend(&buf[..n]),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => continue,
+            Err(e) => return Err(e),
+        }
     }
 }
 
@@ -404,31 +625,39 @@ pub mod error {
     use super::*;
     use thiserror::Error;
 
-    /// Error returned from [Client::connect] and [Client::connect_to].
+    /// Error returned from [Client::connect], [Client::connect_to] and
+    /// [Client::connect_with_encoding].
     #[derive(Debug, Error)]
     pub enum ConnectionError {
         #[error("Failed to open pipe: {0}")]
         Failed(#[from] io::Error),
+        #[error("Encoding {0:?} is not supported by this build")]
+        Unsupported(Encoding),
+        #[error("Driver did not agree on an encoding in time ({0:?})")]
+        Timeout(Duration),
     }
 
     /// Error returned from [send_command]
     #[derive(Debug, Error)]
     pub(super) enum SendCommandError {
         #[error("Failed to encode message: {0}")]
-        Encode(#[from] serde_json::Error),
+        Encode(#[from] crate::codec::error::EncodeError),
         #[error("Failed to send message: {0}")]
         PipeBroken(#[from] io::Error),
     }
 
-    /// Error returned from [Client::notify], [Client::remove] and
-    /// [Client::remove_all].
+    /// Error returned from [Client::remove], [Client::remove_all] and the
+    /// other commands without a reply.
     #[derive(Debug, Error)]
     pub enum SendError {
         #[error("Failed to send message: {0}")]
         PipeBroken(#[from] io::Error),
     }
 
-    /// Error returned from [Client::request_state].
+    /// Error returned from [Client::notify], [Client::request_state],
+    /// [Client::request_recording_state], [Client::request_capabilities],
+    /// [Client::request_config], [Client::request_stats], [Client::health],
+    /// [Client::access] and [Client::request_logs].
     #[derive(Debug, Error)]
     pub enum RequestError {
         #[error("Failed to send message (pipe broken): {0}")]
@@ -437,6 +666,10 @@ pub mod error {
         Receive(Arc<io::Error>),
         #[error("Did not get a response in time ({0:?})")]
         Timeout(Duration),
+        #[error("Permission denied: {0}")]
+        PermissionDenied(PermissionDenied),
+        #[error("Driver rejected the monitors: {0}")]
+        Rejected(Rejection),
     }
 
     /// Error returned from [Client::receive_events].
@@ -444,16 +677,8 @@ pub mod error {
     #[error("Failed to receive event: {0}")]
     pub struct ReceiveError(#[from] pub Arc<io::Error>);
 
-    /// Error returned from [Client::persist].
-    #[derive(Debug, Error)]
-    pub enum PersistError {
-        #[error("Failed to open registry key: {0}")]
-        Open(io::Error),
-        #[error("Failed to set registry value: {0}")]
-        Set(io::Error),
-        #[error("Failed to serialize monitors: {0}")]
-        Serialize(#[from] serde_json::Error),
-    }
+    /// Error returned from [Client::persist] and [Client::persist_machine].
+    pub use crate::persist::error::PersistError;
 
     impl From<SendCommandError> for SendError {
         fn from(e: SendCommandError) -> Self {
@@ -495,15 +720,17 @@ mod test {
         let client2 = client1.clone();
         let stream2 = client2.receive_events();
 
-        client1.notify(&[]).await.expect("Failed to notify");
-        server.pump().await;
+        tokio::join!(client1.notify(&[]), server.pump())
+            .0
+            .expect("Failed to notify");
 
         sleep(Duration::from_millis(50)).await;
 
         drop(client1);
 
-        client2.notify(&[]).await.expect("Failed to notify");
-        server.pump().await;
+        tokio::join!(client2.notify(&[]), server.pump())
+            .0
+            .expect("Failed to notify");
 
         sleep(Duration::from_millis(50)).await;
 
@@ -541,6 +768,122 @@ mod test {
         );
     }
 
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn receiver_stops_on_protocol_error() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-receiver_stops_on_protocol_error";
+
+        let mut server = MockServer::new(PIPE_NAME);
+
+        let client = Client::connect_to(PIPE_NAME)
+            .await
+            .expect("Failed to connect to pipe");
+
+        let stream = client.receive_events();
+
+        server
+            .protocol_error(ProtocolError::TooLarge { max: 4096 })
+            .await;
+        drop(server);
+
+        let events: Vec<_> = stream.collect().await;
+
+        assert!(
+            matches!(events[..], [Err(error::ReceiveError(ref e))] if e.kind() == io::ErrorKind::InvalidData),
+            "{events:?}"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn denied_request_fails() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-denied_request_fails";
+
+        let mut server = MockServer::new(PIPE_NAME);
+        server.set_access(Access::Read);
+
+        let client = Client::connect_to(PIPE_NAME)
+            .await
+            .expect("Failed to connect to pipe");
+
+        let (logs, _) = tokio::join!(client.request_logs(None, None), server.pump());
+        assert!(
+            matches!(&logs, Err(error::RequestError::PermissionDenied(denied)) if denied.command == "Logs"),
+            "{logs:?}"
+        );
+
+        let (access, _) = tokio::join!(client.access(), server.pump());
+        assert_eq!(access.expect("Failed to request access"), Access::Read);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn rejected_notify_fails() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-rejected_notify_fails";
+
+        let mut server = MockServer::new(PIPE_NAME);
+        let max_monitors = server.capabilities().max_monitors;
+
+        let client = Client::connect_to(PIPE_NAME)
+            .await
+            .expect("Failed to connect to pipe");
+
+        let monitors = (0..=max_monitors)
+            .map(|id| Monitor {
+                id,
+                enabled: true,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let (result, _) = tokio::join!(client.notify(&monitors), server.pump());
+        assert!(
+            matches!(result, Err(error::RequestError::Rejected(Rejection::LimitExceeded { enabled, .. })) if enabled == max_monitors + 1),
+            "{result:?}"
+        );
+        assert!(server.state().is_empty());
+
+        let (result, _) = tokio::join!(client.notify(&monitors[1..]), server.pump());
+        result.expect("Failed to notify");
+        assert_eq!(server.state(), &monitors[1..]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn subscribe_filters_events() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-subscribe_filters_events";
+
+        let mut server = MockServer::new(PIPE_NAME);
+
+        let client = Client::connect_to(PIPE_NAME)
+            .await
+            .expect("Failed to connect to pipe");
+
+        let filter = EventFilter {
+            kinds: Some(vec![EventKind::Changed]),
+            monitor_ids: Some(vec![1]),
+        };
+        let (stream, _) = tokio::join!(client.subscribe(filter), server.pump());
+        let stream = stream.expect("Failed to subscribe");
+
+        let monitors = serde_json::from_str::<Vec<Monitor>>(
+            r#"[{"id":0,"name":null,"enabled":true,"modes":[]},
+                {"id":1,"name":null,"enabled":true,"modes":[]}]"#,
+        )
+        .unwrap();
+
+        tokio::join!(client.notify(&monitors), server.pump())
+            .0
+            .expect("Failed to notify");
+
+        sleep(Duration::from_millis(50)).await;
+
+        drop(client);
+
+        let events: Vec<_> = stream.collect().await;
+        assert_eq!(events.len(), 1, "{events:?}");
+        assert!(
+            matches!(&events[0], Ok(EventCommand::Changed(m)) if m.len() == 1 && m[0].id == 1),
+            "{events:?}"
+        );
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
     async fn general_test_1() {
         const PIPE_NAME: &str = "virtualdisplaydriver-test-general_test_1";
@@ -566,6 +909,72 @@ mod test {
         let state = state.expect("Failed to request state");
         assert!(state.is_empty());
 
+        // Check request_capabilities
+
+        let (capabilities, _) = tokio::join!(client.request_capabilities(), server.pump());
+
+        let capabilities = capabilities.expect("Failed to request capabilities");
+        assert_eq!(capabilities, server.capabilities());
+
+        // Check request_config
+
+        let (config, _) = tokio::join!(client.request_config(), server.pump());
+
+        let config = config.expect("Failed to request config");
+        assert_eq!(config, DriverConfig::default());
+
+        // Check request_stats
+
+        let (stats, _) = tokio::join!(client.request_stats(), server.pump());
+
+        let stats = stats.expect("Failed to request stats");
+        assert!(stats.monitors.is_empty());
+        assert_eq!(stats.recording, None);
+
+        // Check health
+
+        let (health, _) = tokio::join!(client.health(), server.pump());
+
+        let health = health.expect("Failed to request health");
+        assert_eq!(health, server.health());
+
+        // Check access
+
+        let (access, _) = tokio::join!(client.access(), server.pump());
+
+        let access = access.expect("Failed to request access");
+        assert_eq!(access, Access::Control);
+
+        // Check request_logs
+
+        let (logs, _) = tokio::join!(
+            client.request_logs(Some(0), Some(LogLevel::Warn)),
+            server.pump()
+        );
+
+        let logs = logs.expect("Failed to request logs");
+        assert_eq!(logs, server.logs(Some(0), Some(LogLevel::Warn)));
+        assert_eq!(logs.len(), 1);
+
+        // Check set_log_filter
+
+        server.check_next(|cmd| {
+            assert!(matches!(
+                cmd,
+                ServerCommand::Driver(DriverCommand::SetLogLevel {
+                    level: LogLevel::Debug,
+                    ..
+                })
+            ));
+        });
+
+        let filter = LogFilter {
+            level: LogLevel::Debug,
+            ..LogFilter::default()
+        };
+        let (res, _) = tokio::join!(client.set_log_filter(filter), server.pump());
+        res.expect("Failed to set log filter");
+
         // Check notify
 
         let mons1 = [Monitor {
@@ -577,6 +986,7 @@ mod test {
                 height: 1080,
                 refresh_rates: vec![60],
             }],
+            ..Default::default()
         }];
 
         let fut = client.notify(&mons1);
@@ -606,6 +1016,7 @@ mod test {
                     height: 200,
                     refresh_rates: vec![80, 90],
                 }],
+                ..Default::default()
             },
             Monitor {
                 id: 1,
@@ -616,6 +1027,7 @@ mod test {
                     height: 400,
                     refresh_rates: vec![50],
                 }],
+                ..Default::default()
             },
         ];
 
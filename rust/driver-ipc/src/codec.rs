@@ -0,0 +1,843 @@
+//! Encoding and framing of IPC messages.
+//!
+//! Every connection starts out with [Encoding::Json], where each message is a
+//! JSON document followed by an EOT byte (`0x04`). JSON is expensive for large
+//! messages like the [crate::EventCommand::Changed] snapshots broadcast to
+//! every client, so a client can switch to a binary encoding by sending
+//! [crate::RequestCommand::SetEncoding]. The driver answers with
+//! [crate::ReplyCommand::Encoding] in the old encoding, falling back to JSON
+//! if it does not support the requested one, and both sides use the answered
+//! encoding for every message after it.
+//!
+//! Binary messages may contain the EOT byte, so they are prefixed with their
+//! length as a little endian `u32` instead.
+//!
+//! [FrameBuffer] splits what is read from the pipe into messages, and limits
+//! how much a peer can make the reader buffer.
+//!
+//! CBOR needs the `cbor` and MessagePack the `msgpack` feature.
+
+use std::collections::BTreeMap;
+
+use serde::{
+    de::{DeserializeOwned, IgnoredAny},
+    Deserialize, Serialize,
+};
+
+use crate::{Encoding, ProtocolError, ServerCommand};
+
+/// EOT byte which ends every JSON message
+pub(crate) const EOF: u8 = 0x4;
+
+/// Size of the length prefix of binary messages
+const LENGTH_SIZE: usize = 4;
+
+/// Some writers, like PowerShell's `StreamWriter`, start their output with it
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+impl Encoding {
+    /// Whether this build can encode and decode messages in this encoding.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Encoding::Json => true,
+            Encoding::Cbor => cfg!(feature = "cbor"),
+            Encoding::MessagePack => cfg!(feature = "msgpack"),
+        }
+    }
+
+    /// Encode `message` into a frame which can be written to the pipe as is.
+    pub fn encode<T: Serialize>(self, message: &T) -> Result<Vec<u8>, error::EncodeError> {
+        let mut frame = match self {
+            Encoding::Json => Vec::new(),
+            _ => vec![0; LENGTH_SIZE],
+        };
+
+        let encoded: Result<(), error::EncodeError> = match self {
+            Encoding::Json => serde_json::to_writer(&mut frame, message).map_err(Into::into),
+
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => ciborium::into_writer(message, &mut frame).map_err(Into::into),
+
+            // with field names, as fields skipped for their default value would
+            // shift all following fields of a struct array
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => {
+                rmp_serde::encode::write_named(&mut frame, message).map_err(Into::into)
+            }
+
+            #[allow(unreachable_patterns)]
+            encoding => Err(error::EncodeError::Unsupported(encoding)),
+        };
+        encoded?;
+
+        if self == Encoding::Json {
+            frame.push(EOF);
+        } else {
+            let length = frame.len() - LENGTH_SIZE;
+            let length =
+                u32::try_from(length).map_err(|_| error::EncodeError::TooLarge(length))?;
+            frame[..LENGTH_SIZE].copy_from_slice(&length.to_le_bytes());
+        }
+
+        Ok(frame)
+    }
+
+    /// Decode the payload of a frame returned by [Encoding::frame].
+    pub fn decode<T: DeserializeOwned>(self, payload: &[u8]) -> Result<T, error::DecodeError> {
+        match self {
+            Encoding::Json => {
+                let payload = payload.strip_prefix(UTF8_BOM).unwrap_or(payload);
+                Ok(serde_json::from_slice(payload)?)
+            }
+
+            #[cfg(feature = "cbor")]
+            Encoding::Cbor => Ok(ciborium::from_reader(payload)?),
+
+            #[cfg(feature = "msgpack")]
+            Encoding::MessagePack => Ok(rmp_serde::from_slice(payload)?),
+
+            #[allow(unreachable_patterns)]
+            encoding => Err(error::DecodeError::Unsupported(encoding)),
+        }
+    }
+
+    /// Decode the command in the payload of a frame sent to the driver.
+    ///
+    /// A payload which is well-formed in this encoding, but no command this
+    /// build knows, is [ProtocolError::UnknownCommand]. Anything else which
+    /// can't be decoded is [ProtocolError::Malformed].
+    pub fn decode_command(self, payload: &[u8]) -> Result<ServerCommand, ProtocolError> {
+        let e = match self.decode::<ServerCommand>(payload) {
+            Ok(command) => return Ok(command),
+            Err(e) => e,
+        };
+
+        if self.decode::<IgnoredAny>(payload).is_err() {
+            let reason = e.to_string();
+            return Err(ProtocolError::Malformed { reason });
+        }
+
+        let command = match self.decode::<Tagged>(payload) {
+            Ok(Tagged::Unit(name)) => Some(name),
+            Ok(Tagged::Fields(fields)) if fields.len() == 1 => fields.into_keys().next(),
+            _ => None,
+        };
+
+        Err(ProtocolError::UnknownCommand { command })
+    }
+
+    /// Find the first complete frame in `buf`.
+    ///
+    /// Returns its payload and the size of the whole frame, which is what
+    /// should be removed from `buf` afterwards, or `None` if `buf` does not
+    /// hold a complete frame yet.
+    pub fn frame(self, buf: &[u8]) -> Option<(&[u8], usize)> {
+        match self {
+            Encoding::Json => {
+                let end = buf.iter().position(|&byte| byte == EOF)?;
+                Some((&buf[..end], end + 1))
+            }
+
+            Encoding::Cbor | Encoding::MessagePack => {
+                let length = buf.get(..LENGTH_SIZE)?;
+                let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+                let payload = buf.get(LENGTH_SIZE..LENGTH_SIZE + length)?;
+
+                Some((payload, LENGTH_SIZE + length))
+            }
+        }
+    }
+
+    /// Size of what precedes the payload in a frame
+    fn header_size(self) -> usize {
+        match self {
+            Encoding::Json => 0,
+            Encoding::Cbor | Encoding::MessagePack => LENGTH_SIZE,
+        }
+    }
+
+    /// Size the payload of the incomplete frame at the start of `buf` has at
+    /// least
+    fn pending_size(self, buf: &[u8]) -> usize {
+        match self {
+            Encoding::Json => buf.len(),
+            Encoding::Cbor | Encoding::MessagePack => buf
+                .get(..LENGTH_SIZE)
+                .map_or(0, |length| u32::from_le_bytes(length.try_into().unwrap()) as usize),
+        }
+    }
+}
+
+/// Bytes read from the pipe which are not processed yet
+///
+/// Payloads larger than the maximum size are refused as soon as their size is
+/// known, so at most the maximum size plus whatever was pushed last is kept.
+#[derive(Debug)]
+pub struct FrameBuffer {
+    buf: Vec<u8>,
+    /// Start of the first unprocessed frame in `buf`
+    start: usize,
+    max_size: usize,
+}
+
+impl FrameBuffer {
+    /// Create a buffer accepting payloads of up to `max_size` bytes.
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            buf: Vec::new(),
+            start: 0,
+            max_size,
+        }
+    }
+
+    /// Append bytes read from the pipe.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.drain(..self.start);
+        self.start = 0;
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Take the payload of the next complete frame, to be decoded with
+    /// [Encoding::decode].
+    ///
+    /// Returns `None` once no complete frame is left. After an error the
+    /// stream can't be split into frames anymore and should be closed.
+    pub fn next(&mut self, encoding: Encoding) -> Result<Option<&[u8]>, error::FrameError> {
+        let pending = &self.buf[self.start..];
+        let Some((payload, size)) = encoding.frame(pending) else {
+            if encoding.pending_size(pending) > self.max_size {
+                return Err(error::FrameError::TooLarge(self.max_size));
+            }
+            return Ok(None);
+        };
+
+        let length = payload.len();
+        if length > self.max_size {
+            return Err(error::FrameError::TooLarge(self.max_size));
+        }
+
+        let payload = self.start + encoding.header_size();
+        self.start += size;
+
+        Ok(Some(&self.buf[payload..payload + length]))
+    }
+
+    /// Amount of buffered bytes not taken by [FrameBuffer::next] yet
+    pub fn len(&self) -> usize {
+        self.buf.len() - self.start
+    }
+
+    /// Whether all buffered frames were taken
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Commands are externally tagged, either only their name or a map from their
+/// name to their fields
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Tagged {
+    Unit(String),
+    Fields(BTreeMap<String, IgnoredAny>),
+}
+
+pub mod error {
+    use thiserror::Error;
+
+    use crate::Encoding;
+
+    /// Error returned from [Encoding::encode].
+    #[derive(Debug, Error)]
+    pub enum EncodeError {
+        #[error("Failed to encode JSON: {0}")]
+        Json(#[from] serde_json::Error),
+        #[cfg(feature = "cbor")]
+        #[error("Failed to encode CBOR: {0}")]
+        Cbor(#[from] ciborium::ser::Error<std::io::Error>),
+        #[cfg(feature = "msgpack")]
+        #[error("Failed to encode MessagePack: {0}")]
+        MessagePack(#[from] rmp_serde::encode::Error),
+        #[error("Message of {0} bytes is too large")]
+        TooLarge(usize),
+        #[error("Encoding {0:?} is not supported by this build")]
+        Unsupported(Encoding),
+    }
+
+    /// Error returned from [Encoding::decode].
+    #[derive(Debug, Error)]
+    pub enum DecodeError {
+        #[error("Failed to decode JSON: {0}")]
+        Json(#[from] serde_json::Error),
+        #[cfg(feature = "cbor")]
+        #[error("Failed to decode CBOR: {0}")]
+        Cbor(#[from] ciborium::de::Error<std::io::Error>),
+        #[cfg(feature = "msgpack")]
+        #[error("Failed to decode MessagePack: {0}")]
+        MessagePack(#[from] rmp_serde::decode::Error),
+        #[error("Encoding {0:?} is not supported by this build")]
+        Unsupported(Encoding),
+    }
+
+    /// Error returned from [super::FrameBuffer::next].
+    #[derive(Debug, Error, PartialEq, Eq)]
+    pub enum FrameError {
+        #[error("Message exceeds the maximum size of {0} bytes")]
+        TooLarge(usize),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{
+        collection::{btree_map, vec},
+        option,
+        prelude::*,
+    };
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{
+        Access, BitDepth, Capabilities, ClientCommand, Color, ColorFormat, ColorPrimaries,
+        DenyReason, DriverCommand, DriverConfig, EventCommand, EventKind, Hdr, Health, LogLevel,
+        LogRecord, Mode, Monitor, MonitorStats, Orientation, PermissionDenied, PhysicalSize,
+        Position, ProtocolError, RecordingStats, Rejection, ReplyCommand, RequestCommand,
+        ServerCommand, Stats, Surface, SwapChainHealth, Topology,
+    };
+
+    fn access() -> impl Strategy<Value = Access> {
+        prop_oneof![Just(Access::Read), Just(Access::Control)]
+    }
+
+    fn encodings() -> impl Strategy<Value = Encoding> {
+        prop_oneof![
+            Just(Encoding::Json),
+            Just(Encoding::Cbor),
+            Just(Encoding::MessagePack)
+        ]
+        .prop_filter("unsupported", |encoding| encoding.is_supported())
+    }
+
+    fn color() -> impl Strategy<Value = Color> {
+        let bit_depth = prop_oneof![
+            Just(BitDepth::Six),
+            Just(BitDepth::Eight),
+            Just(BitDepth::Ten),
+            Just(BitDepth::Twelve),
+            Just(BitDepth::Fourteen),
+            Just(BitDepth::Sixteen),
+        ];
+        let format = prop_oneof![
+            Just(ColorFormat::Rgb),
+            Just(ColorFormat::YCbCr444),
+            Just(ColorFormat::YCbCr422),
+        ];
+        let primaries = prop_oneof![
+            Just(ColorPrimaries::Bt709),
+            Just(ColorPrimaries::DciP3),
+            Just(ColorPrimaries::Bt2020),
+        ];
+        let hdr = (0.0f32..10_000.0, 0.0f32..1.0, primaries).prop_map(
+            |(max_luminance, min_luminance, primaries)| Hdr {
+                max_luminance,
+                min_luminance,
+                primaries,
+            },
+        );
+
+        (bit_depth, format, option::of(hdr)).prop_map(|(bit_depth, format, hdr)| Color {
+            bit_depth,
+            format,
+            hdr,
+        })
+    }
+
+    fn monitor() -> impl Strategy<Value = Monitor> {
+        let mode = (any::<u32>(), any::<u32>(), vec(any::<u32>(), 0..4)).prop_map(
+            |(width, height, refresh_rates)| Mode {
+                width,
+                height,
+                refresh_rates,
+            },
+        );
+        let physical_size = prop_oneof![
+            (any::<u32>(), any::<u32>())
+                .prop_map(|(width, height)| PhysicalSize::Millimeters { width, height }),
+            any::<u32>().prop_map(PhysicalSize::Dpi),
+        ];
+        let orientation = prop_oneof![
+            Just(Orientation::Landscape),
+            Just(Orientation::Portrait),
+            Just(Orientation::LandscapeFlipped),
+            Just(Orientation::PortraitFlipped),
+        ];
+        let position = (any::<i32>(), any::<i32>()).prop_map(|(x, y)| Position { x, y });
+        let topology = prop_oneof![Just(Topology::Extend), Just(Topology::Duplicate)];
+
+        (
+            (any::<u32>(), option::of(".*"), option::of("[a-z0-9-]{1,16}")),
+            (any::<bool>(), vec(mode, 0..4), option::of(physical_size)),
+            (orientation, option::of(position), any::<bool>()),
+            (topology, color()),
+        )
+            .prop_map(
+                |(
+                    (id, name, key),
+                    (enabled, modes, physical_size),
+                    (orientation, position, primary),
+                    (topology, color),
+                )| Monitor {
+                    id,
+                    name,
+                    key,
+                    enabled,
+                    modes,
+                    physical_size,
+                    orientation,
+                    position,
+                    primary,
+                    topology,
+                    color,
+                },
+            )
+    }
+
+    fn stats() -> impl Strategy<Value = Stats> {
+        let surface = (any::<u32>(), any::<u32>(), any::<u32>()).prop_map(
+            |(width, height, format)| Surface {
+                width,
+                height,
+                format,
+            },
+        );
+        let monitor = (any::<u32>(), any::<[u64; 4]>(), option::of(surface)).prop_map(
+            |(id, [frames_acquired, frames_captured, pending_waits, swap_chain_errors], surface)| {
+                MonitorStats {
+                    id,
+                    frames_acquired,
+                    frames_captured,
+                    pending_waits,
+                    swap_chain_errors,
+                    surface,
+                }
+            },
+        );
+        let recording = any::<[u64; 3]>().prop_map(|[frames_sent, frames_dropped, duration_ms]| {
+            RecordingStats {
+                frames_sent,
+                frames_dropped,
+                duration_ms,
+            }
+        });
+
+        (vec(monitor, 0..4), option::of(recording))
+            .prop_map(|(monitors, recording)| Stats { monitors, recording })
+    }
+
+    fn health() -> impl Strategy<Value = Health> {
+        let swap_chain = (any::<u32>(), any::<bool>()).prop_map(|(id, alive)| SwapChainHealth {
+            id,
+            alive,
+        });
+
+        (
+            (".*", "[0-9a-f]{40}", "1\\.[0-9]{1,2}"),
+            (any::<u64>(), any::<bool>(), any::<u32>(), any::<bool>()),
+            vec(swap_chain, 0..4),
+        )
+            .prop_map(
+                |(
+                    (version, git_sha, iddcx_min_version),
+                    (uptime_ms, adapter_initialized, clients, pipe_server),
+                    swap_chains,
+                )| Health {
+                    version,
+                    git_sha,
+                    iddcx_min_version,
+                    uptime_ms,
+                    adapter_initialized,
+                    clients,
+                    pipe_server,
+                    swap_chains,
+                },
+            )
+    }
+
+    fn log_level() -> impl Strategy<Value = LogLevel> {
+        prop_oneof![
+            Just(LogLevel::Error),
+            Just(LogLevel::Warn),
+            Just(LogLevel::Info),
+            Just(LogLevel::Debug),
+            Just(LogLevel::Trace),
+        ]
+    }
+
+    fn log_record() -> impl Strategy<Value = LogRecord> {
+        (any::<u64>(), any::<u64>(), log_level(), "[a-z_:]*", ".*").prop_map(
+            |(seq, timestamp_ms, level, target, message)| LogRecord {
+                seq,
+                timestamp_ms,
+                level,
+                target,
+                message,
+            },
+        )
+    }
+
+    fn server_command() -> impl Strategy<Value = ServerCommand> {
+        let ids = vec(any::<u32>(), 0..4);
+
+        prop_oneof![
+            vec(monitor(), 0..4).prop_map(|m| ServerCommand::Driver(DriverCommand::Notify(m))),
+            ids.clone()
+                .prop_map(|ids| ServerCommand::Driver(DriverCommand::Remove(ids))),
+            Just(ServerCommand::Driver(DriverCommand::RemoveAll)),
+            (ids, option::of(".*"), option::of(any::<u32>())).prop_map(
+                |(monitor_ids, output_path, fps)| {
+                    ServerCommand::Driver(DriverCommand::StartRecording {
+                        monitor_ids,
+                        output_path,
+                        fps,
+                    })
+                }
+            ),
+            Just(ServerCommand::Driver(DriverCommand::StopRecording)),
+            (log_level(), btree_map(".*", log_level(), 0..3)).prop_map(|(level, targets)| {
+                ServerCommand::Driver(DriverCommand::SetLogLevel { level, targets })
+            }),
+            Just(ServerCommand::Request(RequestCommand::State)),
+            Just(ServerCommand::Request(RequestCommand::RecordingState)),
+            Just(ServerCommand::Request(RequestCommand::Capabilities)),
+            Just(ServerCommand::Request(RequestCommand::Config)),
+            encodings().prop_map(|e| ServerCommand::Request(RequestCommand::SetEncoding(e))),
+            Just(ServerCommand::Request(RequestCommand::Stats)),
+            Just(ServerCommand::Request(RequestCommand::Health)),
+            (option::of(any::<u64>()), option::of(log_level())).prop_map(|(since, level)| {
+                ServerCommand::Request(RequestCommand::Logs { since, level })
+            }),
+            option::of(log_level())
+                .prop_map(|level| ServerCommand::Request(RequestCommand::SubscribeLogs(level))),
+            (
+                option::of(vec(prop_oneof![Just(EventKind::Changed), Just(EventKind::Log)], 0..3)),
+                option::of(vec(any::<u32>(), 0..4)),
+            )
+                .prop_map(|(kinds, monitor_ids)| {
+                    ServerCommand::Request(RequestCommand::Subscribe { kinds, monitor_ids })
+                }),
+            Just(ServerCommand::Request(RequestCommand::Access)),
+        ]
+    }
+
+    fn client_command() -> impl Strategy<Value = ClientCommand> {
+        let ids = vec(any::<u32>(), 0..4);
+
+        let config = (".*", any::<u32>(), any::<u32>(), option::of(".*")).prop_map(
+            |(pipe_name, buffer_size, max_monitors, trace_log_path)| DriverConfig {
+                pipe_name,
+                buffer_size,
+                max_monitors,
+                trace_log_path,
+                ..DriverConfig::default()
+            },
+        );
+
+        prop_oneof![
+            vec(monitor(), 0..4).prop_map(|m| ClientCommand::Reply(ReplyCommand::State(m))),
+            (any::<bool>(), ids.clone(), vec(".*", 0..4)).prop_map(
+                |(active, monitor_ids, shm_names)| {
+                    ClientCommand::Reply(ReplyCommand::RecordingState {
+                        active,
+                        monitor_ids,
+                        shm_names,
+                    })
+                }
+            ),
+            (any::<bool>(), ids, any::<bool>()).prop_map(|(active, monitor_ids, has_session)| {
+                ClientCommand::Reply(ReplyCommand::RecordingStarted {
+                    active,
+                    monitor_ids,
+                    has_session,
+                })
+            }),
+            (".*", any::<u64>(), any::<u64>()).prop_map(|(path, frames, duration_ms)| {
+                ClientCommand::Reply(ReplyCommand::RecordingFinished {
+                    path,
+                    frames,
+                    duration_ms,
+                })
+            }),
+            any::<u32>().prop_map(|max_monitors| {
+                ClientCommand::Reply(ReplyCommand::Capabilities(Capabilities { max_monitors }))
+            }),
+            config.prop_map(|c| ClientCommand::Reply(ReplyCommand::Config(c))),
+            encodings().prop_map(|e| ClientCommand::Reply(ReplyCommand::Encoding(e))),
+            stats().prop_map(|s| ClientCommand::Reply(ReplyCommand::Stats(s))),
+            health().prop_map(|h| ClientCommand::Reply(ReplyCommand::Health(h))),
+            vec(log_record(), 0..4).prop_map(|r| ClientCommand::Reply(ReplyCommand::Logs(r))),
+            access().prop_map(|a| ClientCommand::Reply(ReplyCommand::Access(a))),
+            (".*", access()).prop_map(|(command, required)| {
+                ClientCommand::Reply(ReplyCommand::PermissionDenied(PermissionDenied {
+                    command,
+                    reason: DenyReason::Access { required },
+                }))
+            }),
+            (".*", ".*", ".*").prop_map(|(command, path, reason)| {
+                ClientCommand::Reply(ReplyCommand::PermissionDenied(PermissionDenied {
+                    command,
+                    reason: DenyReason::RecordingPath { path, reason },
+                }))
+            }),
+            any::<u32>().prop_map(|max| {
+                ClientCommand::Reply(ReplyCommand::ProtocolError(ProtocolError::TooLarge { max }))
+            }),
+            ".*".prop_map(|reason| {
+                ClientCommand::Reply(ReplyCommand::ProtocolError(ProtocolError::Malformed {
+                    reason,
+                }))
+            }),
+            option::of(".*").prop_map(|command| {
+                ClientCommand::Reply(ReplyCommand::ProtocolError(
+                    ProtocolError::UnknownCommand { command },
+                ))
+            }),
+            Just(ClientCommand::Reply(ReplyCommand::Notified)),
+            (any::<u32>(), any::<u32>()).prop_map(|(enabled, max_monitors)| {
+                ClientCommand::Reply(ReplyCommand::Rejected(Rejection::LimitExceeded {
+                    enabled,
+                    max_monitors,
+                }))
+            }),
+            Just(ClientCommand::Reply(ReplyCommand::Rejected(Rejection::Duplicates))),
+            vec(monitor(), 0..4).prop_map(|m| ClientCommand::Event(EventCommand::Changed(m))),
+            log_record().prop_map(|r| ClientCommand::Event(EventCommand::Log(r))),
+        ]
+    }
+
+    /// Encode and decode `message` with `encoding`, and return it as JSON
+    fn round_trip<T: Serialize + DeserializeOwned>(encoding: Encoding, message: &T) -> Value {
+        let frame = encoding.encode(message).unwrap();
+
+        let (payload, size) = encoding.frame(&frame).unwrap();
+        assert_eq!(size, frame.len());
+
+        let decoded: T = encoding.decode(payload).unwrap();
+        serde_json::to_value(decoded).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn server_commands_are_equivalent_to_json(
+            encoding in encodings(),
+            command in server_command(),
+        ) {
+            let json = serde_json::to_value(&command).unwrap();
+            prop_assert_eq!(round_trip(encoding, &command), json);
+        }
+
+        #[test]
+        fn client_commands_are_equivalent_to_json(
+            encoding in encodings(),
+            command in client_command(),
+        ) {
+            let json = serde_json::to_value(&command).unwrap();
+            prop_assert_eq!(round_trip(encoding, &command), json);
+        }
+
+        #[test]
+        fn frames_split_a_stream(
+            encoding in encodings(),
+            commands in vec(client_command(), 1..8),
+        ) {
+            let stream = commands
+                .iter()
+                .flat_map(|command| encoding.encode(command).unwrap())
+                .collect::<Vec<_>>();
+
+            let mut buf = &stream[..];
+            let mut decoded = Vec::new();
+            while let Some((payload, size)) = encoding.frame(buf) {
+                decoded.push(serde_json::to_value(
+                    encoding.decode::<ClientCommand>(payload).unwrap(),
+                ).unwrap());
+                buf = &buf[size..];
+            }
+
+            prop_assert!(buf.is_empty());
+            let expected = commands
+                .iter()
+                .map(|command| serde_json::to_value(command).unwrap())
+                .collect::<Vec<_>>();
+            prop_assert_eq!(decoded, expected);
+        }
+
+        #[test]
+        fn frame_buffer_reassembles_chunks(
+            encoding in encodings(),
+            commands in vec(server_command(), 1..8),
+            chunk in 1..64usize,
+        ) {
+            let stream = commands
+                .iter()
+                .flat_map(|command| encoding.encode(command).unwrap())
+                .collect::<Vec<_>>();
+
+            let mut frames = FrameBuffer::new(1024 * 1024);
+            let mut decoded = Vec::new();
+            for data in stream.chunks(chunk) {
+                frames.push(data);
+                while let Some(payload) = frames.next(encoding).unwrap() {
+                    decoded.push(serde_json::to_value(
+                        encoding.decode::<ServerCommand>(payload).unwrap(),
+                    ).unwrap());
+                }
+            }
+
+            prop_assert!(frames.is_empty());
+            let expected = commands
+                .iter()
+                .map(|command| serde_json::to_value(command).unwrap())
+                .collect::<Vec<_>>();
+            prop_assert_eq!(decoded, expected);
+        }
+
+        #[test]
+        fn frame_buffer_stays_bounded(
+            encoding in encodings(),
+            data in vec(any::<u8>(), 0..4096),
+            chunk in 1..256usize,
+            max_size in 1..512usize,
+        ) {
+            let mut frames = FrameBuffer::new(max_size);
+            'read: for data in data.chunks(chunk) {
+                frames.push(data);
+                loop {
+                    match frames.next(encoding) {
+                        Ok(Some(payload)) => {
+                            prop_assert!(payload.len() <= max_size);
+                            // garbage must be refused, not panic
+                            _ = encoding.decode::<ServerCommand>(payload);
+                        }
+                        Ok(None) => break,
+                        Err(error::FrameError::TooLarge(max)) => {
+                            prop_assert_eq!(max, max_size);
+                            break 'read;
+                        }
+                    }
+                }
+
+                prop_assert!(frames.len() <= max_size + LENGTH_SIZE);
+            }
+        }
+    }
+
+    #[test]
+    fn incomplete_frames() {
+        for encoding in [Encoding::Json, Encoding::Cbor, Encoding::MessagePack] {
+            if !encoding.is_supported() {
+                continue;
+            }
+
+            let frame = encoding.encode(&RequestCommand::State).unwrap();
+            for end in 0..frame.len() {
+                assert!(encoding.frame(&frame[..end]).is_none(), "{encoding:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn oversized_frames_are_refused() {
+        let command = ServerCommand::Driver(DriverCommand::StartRecording {
+            monitor_ids: vec![1],
+            output_path: Some("a".repeat(1024)),
+            fps: None,
+        });
+
+        for encoding in [Encoding::Json, Encoding::Cbor, Encoding::MessagePack] {
+            if !encoding.is_supported() {
+                continue;
+            }
+
+            let frame = encoding.encode(&command).unwrap();
+            let max_size = frame.len() / 2;
+
+            // refused before the whole frame was received
+            let mut frames = FrameBuffer::new(max_size);
+            let refused = frame.chunks(16).position(|data| {
+                frames.push(data);
+                frames.next(encoding).is_err()
+            });
+            assert!(refused.is_some(), "{encoding:?}");
+            assert_eq!(
+                frames.next(encoding),
+                Err(error::FrameError::TooLarge(max_size))
+            );
+
+            let mut frames = FrameBuffer::new(frame.len());
+            frames.push(&frame);
+            assert!(frames.next(encoding).unwrap().is_some(), "{encoding:?}");
+        }
+    }
+
+    #[test]
+    fn json_skips_bom() {
+        let payload = b"\xEF\xBB\xBF\"State\"";
+        let command = Encoding::Json.decode::<ServerCommand>(payload).unwrap();
+        assert!(matches!(
+            command,
+            ServerCommand::Request(RequestCommand::State)
+        ));
+    }
+
+    #[test]
+    fn unknown_commands() {
+        let encodings = [Encoding::Json, Encoding::Cbor, Encoding::MessagePack];
+
+        for encoding in encodings.into_iter().filter(|e| e.is_supported()) {
+            let decode = |message: Value| {
+                let frame = encoding.encode(&message).unwrap();
+                let (payload, _) = encoding.frame(&frame).unwrap();
+                encoding.decode_command(payload)
+            };
+            let unknown = |command: &str| ProtocolError::UnknownCommand {
+                command: Some(command.to_owned()),
+            };
+
+            assert!(matches!(
+                decode(serde_json::json!("State")),
+                Ok(ServerCommand::Request(RequestCommand::State))
+            ));
+            assert_eq!(
+                decode(serde_json::json!("Reboot")).unwrap_err(),
+                unknown("Reboot")
+            );
+            assert_eq!(
+                decode(serde_json::json!({ "Reboot": { "delay": 5 } })).unwrap_err(),
+                unknown("Reboot")
+            );
+            assert_eq!(
+                decode(serde_json::json!({ "Remove": "all" })).unwrap_err(),
+                unknown("Remove")
+            );
+            assert_eq!(
+                decode(serde_json::json!(5)).unwrap_err(),
+                ProtocolError::UnknownCommand { command: None }
+            );
+
+            assert!(matches!(
+                encoding.decode_command(&[0xc1]),
+                Err(ProtocolError::Malformed { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn unsupported_encoding() {
+        if !Encoding::Cbor.is_supported() {
+            assert!(matches!(
+                Encoding::Cbor.encode(&RequestCommand::State),
+                Err(error::EncodeError::Unsupported(Encoding::Cbor))
+            ));
+        }
+    }
+}
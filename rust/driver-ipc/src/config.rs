@@ -0,0 +1,436 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{LogFilter, DEFAULT_PIPE_NAME};
+
+/// Path the driver loads its configuration from, unless the `ConfigPath`
+/// registry value under `HKLM\SOFTWARE\VirtualDisplayDriver` points elsewhere.
+pub static DEFAULT_CONFIG_PATH: &str = r"C:\ProgramData\VirtualDisplayDriver\config.json";
+
+/// Driver configuration.
+///
+/// Stored as JSON. Every field is optional and falls back to its default.
+/// Unknown fields are rejected, so typos don't go unnoticed.
+///
+/// Only some settings can be changed while the driver is running, see
+/// [DriverConfig::reload]. Everything else requires a driver restart.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct DriverConfig {
+    /// Maximum amount of monitors which can be enabled at the same time.
+    pub max_monitors: u32,
+    /// Name of the pipe the driver listens on, without the `\\.\pipe\` prefix.
+    pub pipe_name: String,
+    /// Size of the pipe's in and out buffers in bytes.
+    pub buffer_size: u32,
+    /// Accounts which may connect to the pipe, read the state and receive
+    /// events, see [crate::Access]. Either SIDs like `S-1-5-11`, SDDL aliases
+    /// like `AU` or account names like `BUILTIN\Users`.
+    pub read_access: Vec<String>,
+    /// Accounts which may also change monitors, record them and read the
+    /// driver log, in the same format as `read_access`.
+    pub control_access: Vec<String>,
+    /// Amount of monitor changes kept for clients which are slow to read
+    /// events. A client which falls further behind only receives the latest
+    /// state.
+    pub event_capacity: u32,
+    /// Largest message in bytes a client may send. Clients sending larger
+    /// messages, or messages which can't be decoded, are disconnected.
+    ///
+    /// Hot reloadable, applies to new connections.
+    pub max_message_size: u32,
+    /// Messages per second a client may send. Clients sending faster are
+    /// slowed down.
+    ///
+    /// Hot reloadable, applies to new connections.
+    pub rate_limit: u32,
+    /// File trace messages are appended to. `None` disables tracing.
+    ///
+    /// Hot reloadable.
+    pub trace_log_path: Option<String>,
+    /// Recording frame rate used if `StartRecording` does not specify one.
+    ///
+    /// Hot reloadable, applies to the next recording.
+    pub recording_fps: u32,
+    /// Directories recordings may be written to. Recording anywhere else is
+    /// refused.
+    ///
+    /// Hot reloadable.
+    pub recording_dirs: Vec<String>,
+    /// Bitrate of recordings in bits per second.
+    ///
+    /// Hot reloadable, applies to the next recording.
+    pub recording_bitrate: u32,
+    /// Amount of frame slots in the shared memory ring of each monitor.
+    ///
+    /// Hot reloadable, applies once a monitor's capture resources are
+    /// recreated.
+    pub shm_slots: u32,
+    /// Which records the driver logs after starting.
+    ///
+    /// Hot reloadable, replaces filters set with `SetLogLevel`.
+    pub log_filter: LogFilter,
+    /// Keep filters set with `SetLogLevel` across driver restarts, instead of
+    /// starting with `log_filter` again.
+    ///
+    /// Hot reloadable.
+    pub persist_log_filter: bool,
+}
+
+impl Default for DriverConfig {
+    fn default() -> Self {
+        Self {
+            max_monitors: 16,
+            pipe_name: DEFAULT_PIPE_NAME.to_owned(),
+            buffer_size: 4096,
+            // everyone may look, only the logged on user, admins and the
+            // system may make changes
+            read_access: vec!["WD".to_owned()],
+            control_access: vec!["IU".to_owned(), "BA".to_owned(), "SY".to_owned()],
+            event_capacity: 16,
+            max_message_size: 1024 * 1024,
+            rate_limit: 100,
+            trace_log_path: Some(r"C:\Windows\Temp\VDD_trace.log".to_owned()),
+            recording_fps: 5,
+            recording_dirs: vec![r"C:\ProgramData\VirtualDisplayDriver\Recordings".to_owned()],
+            recording_bitrate: 2_000_000,
+            shm_slots: 2,
+            log_filter: LogFilter::default(),
+            persist_log_filter: false,
+        }
+    }
+}
+
+impl DriverConfig {
+    /// Parse and validate a JSON configuration.
+    pub fn from_json(json: &str) -> Result<Self, error::ConfigError> {
+        let config = serde_json::from_str::<Self>(json)?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Check that all values are within their allowed range.
+    pub fn validate(&self) -> Result<(), error::ConfigError> {
+        use error::ConfigError::*;
+
+        if self.max_monitors == 0 {
+            return Err(OutOfRange("max_monitors", "at least 1"));
+        }
+
+        if self.pipe_name.is_empty() || self.pipe_name.contains('\\') {
+            return Err(InvalidPipeName(self.pipe_name.clone()));
+        }
+
+        if !(512..=1024 * 1024).contains(&self.buffer_size) {
+            return Err(OutOfRange("buffer_size", "between 512 and 1048576"));
+        }
+
+        if self
+            .read_access
+            .iter()
+            .chain(&self.control_access)
+            .any(String::is_empty)
+        {
+            return Err(OutOfRange("read_access and control_access", "non-empty accounts"));
+        }
+
+        if !(1..=1024).contains(&self.event_capacity) {
+            return Err(OutOfRange("event_capacity", "between 1 and 1024"));
+        }
+
+        if !(4096..=64 * 1024 * 1024).contains(&self.max_message_size) {
+            return Err(OutOfRange("max_message_size", "between 4096 and 67108864"));
+        }
+
+        if !(1..=100_000).contains(&self.rate_limit) {
+            return Err(OutOfRange("rate_limit", "between 1 and 100000"));
+        }
+
+        if self
+            .trace_log_path
+            .as_ref()
+            .is_some_and(|path| path.is_empty())
+        {
+            return Err(OutOfRange("trace_log_path", "non-empty, or null"));
+        }
+
+        if !(1..=240).contains(&self.recording_fps) {
+            return Err(OutOfRange("recording_fps", "between 1 and 240"));
+        }
+
+        if let Some(dir) = self
+            .recording_dirs
+            .iter()
+            .find(|dir| path_components(dir).is_none())
+        {
+            return Err(error::ConfigError::InvalidRecordingDir(dir.clone()));
+        }
+
+        if !(100_000..=100_000_000).contains(&self.recording_bitrate) {
+            return Err(OutOfRange(
+                "recording_bitrate",
+                "between 100000 and 100000000",
+            ));
+        }
+
+        if !(2..=16).contains(&self.shm_slots) {
+            return Err(OutOfRange("shm_slots", "between 2 and 16"));
+        }
+
+        if self.log_filter.targets.keys().any(String::is_empty) {
+            return Err(OutOfRange("log_filter", "without empty target names"));
+        }
+
+        Ok(())
+    }
+
+    /// Apply a newly loaded configuration to a running driver.
+    ///
+    /// Returns the new effective configuration, which takes the hot reloadable
+    /// settings from `new` and keeps everything else from `self`, and the names
+    /// of all changed settings which only take effect after a restart.
+    pub fn reload(&self, new: &Self) -> (Self, Vec<&'static str>) {
+        let mut ignored = Vec::new();
+
+        if self.max_monitors != new.max_monitors {
+            ignored.push("max_monitors");
+        }
+        if self.pipe_name != new.pipe_name {
+            ignored.push("pipe_name");
+        }
+        if self.buffer_size != new.buffer_size {
+            ignored.push("buffer_size");
+        }
+        if self.read_access != new.read_access {
+            ignored.push("read_access");
+        }
+        if self.control_access != new.control_access {
+            ignored.push("control_access");
+        }
+        if self.event_capacity != new.event_capacity {
+            ignored.push("event_capacity");
+        }
+
+        let config = Self {
+            max_message_size: new.max_message_size,
+            rate_limit: new.rate_limit,
+            trace_log_path: new.trace_log_path.clone(),
+            recording_fps: new.recording_fps,
+            recording_dirs: new.recording_dirs.clone(),
+            recording_bitrate: new.recording_bitrate,
+            shm_slots: new.shm_slots,
+            log_filter: new.log_filter.clone(),
+            persist_log_filter: new.persist_log_filter,
+            ..self.clone()
+        };
+
+        (config, ignored)
+    }
+
+    /// Check that a recording may be written to `path`, and return the entry
+    /// of `recording_dirs` it is in.
+    ///
+    /// The path must be an absolute path to an `.mp4` file. This only looks at
+    /// the path itself, the driver also resolves links before recording.
+    pub fn check_recording_path(&self, path: &str) -> Result<&str, error::RecordingPathError> {
+        use error::RecordingPathError::*;
+
+        let components = path_components(path).ok_or(NotAbsolute)?;
+
+        let is_mp4 = components
+            .last()
+            .is_some_and(|name| name.len() > 4 && name.ends_with(".mp4"));
+        if !is_mp4 {
+            return Err(NotMp4);
+        }
+
+        self.recording_dirs
+            .iter()
+            .find(|dir| {
+                path_components(dir).is_some_and(|dir| {
+                    components.len() > dir.len() && components.starts_with(&dir)
+                })
+            })
+            .map(String::as_str)
+            .ok_or(OutsideDirs)
+    }
+}
+
+/// Lower-cased components of an absolute Windows path like `C:\dir\file`
+///
+/// `None` for relative, UNC and device paths, for paths with `.` or `..`
+/// components and for alternate data streams, which could all escape a
+/// directory.
+fn path_components(path: &str) -> Option<Vec<String>> {
+    let path = path.replace('/', "\\");
+    let mut parts = path.split('\\');
+
+    let drive = parts.next()?;
+    let is_drive = drive.len() == 2
+        && drive.as_bytes()[0].is_ascii_alphabetic()
+        && drive.as_bytes()[1] == b':';
+    if !is_drive || path.len() < 3 {
+        return None;
+    }
+
+    let mut components = vec![drive.to_ascii_lowercase()];
+    for part in parts {
+        match part {
+            "" => (),
+            "." | ".." => return None,
+            part if part.contains(':') => return None,
+            part => components.push(part.to_lowercase()),
+        }
+    }
+
+    Some(components)
+}
+
+pub mod error {
+    use thiserror::Error;
+
+    /// Error returned from [super::DriverConfig::from_json] and
+    /// [super::DriverConfig::validate].
+    #[derive(Debug, Error)]
+    pub enum ConfigError {
+        #[error("Failed to parse config: {0}")]
+        Parse(#[from] serde_json::Error),
+        #[error("Invalid value for {0}: must be {1}")]
+        OutOfRange(&'static str, &'static str),
+        #[error("Invalid pipe name {0:?}: must be non-empty and must not contain '\\'")]
+        InvalidPipeName(String),
+        #[error("Invalid recording directory {0:?}: must be an absolute path like C:\\dir")]
+        InvalidRecordingDir(String),
+    }
+
+    /// Error returned from [super::DriverConfig::check_recording_path].
+    #[derive(Debug, Error, PartialEq, Eq)]
+    pub enum RecordingPathError {
+        #[error("must be an absolute path like C:\\dir\\file.mp4, without . or ..")]
+        NotAbsolute,
+        #[error("must be an .mp4 file")]
+        NotMp4,
+        #[error("must be in one of the recording directories")]
+        OutsideDirs,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_config_is_default() {
+        let config = DriverConfig::from_json("{}").unwrap();
+        assert_eq!(config, DriverConfig::default());
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        DriverConfig::default().validate().unwrap();
+    }
+
+    #[test]
+    fn partial_config_keeps_defaults() {
+        let config =
+            DriverConfig::from_json(r#"{"max_monitors":32,"trace_log_path":null}"#).unwrap();
+
+        assert_eq!(config.max_monitors, 32);
+        assert_eq!(config.trace_log_path, None);
+        assert_eq!(config.pipe_name, DEFAULT_PIPE_NAME);
+        assert_eq!(config.recording_fps, 5);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let err = DriverConfig::from_json(r#"{"max_monitor":32}"#).unwrap_err();
+        assert!(matches!(err, error::ConfigError::Parse(_)), "{err:?}");
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        for json in [
+            r#"{"max_monitors":0}"#,
+            r#"{"buffer_size":16}"#,
+            r#"{"event_capacity":0}"#,
+            r#"{"max_message_size":16}"#,
+            r#"{"rate_limit":0}"#,
+            r#"{"trace_log_path":""}"#,
+            r#"{"recording_fps":0}"#,
+            r#"{"recording_bitrate":1}"#,
+            r#"{"shm_slots":1}"#,
+            r#"{"log_filter":{"level":"info","targets":{"":"debug"}}}"#,
+        ] {
+            let err = DriverConfig::from_json(json).unwrap_err();
+            assert!(
+                matches!(err, error::ConfigError::OutOfRange(..)),
+                "{json}: {err:?}"
+            );
+        }
+
+        let err = DriverConfig::from_json(r#"{"pipe_name":"a\\b"}"#).unwrap_err();
+        assert!(
+            matches!(err, error::ConfigError::InvalidPipeName(_)),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn recording_paths_are_restricted() {
+        use error::RecordingPathError::*;
+
+        let config = DriverConfig {
+            recording_dirs: vec![r"C:\Recordings".to_owned(), "D:/Videos/".to_owned()],
+            ..DriverConfig::default()
+        };
+
+        for (path, dir) in [
+            (r"C:\Recordings\a.mp4", r"C:\Recordings"),
+            (r"c:\recordings\sub\B.MP4", r"C:\Recordings"),
+            ("D:/Videos/a.mp4", "D:/Videos/"),
+        ] {
+            assert_eq!(config.check_recording_path(path), Ok(dir), "{path}");
+        }
+
+        for (path, err) in [
+            (r"C:\Recordings\..\Windows\a.mp4", NotAbsolute),
+            (r"Recordings\a.mp4", NotAbsolute),
+            (r"\\server\share\a.mp4", NotAbsolute),
+            (r"\\?\C:\Recordings\a.mp4", NotAbsolute),
+            (r"C:\Recordings\a.mp4:stream", NotAbsolute),
+            (r"C:\Recordings\a.dll", NotMp4),
+            (r"C:\Recordings\.mp4", NotMp4),
+            (r"C:\RecordingsX\a.mp4", OutsideDirs),
+            (r"C:\Windows\a.mp4", OutsideDirs),
+        ] {
+            assert_eq!(config.check_recording_path(path), Err(err), "{path}");
+        }
+
+        let err = DriverConfig::from_json(r#"{"recording_dirs":["Recordings"]}"#).unwrap_err();
+        assert!(
+            matches!(err, error::ConfigError::InvalidRecordingDir(_)),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn reload_only_applies_hot_settings() {
+        let current = DriverConfig::default();
+        let new = DriverConfig {
+            max_monitors: 4,
+            pipe_name: "other".to_owned(),
+            trace_log_path: None,
+            recording_fps: 30,
+            ..DriverConfig::default()
+        };
+
+        let (config, ignored) = current.reload(&new);
+
+        assert_eq!(ignored, ["max_monitors", "pipe_name"]);
+        assert_eq!(config.max_monitors, current.max_monitors);
+        assert_eq!(config.pipe_name, current.pipe_name);
+        assert_eq!(config.trace_log_path, None);
+        assert_eq!(config.recording_fps, 30);
+    }
+}
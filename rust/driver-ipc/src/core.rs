@@ -1,27 +1,886 @@
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
 use serde::{Deserialize, Serialize};
 
+use crate::DriverConfig;
+
 pub type Id = u32;
 pub type Dimen = u32;
 pub type RefreshRate = u32;
 
-#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Monitor {
     // identifier
     pub id: Id,
     pub name: Option<String>,
+    /// Stable key chosen by the user, unique among all monitors, e.g. a UUID
+    /// or `office-left`. Unlike the ID, it does not depend on the order
+    /// monitors were created in, so it can address a monitor across reboots
+    /// and re-creation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
     pub enabled: bool,
+    /// Modes as seen on the desktop, so `1080x1920` for a portrait monitor
     pub modes: Vec<Mode>,
+    /// Physical size reported to the OS, which derives the DPI scaling from
+    /// it. Without one, every monitor reports the same size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical_size: Option<PhysicalSize>,
+    /// Orientation of the desktop on the monitor. The monitor itself reports
+    /// the modes and physical size unrotated, like a rotated landscape panel.
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub orientation: Orientation,
+    /// Desired position of the top left corner on the desktop. Without one,
+    /// the monitor is placed next to the others, see [crate::layout].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<Position>,
+    /// Whether the monitor should be the primary display
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub primary: bool,
+    /// Whether the monitor extends or duplicates the desktop
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub topology: Topology,
+    /// Bit depth, color formats and HDR capability reported to the OS
+    #[serde(default, skip_serializing_if = "is_default")]
+    pub color: Color,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl Monitor {
+    /// The physical width and height in millimeters, if known.
+    ///
+    /// A [PhysicalSize::Dpi] is converted using the first mode, which is the
+    /// monitor's preferred mode.
+    pub fn physical_size_mm(&self) -> Option<(Dimen, Dimen)> {
+        match self.physical_size? {
+            PhysicalSize::Millimeters { width, height } => Some((width, height)),
+            PhysicalSize::Dpi(0) => None,
+            PhysicalSize::Dpi(dpi) => {
+                let mode = self.modes.first()?;
+                // 1 inch = 25.4 mm, rounded to the nearest mm
+                let mm = |px: Dimen| {
+                    let mm = (u64::from(px) * 254 + u64::from(dpi) * 5) / (u64::from(dpi) * 10);
+                    Dimen::try_from(mm).unwrap_or(Dimen::MAX)
+                };
+
+                Some((mm(mode.width), mm(mode.height)))
+            }
+        }
+    }
+}
+
+/// Position on the desktop, the primary display is at the origin
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{},{}", self.x, self.y)
+    }
+}
+
+/// How a monitor is part of the desktop
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Topology {
+    /// Show its own part of the desktop
+    #[default]
+    Extend,
+    /// Show the same as the primary display
+    Duplicate,
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topology::Extend => write!(f, "extend"),
+            Topology::Duplicate => write!(f, "duplicate"),
+        }
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "extend" => Ok(Topology::Extend),
+            "duplicate" => Ok(Topology::Duplicate),
+            _ => Err(format!("invalid topology `{s}`, expected `extend` or `duplicate`")),
+        }
+    }
+}
+
+/// Physical size of a monitor
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum PhysicalSize {
+    /// Width and height of the visible area in millimeters, as seen on the
+    /// desktop
+    Millimeters { width: Dimen, height: Dimen },
+    /// Dots per inch of the preferred mode, the size follows from its
+    /// resolution
+    Dpi(u32),
+}
+
+/// Orientation of the desktop on a monitor, in degrees clockwise
+///
+/// Matches the display orientation in the Windows display settings, which has
+/// to be set to the same value. The OS renders the desktop rotated, so frames
+/// are rotated back when recording.
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "u32", into = "u32")]
+pub enum Orientation {
+    #[default]
+    Landscape,
+    Portrait,
+    LandscapeFlipped,
+    PortraitFlipped,
+}
+
+impl Orientation {
+    pub fn degrees(self) -> u32 {
+        match self {
+            Orientation::Landscape => 0,
+            Orientation::Portrait => 90,
+            Orientation::LandscapeFlipped => 180,
+            Orientation::PortraitFlipped => 270,
+        }
+    }
+
+    pub fn from_degrees(degrees: u32) -> Option<Self> {
+        match degrees {
+            0 => Some(Orientation::Landscape),
+            90 => Some(Orientation::Portrait),
+            180 => Some(Orientation::LandscapeFlipped),
+            270 => Some(Orientation::PortraitFlipped),
+            _ => None,
+        }
+    }
+
+    pub fn is_portrait(self) -> bool {
+        matches!(self, Orientation::Portrait | Orientation::PortraitFlipped)
+    }
+
+    /// Convert between desktop and monitor dimensions, which are swapped in
+    /// portrait orientations.
+    pub fn rotate<T>(self, width: T, height: T) -> (T, T) {
+        if self.is_portrait() {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+}
+
+impl TryFrom<u32> for Orientation {
+    type Error = String;
+
+    fn try_from(degrees: u32) -> Result<Self, Self::Error> {
+        Self::from_degrees(degrees)
+            .ok_or_else(|| format!("invalid orientation {degrees}, expected 0, 90, 180 or 270"))
+    }
+}
+
+impl From<Orientation> for u32 {
+    fn from(orientation: Orientation) -> Self {
+        orientation.degrees()
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Orientation::Landscape => "landscape",
+            Orientation::Portrait => "portrait",
+            Orientation::LandscapeFlipped => "landscape-flipped",
+            Orientation::PortraitFlipped => "portrait-flipped",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Parses the degrees or the name, e.g. `90` or `portrait`
+impl FromStr for Orientation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(degrees) = s.parse::<u32>() {
+            return Self::try_from(degrees);
+        }
+
+        [
+            Orientation::Landscape,
+            Orientation::Portrait,
+            Orientation::LandscapeFlipped,
+            Orientation::PortraitFlipped,
+        ]
+        .into_iter()
+        .find(|orientation| orientation.to_string() == s)
+        .ok_or_else(|| format!("invalid orientation `{s}`, expected degrees or e.g. `portrait`"))
+    }
+}
+
+impl fmt::Display for PhysicalSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PhysicalSize::Millimeters { width, height } => write!(f, "{width}x{height} mm"),
+            PhysicalSize::Dpi(dpi) => write!(f, "{dpi} dpi"),
+        }
+    }
+}
+
+/// Color capabilities of a monitor, reported to the OS in its EDID
+///
+/// Only the EDID is affected. The driver doesn't opt into the HDR and wide
+/// color support of IddCx 1.10, so the OS still renders to 8-bit BGRA surfaces.
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Color {
+    #[serde(default)]
+    pub bit_depth: BitDepth,
+    #[serde(default)]
+    pub format: ColorFormat,
+    /// HDR static metadata. Without it, the monitor only supports SDR.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hdr: Option<Hdr>,
+}
+
+/// Bits per color component
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "u8", into = "u8")]
+pub enum BitDepth {
+    Six,
+    #[default]
+    Eight,
+    Ten,
+    Twelve,
+    Fourteen,
+    Sixteen,
+}
+
+impl BitDepth {
+    pub fn bits(self) -> u8 {
+        match self {
+            BitDepth::Six => 6,
+            BitDepth::Eight => 8,
+            BitDepth::Ten => 10,
+            BitDepth::Twelve => 12,
+            BitDepth::Fourteen => 14,
+            BitDepth::Sixteen => 16,
+        }
+    }
+
+    pub fn from_bits(bits: u8) -> Option<Self> {
+        match bits {
+            6 => Some(BitDepth::Six),
+            8 => Some(BitDepth::Eight),
+            10 => Some(BitDepth::Ten),
+            12 => Some(BitDepth::Twelve),
+            14 => Some(BitDepth::Fourteen),
+            16 => Some(BitDepth::Sixteen),
+            _ => None,
+        }
+    }
+}
+
+impl TryFrom<u8> for BitDepth {
+    type Error = String;
+
+    fn try_from(bits: u8) -> Result<Self, Self::Error> {
+        Self::from_bits(bits).ok_or_else(|| {
+            format!("invalid bit depth {bits}, expected 6, 8, 10, 12, 14 or 16")
+        })
+    }
+}
+
+impl From<BitDepth> for u8 {
+    fn from(bit_depth: BitDepth) -> Self {
+        bit_depth.bits()
+    }
+}
+
+impl fmt::Display for BitDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-bit", self.bits())
+    }
+}
+
+/// Parses the bits, e.g. `10` or `10-bit`
+impl FromStr for BitDepth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bits = s.strip_suffix("-bit").unwrap_or(s);
+        let bits = bits
+            .parse::<u8>()
+            .map_err(|_| format!("invalid bit depth `{s}`, expected e.g. `10`"))?;
+
+        Self::try_from(bits)
+    }
+}
+
+/// Color encodings a monitor accepts. RGB is always supported.
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ColorFormat {
+    #[default]
+    #[serde(rename = "rgb")]
+    Rgb,
+    /// RGB and YCbCr 4:4:4
+    #[serde(rename = "ycbcr444")]
+    YCbCr444,
+    /// RGB, YCbCr 4:4:4 and YCbCr 4:2:2
+    #[serde(rename = "ycbcr422")]
+    YCbCr422,
+}
+
+impl fmt::Display for ColorFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorFormat::Rgb => "rgb",
+            ColorFormat::YCbCr444 => "ycbcr444",
+            ColorFormat::YCbCr422 => "ycbcr422",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ColorFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [ColorFormat::Rgb, ColorFormat::YCbCr444, ColorFormat::YCbCr422]
+            .into_iter()
+            .find(|format| format.to_string() == s)
+            .ok_or_else(|| {
+                format!("invalid color format `{s}`, expected `rgb`, `ycbcr444` or `ycbcr422`")
+            })
+    }
+}
+
+/// HDR static metadata, the range and gamut of the panel
+///
+/// A monitor with it supports the SMPTE ST 2084 (PQ) transfer function in the
+/// BT.2020 color space, i.e. HDR10.
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Hdr {
+    /// Peak luminance in nits (cd/m²)
+    pub max_luminance: f32,
+    /// Black level in nits (cd/m²)
+    pub min_luminance: f32,
+    #[serde(default)]
+    pub primaries: ColorPrimaries,
+}
+
+/// A typical HDR monitor with 1000 nits and the DCI-P3 gamut
+impl Default for Hdr {
+    fn default() -> Self {
+        Self {
+            max_luminance: 1000.0,
+            min_luminance: 0.05,
+            primaries: ColorPrimaries::DciP3,
+        }
+    }
+}
+
+/// Gamut of a panel, as the color primaries of a standard
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ColorPrimaries {
+    /// BT.709, same as sRGB
+    #[default]
+    Bt709,
+    /// DCI-P3 with a D65 white point, same as Display P3
+    DciP3,
+    Bt2020,
+}
+
+impl ColorPrimaries {
+    /// CIE 1931 xy coordinates of the red, green and blue primaries and the
+    /// white point
+    pub fn chromaticity(self) -> [(f32, f32); 4] {
+        const D65: (f32, f32) = (0.3127, 0.3290);
+
+        match self {
+            ColorPrimaries::Bt709 => [(0.640, 0.330), (0.300, 0.600), (0.150, 0.060), D65],
+            ColorPrimaries::DciP3 => [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060), D65],
+            ColorPrimaries::Bt2020 => [(0.708, 0.292), (0.170, 0.797), (0.131, 0.046), D65],
+        }
+    }
+}
+
+impl fmt::Display for ColorPrimaries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ColorPrimaries::Bt709 => "bt709",
+            ColorPrimaries::DciP3 => "dci-p3",
+            ColorPrimaries::Bt2020 => "bt2020",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ColorPrimaries {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            ColorPrimaries::Bt709,
+            ColorPrimaries::DciP3,
+            ColorPrimaries::Bt2020,
+        ]
+        .into_iter()
+        .find(|primaries| primaries.to_string() == s)
+        .ok_or_else(|| {
+            format!("invalid color primaries `{s}`, expected `bt709`, `dci-p3` or `bt2020`")
+        })
+    }
+}
+
+impl fmt::Display for Hdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "HDR {}-{} nits {}",
+            self.min_luminance, self.max_luminance, self.primaries
+        )
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.bit_depth, self.format)?;
+        if let Some(hdr) = &self.hdr {
+            write!(f, " {hdr}")?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct Mode {
     pub width: Dimen,
     pub height: Dimen,
     pub refresh_rates: Vec<RefreshRate>,
 }
 
+/// Capabilities of the virtual display adapter
+///
+/// Requested with [RequestCommand::Capabilities] instead of being part of
+/// [ReplyCommand::State], which stays a plain list of monitors for existing
+/// clients. A [DriverCommand::Notify] over the limit is answered with
+/// [Rejection::LimitExceeded].
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Capabilities {
+    /// Maximum amount of monitors which can be enabled at the same time
+    pub max_monitors: u32,
+}
+
+/// Encoding of IPC messages, see [crate::codec]
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Encoding {
+    /// JSON documents, each followed by an EOT byte. Every connection starts
+    /// out with it.
+    #[default]
+    Json,
+    /// CBOR documents, each prefixed with its length
+    Cbor,
+    /// MessagePack documents, each prefixed with its length
+    MessagePack,
+}
+
+/// Severity of a driver log record, from most to least severe
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    const ALL: [LogLevel; 5] = [
+        LogLevel::Error,
+        LogLevel::Warn,
+        LogLevel::Info,
+        LogLevel::Debug,
+        LogLevel::Trace,
+    ];
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+/// Parses the name, e.g. `warn`
+impl FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        LogLevel::ALL
+            .into_iter()
+            .find(|level| level.to_string().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("invalid log level `{s}`, expected e.g. `warn` or `debug`"))
+    }
+}
+
+/// A record of the driver log, see [RequestCommand::Logs]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LogRecord {
+    /// Increases by one with every record. Pass the last one seen as `since`
+    /// to continue after it.
+    pub seq: u64,
+    /// Milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    pub level: LogLevel,
+    /// Module which logged the record
+    pub target: String,
+    pub message: String,
+}
+
+/// Which records the driver logs, see [DriverCommand::SetLogLevel]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LogFilter {
+    /// Least severe level logged by targets without their own level
+    pub level: LogLevel,
+    /// Least severe level logged by a module and its submodules, e.g.
+    /// `virtual_display_driver::ipc`. The longest matching module wins.
+    #[serde(default)]
+    pub targets: BTreeMap<String, LogLevel>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            level: LogLevel::Info,
+            targets: BTreeMap::new(),
+        }
+    }
+}
+
+impl LogFilter {
+    /// Least severe level logged by `target`
+    pub fn level_of(&self, target: &str) -> LogLevel {
+        self.targets
+            .iter()
+            .filter(|(module, _)| {
+                target
+                    .strip_prefix(module.as_str())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    /// Whether a record of `target` at `level` is logged
+    pub fn enabled(&self, target: &str, level: LogLevel) -> bool {
+        level <= self.level_of(target)
+    }
+
+    /// Least severe level logged by any target
+    pub fn max_level(&self) -> LogLevel {
+        self.targets
+            .values()
+            .copied()
+            .fold(self.level, LogLevel::max)
+    }
+}
+
+/// Build and liveness of the driver, see [RequestCommand::Health]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Health {
+    /// Version of the driver
+    pub version: String,
+    /// Commit the driver was built from
+    pub git_sha: String,
+    /// Minimum IddCx version the driver was built to require, e.g. `1.4`. The
+    /// OS may provide a newer one.
+    ///
+    /// Named `iddcx_version` by older drivers.
+    #[serde(alias = "iddcx_version")]
+    pub iddcx_min_version: String,
+    /// Time since the driver was loaded
+    pub uptime_ms: u64,
+    /// Whether the OS finished initializing the display adapter. No monitor
+    /// can be added before.
+    pub adapter_initialized: bool,
+    /// Connected IPC clients, including the one asking
+    pub clients: u32,
+    /// Whether the pipe server still accepts new clients
+    pub pipe_server: bool,
+    /// One entry per monitor which received a swap chain
+    pub swap_chains: Vec<SwapChainHealth>,
+}
+
+/// Liveness of the swap chain thread of a monitor
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SwapChainHealth {
+    pub id: Id,
+    /// Whether the thread still processes frames. It stops on a swap chain
+    /// error until the OS assigns a new swap chain.
+    pub alive: bool,
+}
+
+/// Counters of the driver, see [RequestCommand::Stats]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Stats {
+    /// One entry per monitor which received a swap chain
+    pub monitors: Vec<MonitorStats>,
+    /// Counters of the active recording, if any
+    pub recording: Option<RecordingStats>,
+}
+
+/// Swap chain counters of a single monitor, summed up over all swap chains
+/// the OS assigned to it since the driver started
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct MonitorStats {
+    pub id: Id,
+    /// Frames acquired from the swap chain
+    pub frames_acquired: u64,
+    /// Frames copied to the shared memory of a recording
+    pub frames_captured: u64,
+    /// Times the swap chain had no new frame yet
+    pub pending_waits: u64,
+    /// Swap chain failures, each one ends the swap chain until the OS assigns
+    /// a new one
+    pub swap_chain_errors: u64,
+    /// Surface of the last acquired frame
+    pub surface: Option<Surface>,
+}
+
+/// Size and format of an acquired frame
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Surface {
+    pub width: Dimen,
+    pub height: Dimen,
+    /// `DXGI_FORMAT` of the texture, e.g. 87 for `DXGI_FORMAT_B8G8R8A8_UNORM`
+    pub format: u32,
+}
+
+/// Counters of an active recording
+#[derive(Debug, Copy, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RecordingStats {
+    /// Frames handed to the encoder
+    pub frames_sent: u64,
+    /// Frames dropped because the encoder fell behind
+    pub frames_dropped: u64,
+    pub duration_ms: u64,
+}
+
+/// What a client may do, from least to most
+///
+/// Which clients get which access is configured with
+/// [DriverConfig::read_access] and [DriverConfig::control_access].
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum Access {
+    /// Read the state of monitors and the driver, and receive events
+    Read,
+    /// Also change monitors, record them and read the driver log
+    Control,
+}
+
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Control => write!(f, "control"),
+        }
+    }
+}
+
+/// The driver refused a command, see [ReplyCommand::PermissionDenied]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PermissionDenied {
+    /// Name of the refused command, see [ServerCommand::name]
+    pub command: String,
+    pub reason: DenyReason,
+}
+
+/// Why the driver refused a command
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum DenyReason {
+    /// The command requires more access than the client has
+    Access { required: Access },
+    /// The recording output path is not allowed, see
+    /// [DriverConfig::recording_dirs]
+    RecordingPath { path: String, reason: String },
+}
+
+impl fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            DenyReason::Access { required } => {
+                write!(f, "{} requires {required} access", self.command)
+            }
+            DenyReason::RecordingPath { path, reason } => {
+                write!(f, "{} to {path:?} is not allowed: {reason}", self.command)
+            }
+        }
+    }
+}
+
+/// Why the driver could not handle a message of a client, see
+/// [ReplyCommand::ProtocolError]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum ProtocolError {
+    /// A message was larger than [DriverConfig::max_message_size]
+    TooLarge { max: u32 },
+    /// A message could not be decoded
+    Malformed { reason: String },
+    /// A message was well-formed, but no command the driver knows, e.g. one
+    /// added in a later version or one with invalid fields. `command` is its
+    /// name, if it has one.
+    UnknownCommand { command: Option<String> },
+}
+
+impl ProtocolError {
+    /// Whether the driver closes the connection after sending this
+    ///
+    /// Only [ProtocolError::UnknownCommand] keeps it open, the driver can't
+    /// tell where the next message starts after the others.
+    pub fn disconnects(&self) -> bool {
+        !matches!(self, ProtocolError::UnknownCommand { .. })
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolError::TooLarge { max } => {
+                write!(f, "Message exceeds the maximum size of {max} bytes")
+            }
+            ProtocolError::Malformed { reason } => write!(f, "Malformed message: {reason}"),
+            ProtocolError::UnknownCommand { command: Some(command) } => {
+                write!(f, "Unknown or invalid command {command}")
+            }
+            ProtocolError::UnknownCommand { command: None } => {
+                write!(f, "Message is not a command")
+            }
+        }
+    }
+}
+
+/// Why the driver refused the monitors of a [DriverCommand::Notify], see
+/// [ReplyCommand::Rejected]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub enum Rejection {
+    /// More monitors were enabled than [Capabilities::max_monitors]
+    LimitExceeded { enabled: u32, max_monitors: u32 },
+    /// Monitor IDs, modes or refresh rates were not unique
+    Duplicates,
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::LimitExceeded {
+                enabled,
+                max_monitors,
+            } => write!(
+                f,
+                "{enabled} monitors enabled, but at most {max_monitors} are supported"
+            ),
+            Rejection::Duplicates => write!(f, "Duplicate monitors, modes or refresh rates"),
+        }
+    }
+}
+
+/// Kind of an [EventCommand], see [RequestCommand::Subscribe]
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Changed,
+    Log,
+}
+
+/// Which events a client receives, see [RequestCommand::Subscribe]
+#[derive(Debug, Clone, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EventFilter {
+    /// Kinds of events to receive, all if `None`
+    #[serde(default)]
+    pub kinds: Option<Vec<EventKind>>,
+    /// Monitors to receive `Changed` events of, all if `None`. Other monitors
+    /// are left out of the events.
+    #[serde(default)]
+    pub monitor_ids: Option<Vec<Id>>,
+}
+
+impl EventFilter {
+    /// The part of `event` this filter lets through, if any
+    pub fn apply(&self, event: EventCommand) -> Option<EventCommand> {
+        if self
+            .kinds
+            .as_ref()
+            .is_some_and(|kinds| !kinds.contains(&event.kind()))
+        {
+            return None;
+        }
+
+        match (event, &self.monitor_ids) {
+            (EventCommand::Changed(mut monitors), Some(ids)) => {
+                monitors.retain(|m| ids.contains(&m.id));
+                Some(EventCommand::Changed(monitors))
+            }
+            (event, _) => Some(event),
+        }
+    }
+}
+
 #[non_exhaustive]
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum DriverCommand {
     // Single line of communication client->server
     // Driver commands
@@ -42,21 +901,61 @@ pub enum DriverCommand {
     },
     // Stop recording frames
     StopRecording,
+    // Change which records the driver logs, see `LogFilter`. Lasts until the
+    // driver restarts, unless `persist_log_filter` is configured
+    SetLogLevel {
+        level: LogLevel,
+        #[serde(default)]
+        targets: BTreeMap<String, LogLevel>,
+    },
 }
 
 /// Request command sent from client->server
 #[non_exhaustive]
 #[derive(Debug, Clone, Deserialize, Serialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum RequestCommand {
     // Request information on the current system monitor state
     State,
     // Request recording state
     RecordingState,
+    // Request the adapter capabilities, like the monitor limit
+    Capabilities,
+    // Request the effective driver configuration
+    Config,
+    // Switch all following messages to another encoding, see `crate::codec`
+    SetEncoding(Encoding),
+    // Request the frame and swap chain counters
+    Stats,
+    // Request build and liveness information
+    Health,
+    // Request the recent records of the driver log which are at least as
+    // severe as `level`, and newer than the record with sequence number `since`
+    Logs {
+        #[serde(default)]
+        since: Option<u64>,
+        #[serde(default)]
+        level: Option<LogLevel>,
+    },
+    // Stream new log records at least as severe as the level to this client
+    // as `EventCommand::Log`, or stop with `None`
+    SubscribeLogs(Option<LogLevel>),
+    // Only send this client the events matching the filter, see `EventFilter`.
+    // Without one, it receives all events
+    Subscribe {
+        #[serde(default)]
+        kinds: Option<Vec<EventKind>>,
+        #[serde(default)]
+        monitor_ids: Option<Vec<Id>>,
+    },
+    // Request the access this client has
+    Access,
 }
 
 /// Reply command sent from server->client
 #[non_exhaustive]
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum ReplyCommand {
     // Reply to previous current system monitor state request
     State(Vec<Monitor>),
@@ -78,14 +977,50 @@ pub enum ReplyCommand {
         frames: u64,
         duration_ms: u64,
     },
+    // Reply with the adapter capabilities
+    Capabilities(Capabilities),
+    // Reply with the effective driver configuration
+    Config(DriverConfig),
+    // Reply with the encoding of all messages after this one
+    Encoding(Encoding),
+    // Reply with the frame and swap chain counters
+    Stats(Stats),
+    // Reply with build and liveness information
+    Health(Health),
+    // Reply with recent log records, oldest first
+    Logs(Vec<LogRecord>),
+    // Reply with the access this client has
+    Access(Access),
+    // The driver refused a command of this client, instead of executing it
+    // and sending its reply, if any
+    PermissionDenied(PermissionDenied),
+    // The client sent something the driver can't handle, the driver closes
+    // the connection after this unless `ProtocolError::disconnects` is false
+    ProtocolError(ProtocolError),
+    // The driver applied the monitors of a `Notify`
+    Notified,
+    // The driver refused the monitors of a `Notify` and kept the previous ones
+    Rejected(Rejection),
 }
 
 /// An event happened
 #[non_exhaustive]
-#[derive(Debug, Clone, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub enum EventCommand {
     // Monitor state was changed while client was connected
     Changed(Vec<Monitor>),
+    // The driver logged a record, see `RequestCommand::SubscribeLogs`
+    Log(LogRecord),
+}
+
+impl EventCommand {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            EventCommand::Changed(_) => EventKind::Changed,
+            EventCommand::Log(_) => EventKind::Log,
+        }
+    }
 }
 
 /// An untagged enum of commands to be used with deserialization.
@@ -93,17 +1028,73 @@ pub enum EventCommand {
 /// when a received command could be of multiple types
 #[non_exhaustive]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 pub enum ServerCommand {
     Driver(DriverCommand),
     Request(RequestCommand),
 }
 
+impl From<DriverCommand> for ServerCommand {
+    fn from(command: DriverCommand) -> Self {
+        ServerCommand::Driver(command)
+    }
+}
+
+impl From<RequestCommand> for ServerCommand {
+    fn from(command: RequestCommand) -> Self {
+        ServerCommand::Request(command)
+    }
+}
+
+impl ServerCommand {
+    /// Name of the command as sent, e.g. `RemoveAll`
+    pub fn name(&self) -> &'static str {
+        match self {
+            ServerCommand::Driver(command) => match command {
+                DriverCommand::Notify(_) => "Notify",
+                DriverCommand::Remove(_) => "Remove",
+                DriverCommand::RemoveAll => "RemoveAll",
+                DriverCommand::StartRecording { .. } => "StartRecording",
+                DriverCommand::StopRecording => "StopRecording",
+                DriverCommand::SetLogLevel { .. } => "SetLogLevel",
+            },
+            ServerCommand::Request(command) => match command {
+                RequestCommand::State => "State",
+                RequestCommand::RecordingState => "RecordingState",
+                RequestCommand::Capabilities => "Capabilities",
+                RequestCommand::Config => "Config",
+                RequestCommand::SetEncoding(_) => "SetEncoding",
+                RequestCommand::Stats => "Stats",
+                RequestCommand::Health => "Health",
+                RequestCommand::Logs { .. } => "Logs",
+                RequestCommand::SubscribeLogs(_) => "SubscribeLogs",
+                RequestCommand::Subscribe { .. } => "Subscribe",
+                RequestCommand::Access => "Access",
+            },
+        }
+    }
+
+    /// Access a client needs to send the command
+    ///
+    /// Everything which changes the driver, and the driver log, which may
+    /// contain paths and names of other users, requires [Access::Control].
+    pub fn required_access(&self) -> Access {
+        match self {
+            ServerCommand::Driver(_)
+            | ServerCommand::Request(RequestCommand::Logs { .. })
+            | ServerCommand::Request(RequestCommand::SubscribeLogs(_)) => Access::Control,
+            ServerCommand::Request(_) => Access::Read,
+        }
+    }
+}
+
 /// An untagged enum of commands to be used with deserialization.
 /// This makes the deserialization process much easier to handle
 /// when a received command could be of multiple types
 #[non_exhaustive]
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(untagged)]
 pub enum ClientCommand {
     Reply(ReplyCommand),
@@ -154,4 +1145,248 @@ mod tests {
         assert!(json.contains("RecordingFinished"));
         assert!(json.contains("out.mp4"));
     }
+
+    #[test]
+    fn capabilities_round_trip() {
+        let json = serde_json::to_string(&RequestCommand::Capabilities).unwrap();
+        assert_eq!(json, r#""Capabilities""#);
+
+        let reply = ReplyCommand::Capabilities(Capabilities { max_monitors: 32 });
+        let json = serde_json::to_string(&reply).unwrap();
+        assert_eq!(json, r#"{"Capabilities":{"max_monitors":32}}"#);
+
+        let cmd: ClientCommand = serde_json::from_str(&json).unwrap();
+        assert!(matches!(
+            cmd,
+            ClientCommand::Reply(ReplyCommand::Capabilities(Capabilities { max_monitors: 32 }))
+        ));
+    }
+
+    #[test]
+    fn health_reads_old_iddcx_version() {
+        let json = r#"{"version":"0.1.0","git_sha":"abc","iddcx_version":"1.4","uptime_ms":0,
+            "adapter_initialized":true,"clients":1,"pipe_server":true,"swap_chains":[]}"#;
+        let health: Health = serde_json::from_str(json).unwrap();
+        assert_eq!(health.iddcx_min_version, "1.4");
+    }
+
+    #[test]
+    fn physical_size_mm() {
+        let mut monitor = Monitor {
+            id: 0,
+            enabled: true,
+            modes: vec![Mode {
+                width: 3840,
+                height: 2160,
+                refresh_rates: vec![60],
+            }],
+            ..Default::default()
+        };
+        assert_eq!(monitor.physical_size_mm(), None);
+
+        monitor.physical_size = Some(PhysicalSize::Millimeters {
+            width: 597,
+            height: 336,
+        });
+        assert_eq!(monitor.physical_size_mm(), Some((597, 336)));
+
+        monitor.physical_size = Some(PhysicalSize::Dpi(163));
+        assert_eq!(monitor.physical_size_mm(), Some((598, 337)));
+
+        monitor.physical_size = Some(PhysicalSize::Dpi(0));
+        assert_eq!(monitor.physical_size_mm(), None);
+
+        monitor.physical_size = Some(PhysicalSize::Dpi(96));
+        monitor.modes.clear();
+        assert_eq!(monitor.physical_size_mm(), None);
+    }
+
+    #[test]
+    fn orientation() {
+        assert_eq!(Orientation::Portrait.rotate(1920, 1080), (1080, 1920));
+        assert_eq!(Orientation::LandscapeFlipped.rotate(1920, 1080), (1920, 1080));
+
+        assert_eq!("270".parse(), Ok(Orientation::PortraitFlipped));
+        assert_eq!("portrait".parse(), Ok(Orientation::Portrait));
+        assert!("45".parse::<Orientation>().is_err());
+        assert!("sideways".parse::<Orientation>().is_err());
+
+        let json = r#"{"id":0,"name":null,"enabled":true,"modes":[],"orientation":90}"#;
+        let monitor: Monitor = serde_json::from_str(json).unwrap();
+        assert_eq!(monitor.orientation, Orientation::Portrait);
+        assert_eq!(serde_json::to_string(&monitor).unwrap(), json);
+
+        let json = r#"{"id":0,"name":null,"enabled":true,"modes":[],"orientation":45}"#;
+        assert!(serde_json::from_str::<Monitor>(json).is_err());
+    }
+
+    #[test]
+    fn log_level() {
+        assert!(LogLevel::Error < LogLevel::Warn);
+        assert!(LogLevel::Debug < LogLevel::Trace);
+
+        assert_eq!("warn".parse(), Ok(LogLevel::Warn));
+        assert_eq!("DEBUG".parse(), Ok(LogLevel::Debug));
+        assert!("verbose".parse::<LogLevel>().is_err());
+        assert_eq!(serde_json::to_string(&LogLevel::Info).unwrap(), r#""info""#);
+    }
+
+    #[test]
+    fn event_filter() {
+        let monitors = serde_json::from_str::<Vec<Monitor>>(
+            r#"[{"id":0,"name":null,"enabled":true,"modes":[]},
+                {"id":1,"name":null,"enabled":true,"modes":[]}]"#,
+        )
+        .unwrap();
+        let changed = EventCommand::Changed(monitors);
+        let log = EventCommand::Log(LogRecord {
+            seq: 0,
+            timestamp_ms: 0,
+            level: LogLevel::Info,
+            target: "test".to_owned(),
+            message: String::new(),
+        });
+
+        let all = EventFilter::default();
+        assert!(matches!(
+            all.apply(changed.clone()),
+            Some(EventCommand::Changed(m)) if m.len() == 2
+        ));
+        assert!(all.apply(log.clone()).is_some());
+
+        let filter = EventFilter {
+            kinds: Some(vec![EventKind::Changed]),
+            monitor_ids: Some(vec![1, 2]),
+        };
+        let Some(EventCommand::Changed(monitors)) = filter.apply(changed) else {
+            panic!("Expected Changed");
+        };
+        assert_eq!(monitors.iter().map(|m| m.id).collect::<Vec<_>>(), [1]);
+        assert!(filter.apply(log).is_none());
+
+        let json = r#"{"Subscribe":{"kinds":["changed"]}}"#;
+        let cmd: RequestCommand = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            cmd,
+            RequestCommand::Subscribe { kinds: Some(kinds), monitor_ids: None }
+                if kinds == [EventKind::Changed]
+        ));
+    }
+
+    #[test]
+    fn required_access() {
+        let read = [
+            ServerCommand::Request(RequestCommand::State),
+            ServerCommand::Request(RequestCommand::RecordingState),
+            ServerCommand::Request(RequestCommand::Subscribe {
+                kinds: None,
+                monitor_ids: None,
+            }),
+        ];
+        for command in read {
+            assert_eq!(command.required_access(), Access::Read, "{}", command.name());
+        }
+
+        let control = [
+            ServerCommand::Driver(DriverCommand::RemoveAll),
+            ServerCommand::Driver(DriverCommand::StopRecording),
+            ServerCommand::Request(RequestCommand::SubscribeLogs(None)),
+        ];
+        for command in control {
+            assert_eq!(command.required_access(), Access::Control, "{}", command.name());
+        }
+
+        // the name is the one sent
+        let command = ServerCommand::Driver(DriverCommand::RemoveAll);
+        assert_eq!(serde_json::to_string(&command).unwrap(), r#""RemoveAll""#);
+        assert_eq!(command.name(), "RemoveAll");
+
+        let denied = PermissionDenied {
+            command: command.name().to_owned(),
+            reason: DenyReason::Access {
+                required: Access::Control,
+            },
+        };
+        assert_eq!(denied.to_string(), "RemoveAll requires control access");
+    }
+
+    #[test]
+    fn log_filter() {
+        let filter = LogFilter {
+            level: LogLevel::Warn,
+            targets: [
+                ("driver::ipc".to_owned(), LogLevel::Debug),
+                ("driver::ipc::codec".to_owned(), LogLevel::Error),
+            ]
+            .into(),
+        };
+
+        assert_eq!(filter.level_of("driver"), LogLevel::Warn);
+        assert_eq!(filter.level_of("driver::ipc"), LogLevel::Debug);
+        assert_eq!(filter.level_of("driver::ipc::client"), LogLevel::Debug);
+        assert_eq!(filter.level_of("driver::ipc::codec"), LogLevel::Error);
+        // only whole modules match
+        assert_eq!(filter.level_of("driver::ipcx"), LogLevel::Warn);
+
+        assert!(filter.enabled("driver::ipc", LogLevel::Info));
+        assert!(!filter.enabled("driver::ipc::codec", LogLevel::Warn));
+        assert_eq!(filter.max_level(), LogLevel::Debug);
+
+        let json = r#"{"SetLogLevel":{"level":"trace"}}"#;
+        let cmd: DriverCommand = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            cmd,
+            DriverCommand::SetLogLevel { level: LogLevel::Trace, targets } if targets.is_empty()
+        ));
+    }
+
+    #[test]
+    fn color_serde() {
+        let json = r#"{"id":0,"name":null,"enabled":true,"modes":[]}"#;
+        let monitor: Monitor = serde_json::from_str(json).unwrap();
+        assert_eq!(monitor.color, Color::default());
+        assert_eq!(serde_json::to_string(&monitor).unwrap(), json);
+
+        let json = r#"{"bit_depth":10,"format":"ycbcr422","hdr":{"max_luminance":600.0,"min_luminance":0.1,"primaries":"bt2020"}}"#;
+        let color: Color = serde_json::from_str(json).unwrap();
+        assert_eq!(
+            color,
+            Color {
+                bit_depth: BitDepth::Ten,
+                format: ColorFormat::YCbCr422,
+                hdr: Some(Hdr {
+                    max_luminance: 600.0,
+                    min_luminance: 0.1,
+                    primaries: ColorPrimaries::Bt2020,
+                }),
+            }
+        );
+        assert_eq!(serde_json::to_string(&color).unwrap(), json);
+
+        assert!(serde_json::from_str::<Color>(r#"{"bit_depth":9}"#).is_err());
+        assert_eq!("10-bit".parse(), Ok(BitDepth::Ten));
+        assert_eq!("12".parse(), Ok(BitDepth::Twelve));
+        assert!("7".parse::<BitDepth>().is_err());
+    }
+
+    #[test]
+    fn physical_size_serde() {
+        let json = r#"{"id":0,"name":null,"enabled":true,"modes":[]}"#;
+        let monitor: Monitor = serde_json::from_str(json).unwrap();
+        assert_eq!(monitor.physical_size, None);
+        assert_eq!(serde_json::to_string(&monitor).unwrap(), json);
+
+        let size = PhysicalSize::Millimeters {
+            width: 597,
+            height: 336,
+        };
+        assert_eq!(
+            serde_json::to_string(&size).unwrap(),
+            r#"{"millimeters":{"width":597,"height":336}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&PhysicalSize::Dpi(144)).unwrap(),
+            r#"{"dpi":144}"#
+        );
+    }
 }
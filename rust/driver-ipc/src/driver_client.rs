@@ -1,9 +1,14 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, sync::Arc};
 
 use tokio::{sync::watch, task};
 use tokio_stream::{Stream, StreamExt};
 
-use crate::*;
+use crate::{
+    persist::{RegistryStore, Scope, Store, DEFAULT_ENTRY},
+    reconcile::{Plan, Policy},
+    selector::{self, Selector},
+    *,
+};
 
 /// Abstraction layer over [Client].
 ///
@@ -13,11 +18,15 @@ use crate::*;
 /// [DriverClient::persist]. To synchronize this object with the driver, you
 /// must call [DriverClient::refresh_state]. The state will not be updated
 /// automatically.
+///
+/// The state can also be saved as named profiles, see
+/// [DriverClient::save_profile].
 #[derive(Debug)]
 pub struct DriverClient {
     client: Client,
     state_rx: watch::Receiver<Vec<Monitor>>,
     state: Vec<Monitor>,
+    profiles: Arc<dyn Store>,
 }
 
 impl DriverClient {
@@ -55,6 +64,7 @@ impl DriverClient {
             client,
             state_rx,
             state: current_state,
+            profiles: Arc::new(RegistryStore::new(Scope::User)),
         })
     }
 
@@ -62,41 +72,37 @@ impl DriverClient {
     ///
     /// ## Query syntax
     ///
-    /// The query can either be a monitor name or an ID.
+    /// The query can either be a monitor key, a monitor name or an ID.
     ///
-    /// The name has precedence over the ID.
+    /// The key has precedence over the name, which has precedence over the ID.
+    /// Keys are unique and stable, so they are the most reliable way to
+    /// address a monitor.
     ///
     /// ### Example
     /// ```ignore
     /// // Monitors are:
-    /// //   { id: 0, name: Some("foo") }
-    /// //   { id: 1, name: Some("bar") }
-    /// //   { id: 2, name: Some("1") }
+    /// //   { id: 0, name: Some("foo"), key: None }
+    /// //   { id: 1, name: Some("bar"), key: Some("foo") }
+    /// //   { id: 2, name: Some("1"), key: None }
     ///
-    /// assert_eq!(client.find_id("foo"), Some(0));
+    /// assert_eq!(client.find_id("foo"), Some(1));
     /// assert_eq!(client.find_id("bar"), Some(1));
     /// assert_eq!(client.find_id("1"), Some(2));
     /// assert_eq!(client.find_id("0"), Some(0));
     /// assert_eq!(client.find_id("baz"), None);
     /// ```
     pub fn find_id(&self, query: &str) -> Option<Id> {
-        let id = query.parse::<Id>();
-
-        for monitor in self.monitors().iter() {
-            if let Some(name) = monitor.name.as_deref() {
-                if name == query {
-                    return Some(monitor.id);
-                }
-            }
-
-            if let Ok(id) = id {
-                if monitor.id == id {
-                    return Some(monitor.id);
-                }
-            }
-        }
+        selector::find_id(self.monitors(), query)
+    }
 
-        None
+    /// Get the IDs of all monitors matching a selector, in order.
+    ///
+    /// See [crate::selector] for the syntax.
+    ///
+    /// Note: Client state might be stale. To synchronize with the driver,
+    /// manually call [DriverClient::refresh_state].
+    pub fn select(&self, selector: &Selector) -> Vec<Id> {
+        selector.select(&self.state)
     }
 
     /// Manually synchronize with the driver.
@@ -161,10 +167,56 @@ impl DriverClient {
     ///
     /// State changes of the client are not automatically sent to the driver.
     /// You must manually call this method to send changes to the driver.
-    pub async fn notify(&mut self) -> Result<(), error::SendError> {
+    ///
+    /// Fails if the driver rejects the state, see [Client::notify].
+    pub async fn notify(&mut self) -> Result<(), error::RequestError> {
         self.client.notify(&self.state).await
     }
 
+    /// Compute the changes [DriverClient::reconcile] would make, without
+    /// making them.
+    ///
+    /// Note: This requests the state of the driver and replaces the client
+    /// state with it first.
+    pub async fn plan(
+        &mut self,
+        desired: &[Monitor],
+        policy: Policy,
+    ) -> Result<Plan, error::ReconcileError> {
+        mons_have_duplicates(desired)?;
+
+        // the driver does not send a client the events of its own changes, so
+        // the state of [DriverClient::refresh_state] may lag behind them
+        self.state = self.client.request_state().await?;
+
+        Ok(Plan::new(&self.state, desired, policy))
+    }
+
+    /// Bring the driver to the `desired` state, changing only what differs.
+    ///
+    /// Unlike [DriverClient::set_monitors] followed by [DriverClient::notify],
+    /// monitors which are already in their desired state are not touched, and
+    /// `policy` decides whether monitors missing from `desired` are removed.
+    ///
+    /// Returns the applied plan. Nothing is sent if it is empty.
+    ///
+    /// Note: This requests the state of the driver and replaces the client
+    /// state with it first.
+    pub async fn reconcile(
+        &mut self,
+        desired: &[Monitor],
+        policy: Policy,
+    ) -> Result<Plan, error::ReconcileError> {
+        let plan = self.plan(desired, policy).await?;
+
+        if !plan.is_empty() {
+            self.state = plan.apply(&self.state);
+            self.notify().await?;
+        }
+
+        Ok(plan)
+    }
+
     /// Start recording frames from specified monitors.
     ///
     /// If `monitor_ids` is empty, all monitors will be recorded.
@@ -184,6 +236,40 @@ impl DriverClient {
         self.client.request_recording_state().await
     }
 
+    /// Request the frame and swap chain counters of the driver.
+    pub async fn request_stats(&self) -> Result<Stats, error::RequestError> {
+        self.client.request_stats().await
+    }
+
+    /// Request build and liveness information of the driver.
+    pub async fn health(&self) -> Result<Health, error::RequestError> {
+        self.client.health().await
+    }
+
+    /// Request the access this client has to the driver.
+    pub async fn access(&self) -> Result<Access, error::RequestError> {
+        self.client.access().await
+    }
+
+    /// Request the recent records of the driver log, see [Client::request_logs].
+    pub async fn request_logs(
+        &self,
+        since: Option<u64>,
+        level: Option<LogLevel>,
+    ) -> Result<Vec<LogRecord>, error::RequestError> {
+        self.client.request_logs(since, level).await
+    }
+
+    /// Stream new records of the driver log, see [Client::subscribe_logs].
+    pub async fn subscribe_logs(&self, level: Option<LogLevel>) -> Result<(), error::SendError> {
+        self.client.subscribe_logs(level).await
+    }
+
+    /// Change which records the driver logs, see [Client::set_log_filter].
+    pub async fn set_log_filter(&self, filter: LogFilter) -> Result<(), error::SendError> {
+        self.client.set_log_filter(filter).await
+    }
+
     /// Find the monitor with the given ID.
     ///
     /// Note: Client state might be stale. To synchronize with the driver,
@@ -272,13 +358,84 @@ impl DriverClient {
 
     /// Write client state to the registry for current user.
     ///
-    /// Next time the driver is started, it will load this state from the
-    /// registry. This might be after a reboot or a driver restart.
+    /// Next time the user logs on, the user session service will load this
+    /// state from the registry.
     pub fn persist(&self) -> Result<(), error::PersistError> {
         Client::persist(&self.state)
     }
 
-    /// Get the closest available free ID.
+    /// Write client state to the registry for the whole machine. Requires
+    /// admin rights.
+    ///
+    /// Next time the driver is started, it will load this state from the
+    /// registry. This might be after a reboot or a driver restart.
+    pub fn persist_machine(&self) -> Result<(), error::PersistError> {
+        Client::persist_machine(&self.state)
+    }
+
+    /// Use `store` for profiles instead of the registry of the current user.
+    pub fn set_profile_store(&mut self, store: impl Store + 'static) {
+        self.profiles = Arc::new(store);
+    }
+
+    /// Save client state as profile `name`, replacing any existing profile
+    /// with that name.
+    pub fn save_profile(&self, name: &str) -> Result<(), error::PersistError> {
+        self.profiles.save(name, &self.state)
+    }
+
+    /// Replace client state with profile `name` and send it to the driver.
+    pub async fn load_profile(&mut self, name: &str) -> Result<(), error::ProfileError> {
+        let monitors = self
+            .profiles
+            .load(name)?
+            .ok_or_else(|| error::ProfileError::NotFound(name.to_owned()))?;
+
+        self.set_monitors(&monitors)?;
+        self.notify().await?;
+
+        Ok(())
+    }
+
+    /// Names of all saved profiles, sorted.
+    pub fn list_profiles(&self) -> Result<Vec<String>, error::PersistError> {
+        self.profiles.list()
+    }
+
+    /// Delete profile `name`.
+    ///
+    /// Returns `false` if the profile did not exist.
+    pub fn delete_profile(&self, name: &str) -> Result<bool, error::PersistError> {
+        self.profiles.delete(name)
+    }
+
+    /// Make profile `name` the default profile, or remove the default profile
+    /// if `name` is `None`.
+    ///
+    /// The default profile is the profile named [DEFAULT_ENTRY]. For the
+    /// registry of the current user, it is applied by the user session service
+    /// when the user logs on. It is a copy, later changes to profile `name` do
+    /// not change the default profile.
+    pub fn set_default_profile(&self, name: Option<&str>) -> Result<(), error::ProfileError> {
+        let Some(name) = name else {
+            self.profiles.delete(DEFAULT_ENTRY)?;
+            return Ok(());
+        };
+
+        let monitors = self
+            .profiles
+            .load(name)?
+            .ok_or_else(|| error::ProfileError::NotFound(name.to_owned()))?;
+
+        self.profiles.save(DEFAULT_ENTRY, &monitors)?;
+
+        Ok(())
+    }
+
+    /// Get an available ID.
+    ///
+    /// Returns `preferred_id` if it is free, or `None` if it is taken. Without
+    /// a preferred ID, returns the lowest free ID.
     ///
     /// Note: Client state might be stale. To synchronize with the driver,
     /// manually call [DriverClient::refresh_state].
@@ -286,25 +443,7 @@ impl DriverClient {
     /// Note: Duplicate monitors are ignored when send to the Driver using
     /// [DriverClient::notify].
     pub fn new_id(&self, preferred_id: Option<Id>) -> Option<Id> {
-        let existing_ids = self
-            .state
-            .iter()
-            .map(|monitor| monitor.id)
-            .collect::<HashSet<_>>();
-
-        if let Some(id) = preferred_id {
-            if !existing_ids.contains(&id) {
-                return None;
-            }
-
-            Some(id)
-        } else {
-            #[allow(clippy::maybe_infinite_iter)]
-            let new_id = (0..)
-                .find(|id| !existing_ids.contains(id))
-                .expect("failed to get a new ID");
-            Some(new_id)
-        }
+        new_id(&self.state, preferred_id)
     }
 
     /// Remove monitors by id.
@@ -370,6 +509,11 @@ impl DriverClient {
         if self.state.iter().any(|mon| mon.id == monitor.id) {
             return Err(error::DuplicateError::Monitor(monitor.id));
         }
+        if let Some(key) = monitor.key.as_deref() {
+            if self.state.iter().any(|mon| mon.key.as_deref() == Some(key)) {
+                return Err(error::DuplicateError::Key(key.to_owned()));
+            }
+        }
         mon_has_duplicates(&monitor)?;
 
         self.state.push(monitor);
@@ -571,7 +715,29 @@ impl DriverClient {
             client: self.client.clone(),
             state_rx: self.state_rx.clone(),
             state: self.state.clone(),
+            profiles: self.profiles.clone(),
+        }
+    }
+}
+
+fn new_id(monitors: &[Monitor], preferred_id: Option<Id>) -> Option<Id> {
+    let existing_ids = monitors
+        .iter()
+        .map(|monitor| monitor.id)
+        .collect::<HashSet<_>>();
+
+    if let Some(id) = preferred_id {
+        if existing_ids.contains(&id) {
+            return None;
         }
+
+        Some(id)
+    } else {
+        #[allow(clippy::maybe_infinite_iter)]
+        let new_id = (0..)
+            .find(|id| !existing_ids.contains(id))
+            .expect("failed to get a new ID");
+        Some(new_id)
     }
 }
 
@@ -583,6 +749,15 @@ fn mons_have_duplicates(monitors: &[Monitor]) -> Result<(), error::DuplicateErro
             return Err(error::DuplicateError::Monitor(monitor.id));
         }
 
+        if let Some(key) = monitor.key.as_deref() {
+            let duplicate_key = monitor_iter
+                .clone()
+                .any(|b| b.key.as_deref() == Some(key));
+            if duplicate_key {
+                return Err(error::DuplicateError::Key(key.to_owned()));
+            }
+        }
+
         mon_has_duplicates(monitor)?;
     }
 
@@ -635,6 +810,8 @@ pub mod error {
     pub enum DuplicateError {
         #[error("Duplicate monitor with ID {0}")]
         Monitor(Id),
+        #[error("Duplicate monitor with key {0}")]
+        Key(String),
         #[error("Duplicate mode {1}x{2} on monitor {0}")]
         Mode(u32, u32, Id),
         #[error("Duplicate refresh rate {0} on mode {1}x{2} on monitor {3}")]
@@ -671,6 +848,29 @@ pub mod error {
         DupRefreshRate(u32, u32, u32, Id),
     }
 
+    /// Error returned from [DriverClient::load_profile] and
+    /// [DriverClient::set_default_profile].
+    #[derive(Debug, Error)]
+    pub enum ProfileError {
+        #[error("Profile not found: {0}")]
+        NotFound(String),
+        #[error(transparent)]
+        Persist(#[from] PersistError),
+        #[error("Profile is invalid: {0}")]
+        Duplicate(#[from] DuplicateError),
+        #[error(transparent)]
+        Request(#[from] RequestError),
+    }
+
+    /// Error returned from [DriverClient::plan] and [DriverClient::reconcile].
+    #[derive(Debug, Error)]
+    pub enum ReconcileError {
+        #[error("Desired state is invalid: {0}")]
+        Duplicate(#[from] DuplicateError),
+        #[error(transparent)]
+        Request(#[from] RequestError),
+    }
+
     /// Error returned from [DriverClient::new] and [DriverClient::new_with].
     #[derive(Debug, Error)]
     pub enum InitError {
@@ -680,3 +880,154 @@ pub mod error {
         RequestState(#[from] RequestError),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{mock::*, persist::MemoryStore, reconcile::Change};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn profiles() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-profiles";
+
+        let mut server = MockServer::new(PIPE_NAME);
+
+        let (client, _) = tokio::join!(DriverClient::new_with(PIPE_NAME), server.pump());
+        let mut client = client.expect("Failed to connect to pipe");
+        client.set_profile_store(MemoryStore::default());
+
+        let monitors = vec![Monitor {
+            id: 0,
+            name: Some("office".to_owned()),
+            enabled: true,
+            modes: vec![Mode {
+                width: 1920,
+                height: 1080,
+                refresh_rates: vec![60],
+            }],
+            ..Default::default()
+        }];
+
+        client.set_monitors(&monitors).unwrap();
+        client.save_profile("office").unwrap();
+        client.remove_all();
+        assert_eq!(client.list_profiles().unwrap(), ["office"]);
+
+        let (loaded, _) = tokio::join!(client.load_profile("office"), server.pump());
+        loaded.unwrap();
+        assert_eq!(client.monitors(), monitors);
+        assert_eq!(server.state(), monitors);
+
+        client.set_default_profile(Some("office")).unwrap();
+        assert_eq!(client.list_profiles().unwrap(), [DEFAULT_ENTRY, "office"]);
+        client.set_default_profile(None).unwrap();
+        assert_eq!(client.list_profiles().unwrap(), ["office"]);
+
+        assert!(matches!(
+            client.load_profile("missing").await,
+            Err(error::ProfileError::NotFound(name)) if name == "missing"
+        ));
+        assert!(matches!(
+            client.set_default_profile(Some("missing")),
+            Err(error::ProfileError::NotFound(_))
+        ));
+
+        assert!(client.delete_profile("office").unwrap());
+        assert!(client.list_profiles().unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn reconcile() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-reconcile";
+
+        let mut server = MockServer::new(PIPE_NAME);
+
+        let (client, _) = tokio::join!(DriverClient::new_with(PIPE_NAME), server.pump());
+        let mut client = client.expect("Failed to connect to pipe");
+
+        let desired = vec![Monitor {
+            id: 3,
+            enabled: true,
+            ..Default::default()
+        }];
+
+        let (plan, _) = tokio::join!(client.plan(&desired, Policy::RemoveUnknown), server.pump());
+        let plan = plan.unwrap();
+        assert_eq!(
+            plan.changes,
+            [Change::Create {
+                monitor: desired[0].clone()
+            }]
+        );
+        assert!(client.monitors().is_empty(), "plan must not change state");
+
+        let (applied, _) = tokio::join!(client.reconcile(&desired, Policy::RemoveUnknown), async {
+            server.pump().await;
+            server.pump().await;
+        });
+        let applied = applied.unwrap();
+        assert_eq!(applied, plan);
+        assert_eq!(server.state(), desired);
+
+        let duplicate = [desired[0].clone(), desired[0].clone()];
+        assert!(matches!(
+            client.reconcile(&duplicate, Policy::KeepUnknown).await,
+            Err(error::ReconcileError::Duplicate(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 1)]
+    async fn reconcile_keeps_own_changes() {
+        const PIPE_NAME: &str = "virtualdisplaydriver-test-reconcile_keeps_own_changes";
+
+        // like the driver, which does not tell a client about its own changes
+        let mut server = MockServer::new(PIPE_NAME);
+        server.set_echo(false);
+
+        let (client, _) = tokio::join!(DriverClient::new_with(PIPE_NAME), server.pump());
+        let mut client = client.expect("Failed to connect to pipe");
+
+        for id in [3, 4] {
+            let desired = [monitor(id, None, None)];
+            let (applied, _) = tokio::join!(client.reconcile(&desired, Policy::KeepUnknown), async {
+                server.pump().await;
+                server.pump().await;
+            });
+            applied.unwrap();
+        }
+
+        let ids = server.state().iter().map(|m| m.id).collect::<Vec<_>>();
+        assert_eq!(ids, [3, 4]);
+    }
+
+    fn monitor(id: Id, name: Option<&str>, key: Option<&str>) -> Monitor {
+        Monitor {
+            id,
+            name: name.map(str::to_owned),
+            key: key.map(str::to_owned),
+            enabled: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn new_id_honours_preferred() {
+        let monitors = [monitor(0, None, None), monitor(2, None, None)];
+
+        assert_eq!(new_id(&monitors, Some(5)), Some(5));
+        assert_eq!(new_id(&monitors, Some(2)), None);
+        assert_eq!(new_id(&monitors, None), Some(1));
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let monitors = [monitor(0, None, Some("a")), monitor(1, None, Some("a"))];
+        assert!(matches!(
+            mons_have_duplicates(&monitors),
+            Err(error::DuplicateError::Key(key)) if key == "a"
+        ));
+
+        let monitors = [monitor(0, None, None), monitor(1, None, None)];
+        assert!(mons_have_duplicates(&monitors).is_ok());
+    }
+}
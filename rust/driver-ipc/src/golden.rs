@@ -0,0 +1,396 @@
+//! Golden fixtures of the IPC protocol.
+//!
+//! External clients parse and produce the JSON of these messages themselves,
+//! so a serde change must not alter it. Each fixture in `driver-ipc/fixtures`
+//! holds one message, and must both deserialize and serialize to exactly the
+//! same JSON:
+//!
+//! - `v1` holds the messages of the first protocol, before capabilities,
+//!   config and the optional monitor fields were added. These are frozen, old
+//!   clients still send and expect them.
+//! - `latest` holds every message with every field set. To update them after
+//!   adding a message or field, run
+//!   `UPDATE_FIXTURES=1 cargo test -p driver-ipc golden` and review the diff.
+
+use std::{collections::BTreeSet, path::PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use crate::{
+    Access, BitDepth, Capabilities, ClientCommand, Color, ColorFormat, ColorPrimaries, DenyReason,
+    DriverCommand, DriverConfig, Encoding, EventCommand, EventKind, Hdr, Health, LogLevel,
+    LogRecord, Mode, Monitor, MonitorStats, Orientation, PermissionDenied, PhysicalSize, Position,
+    ProtocolError, RecordingStats, Rejection, ReplyCommand, RequestCommand, ServerCommand, Stats,
+    Surface, SwapChainHealth, Topology,
+};
+
+/// Fixture name of a message sent to the driver
+fn server_name(command: &ServerCommand) -> &'static str {
+    match command {
+        ServerCommand::Driver(command) => match command {
+            DriverCommand::Notify(_) => "notify",
+            DriverCommand::Remove(_) => "remove",
+            DriverCommand::RemoveAll => "remove_all",
+            DriverCommand::StartRecording { .. } => "start_recording",
+            DriverCommand::StopRecording => "stop_recording",
+            DriverCommand::SetLogLevel { .. } => "set_log_level",
+        },
+        ServerCommand::Request(command) => match command {
+            RequestCommand::State => "state",
+            RequestCommand::RecordingState => "recording_state",
+            RequestCommand::Capabilities => "capabilities",
+            RequestCommand::Config => "config",
+            RequestCommand::SetEncoding(_) => "set_encoding",
+            RequestCommand::Stats => "stats",
+            RequestCommand::Health => "health",
+            RequestCommand::Logs { .. } => "logs",
+            RequestCommand::SubscribeLogs(_) => "subscribe_logs",
+            RequestCommand::Subscribe { .. } => "subscribe",
+            RequestCommand::Access => "access",
+        },
+    }
+}
+
+/// Fixture name of a message sent by the driver
+fn client_name(command: &ClientCommand) -> &'static str {
+    match command {
+        ClientCommand::Reply(command) => match command {
+            ReplyCommand::State(_) => "state",
+            ReplyCommand::RecordingState { .. } => "recording_state",
+            ReplyCommand::RecordingStarted { .. } => "recording_started",
+            ReplyCommand::RecordingFinished { .. } => "recording_finished",
+            ReplyCommand::Capabilities(_) => "capabilities",
+            ReplyCommand::Config(_) => "config",
+            ReplyCommand::Encoding(_) => "encoding",
+            ReplyCommand::Stats(_) => "stats",
+            ReplyCommand::Health(_) => "health",
+            ReplyCommand::Logs(_) => "logs",
+            ReplyCommand::Access(_) => "access",
+            ReplyCommand::PermissionDenied(_) => "permission_denied",
+            ReplyCommand::ProtocolError(_) => "protocol_error",
+            ReplyCommand::Notified => "notified",
+            ReplyCommand::Rejected(_) => "rejected",
+        },
+        ClientCommand::Event(command) => match command {
+            EventCommand::Changed(_) => "changed",
+            EventCommand::Log(_) => "log",
+        },
+    }
+}
+
+fn v1_monitors() -> Vec<Monitor> {
+    vec![Monitor {
+        id: 0,
+        name: Some("test".to_owned()),
+        enabled: true,
+        modes: vec![Mode {
+            width: 1920,
+            height: 1080,
+            refresh_rates: vec![60, 120],
+        }],
+        ..Default::default()
+    }]
+}
+
+fn latest_monitors() -> Vec<Monitor> {
+    vec![
+        Monitor {
+            id: 0,
+            name: Some("office".to_owned()),
+            key: Some("office-left".to_owned()),
+            enabled: true,
+            modes: vec![
+                Mode {
+                    width: 1080,
+                    height: 1920,
+                    refresh_rates: vec![60, 120],
+                },
+                Mode {
+                    width: 720,
+                    height: 1280,
+                    refresh_rates: vec![60],
+                },
+            ],
+            physical_size: Some(PhysicalSize::Millimeters {
+                width: 336,
+                height: 597,
+            }),
+            orientation: Orientation::Portrait,
+            position: Some(Position { x: -1080, y: 0 }),
+            primary: true,
+            topology: Topology::Extend,
+            color: Color {
+                bit_depth: BitDepth::Ten,
+                format: ColorFormat::YCbCr444,
+                hdr: Some(Hdr {
+                    max_luminance: 1000.0,
+                    min_luminance: 0.5,
+                    primaries: ColorPrimaries::DciP3,
+                }),
+            },
+        },
+        Monitor {
+            id: 1,
+            enabled: false,
+            modes: vec![Mode {
+                width: 3840,
+                height: 2160,
+                refresh_rates: vec![60],
+            }],
+            physical_size: Some(PhysicalSize::Dpi(163)),
+            topology: Topology::Duplicate,
+            ..Default::default()
+        },
+    ]
+}
+
+fn v1_server() -> Vec<ServerCommand> {
+    vec![
+        ServerCommand::Driver(DriverCommand::Notify(v1_monitors())),
+        ServerCommand::Driver(DriverCommand::Remove(vec![0, 1])),
+        ServerCommand::Driver(DriverCommand::RemoveAll),
+        ServerCommand::Driver(DriverCommand::StartRecording {
+            monitor_ids: vec![0],
+            output_path: None,
+            fps: None,
+        }),
+        ServerCommand::Driver(DriverCommand::StopRecording),
+        ServerCommand::Request(RequestCommand::State),
+        ServerCommand::Request(RequestCommand::RecordingState),
+    ]
+}
+
+fn v1_client() -> Vec<ClientCommand> {
+    vec![
+        ClientCommand::Reply(ReplyCommand::State(v1_monitors())),
+        ClientCommand::Reply(ReplyCommand::RecordingState {
+            active: true,
+            monitor_ids: vec![0],
+            shm_names: vec![r"Global\VDD_Frame_0".to_owned()],
+        }),
+        ClientCommand::Reply(ReplyCommand::RecordingStarted {
+            active: true,
+            monitor_ids: vec![0],
+            has_session: true,
+        }),
+        ClientCommand::Reply(ReplyCommand::RecordingFinished {
+            path: r"C:\temp\recording.mp4".to_owned(),
+            frames: 150,
+            duration_ms: 30000,
+        }),
+        ClientCommand::Event(EventCommand::Changed(v1_monitors())),
+    ]
+}
+
+fn latest_server() -> Vec<ServerCommand> {
+    vec![
+        ServerCommand::Driver(DriverCommand::Notify(latest_monitors())),
+        ServerCommand::Driver(DriverCommand::Remove(vec![0, 1])),
+        ServerCommand::Driver(DriverCommand::RemoveAll),
+        ServerCommand::Driver(DriverCommand::StartRecording {
+            monitor_ids: vec![0, 1],
+            output_path: Some(r"C:\temp\recording.mp4".to_owned()),
+            fps: Some(30),
+        }),
+        ServerCommand::Driver(DriverCommand::StopRecording),
+        ServerCommand::Driver(DriverCommand::SetLogLevel {
+            level: LogLevel::Warn,
+            targets: [("virtual_display_driver::ipc".to_owned(), LogLevel::Trace)].into(),
+        }),
+        ServerCommand::Request(RequestCommand::State),
+        ServerCommand::Request(RequestCommand::RecordingState),
+        ServerCommand::Request(RequestCommand::Capabilities),
+        ServerCommand::Request(RequestCommand::Config),
+        ServerCommand::Request(RequestCommand::SetEncoding(Encoding::Cbor)),
+        ServerCommand::Request(RequestCommand::Stats),
+        ServerCommand::Request(RequestCommand::Health),
+        ServerCommand::Request(RequestCommand::Logs {
+            since: Some(41),
+            level: Some(LogLevel::Info),
+        }),
+        ServerCommand::Request(RequestCommand::SubscribeLogs(Some(LogLevel::Debug))),
+        ServerCommand::Request(RequestCommand::Subscribe {
+            kinds: Some(vec![EventKind::Changed]),
+            monitor_ids: Some(vec![0]),
+        }),
+        ServerCommand::Request(RequestCommand::Access),
+    ]
+}
+
+fn latest_client() -> Vec<ClientCommand> {
+    vec![
+        ClientCommand::Reply(ReplyCommand::State(latest_monitors())),
+        ClientCommand::Reply(ReplyCommand::RecordingState {
+            active: true,
+            monitor_ids: vec![0, 1],
+            shm_names: vec![
+                r"Global\VDD_Frame_0".to_owned(),
+                r"Global\VDD_Frame_1".to_owned(),
+            ],
+        }),
+        ClientCommand::Reply(ReplyCommand::RecordingStarted {
+            active: true,
+            monitor_ids: vec![0, 1],
+            has_session: true,
+        }),
+        ClientCommand::Reply(ReplyCommand::RecordingFinished {
+            path: r"C:\temp\recording.mp4".to_owned(),
+            frames: 900,
+            duration_ms: 30000,
+        }),
+        ClientCommand::Reply(ReplyCommand::Capabilities(Capabilities { max_monitors: 16 })),
+        ClientCommand::Reply(ReplyCommand::Config(DriverConfig::default())),
+        ClientCommand::Reply(ReplyCommand::Encoding(Encoding::Cbor)),
+        ClientCommand::Reply(ReplyCommand::Stats(Stats {
+            monitors: vec![
+                MonitorStats {
+                    id: 0,
+                    frames_acquired: 1800,
+                    frames_captured: 900,
+                    pending_waits: 2400,
+                    swap_chain_errors: 1,
+                    surface: Some(Surface {
+                        width: 1080,
+                        height: 1920,
+                        format: 24,
+                    }),
+                },
+                MonitorStats {
+                    id: 1,
+                    ..MonitorStats::default()
+                },
+            ],
+            recording: Some(RecordingStats {
+                frames_sent: 900,
+                frames_dropped: 3,
+                duration_ms: 30000,
+            }),
+        })),
+        ClientCommand::Reply(ReplyCommand::Health(Health {
+            version: "0.4.0".to_owned(),
+            git_sha: "0123456789abcdef0123456789abcdef01234567".to_owned(),
+            iddcx_min_version: "1.4".to_owned(),
+            uptime_ms: 3_600_000,
+            adapter_initialized: true,
+            clients: 2,
+            pipe_server: true,
+            swap_chains: vec![
+                SwapChainHealth { id: 0, alive: true },
+                SwapChainHealth { id: 1, alive: false },
+            ],
+        })),
+        ClientCommand::Reply(ReplyCommand::Logs(vec![
+            LogRecord {
+                seq: 42,
+                timestamp_ms: 1_767_225_600_000,
+                level: LogLevel::Info,
+                target: "virtual_display_driver::ipc".to_owned(),
+                message: "IPC: Client #1 connected to pipe".to_owned(),
+            },
+            LogRecord {
+                seq: 43,
+                timestamp_ms: 1_767_225_600_016,
+                level: LogLevel::Warn,
+                target: "virtual_display_driver::swap_chain_processor".to_owned(),
+                message: "Failed to map staging texture".to_owned(),
+            },
+        ])),
+        ClientCommand::Reply(ReplyCommand::Access(Access::Read)),
+        ClientCommand::Reply(ReplyCommand::PermissionDenied(PermissionDenied {
+            command: "StartRecording".to_owned(),
+            reason: DenyReason::RecordingPath {
+                path: r"C:\Windows\System32\recording.mp4".to_owned(),
+                reason: "must be in one of the recording directories".to_owned(),
+            },
+        })),
+        ClientCommand::Reply(ReplyCommand::ProtocolError(ProtocolError::TooLarge {
+            max: 1024 * 1024,
+        })),
+        ClientCommand::Reply(ReplyCommand::Notified),
+        ClientCommand::Reply(ReplyCommand::Rejected(Rejection::LimitExceeded {
+            enabled: 17,
+            max_monitors: 16,
+        })),
+        ClientCommand::Event(EventCommand::Changed(latest_monitors())),
+        ClientCommand::Event(EventCommand::Log(LogRecord {
+            seq: 44,
+            timestamp_ms: 1_767_225_600_032,
+            level: LogLevel::Error,
+            target: "virtual_display_driver::context".to_owned(),
+            message: "Failed to create monitor".to_owned(),
+        })),
+    ]
+}
+
+fn fixtures_dir(dir: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures")
+        .join(dir)
+}
+
+/// Check that every message matches its fixture in `dir`, and that there is
+/// no fixture without a message.
+fn check<T: Serialize + DeserializeOwned>(
+    dir: &str,
+    messages: &[T],
+    name: fn(&T) -> &'static str,
+    update: bool,
+) {
+    let dir = fixtures_dir(dir);
+
+    for message in messages {
+        let path = dir.join(format!("{}.json", name(message)));
+        let expected = serde_json::to_value(message).unwrap();
+
+        if update {
+            std::fs::create_dir_all(&dir).unwrap();
+            let json = serde_json::to_string_pretty(message).unwrap() + "\n";
+            std::fs::write(&path, json).unwrap();
+            continue;
+        }
+
+        let fixture = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("failed to read {}: {e}", path.display()));
+        let fixture: Value = serde_json::from_str(&fixture).unwrap();
+
+        assert_eq!(expected, fixture, "{} serializes differently", path.display());
+
+        let parsed: T = serde_json::from_value(fixture.clone())
+            .unwrap_or_else(|e| panic!("{} no longer deserializes: {e}", path.display()));
+        assert_eq!(
+            serde_json::to_value(parsed).unwrap(),
+            fixture,
+            "{} deserializes differently",
+            path.display()
+        );
+    }
+
+    let names = messages.iter().map(name).collect::<BTreeSet<_>>();
+    for entry in std::fs::read_dir(&dir).unwrap() {
+        let path = entry.unwrap().path();
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        assert!(names.contains(stem), "no message for {}", path.display());
+    }
+}
+
+#[test]
+fn v1() {
+    check("v1/server", &v1_server(), server_name, false);
+    check("v1/client", &v1_client(), client_name, false);
+}
+
+#[test]
+fn latest() {
+    let update = std::env::var_os("UPDATE_FIXTURES").is_some();
+
+    let server = latest_server();
+    let client = latest_client();
+
+    // one sample of every message, bump these when adding one
+    assert_eq!(server.iter().map(server_name).collect::<BTreeSet<_>>().len(), 17);
+    assert_eq!(client.iter().map(client_name).collect::<BTreeSet<_>>().len(), 17);
+
+    check("latest/server", &server, server_name, update);
+    check("latest/client", &client, client_name, update);
+}
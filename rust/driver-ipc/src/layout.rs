@@ -0,0 +1,406 @@
+//! Plan where monitors go on the desktop.
+//!
+//! The driver can only make monitors arrive, Windows decides where they end
+//! up. [Layout::plan] turns the [Monitor::position], [Monitor::primary] and
+//! [Monitor::topology] hints into a validated layout, which a client in the
+//! user's session can apply with `SetDisplayConfig`.
+//!
+//! Positions are desktop coordinates, where the primary display is at the
+//! origin. Monitors without a position are placed in a row to the right of
+//! everything else. If a monitor is primary, everything is moved afterwards so
+//! it ends up at the origin.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Dimen, Id, Monitor, Topology};
+
+/// An area of the desktop
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: Dimen,
+    pub height: Dimen,
+}
+
+impl Rect {
+    fn right(&self) -> i64 {
+        i64::from(self.x) + i64::from(self.width)
+    }
+
+    fn bottom(&self) -> i64 {
+        i64::from(self.y) + i64::from(self.height)
+    }
+
+    /// Whether the areas share any pixels
+    pub fn overlaps(&self, other: &Rect) -> bool {
+        i64::from(self.x) < other.right()
+            && i64::from(other.x) < self.right()
+            && i64::from(self.y) < other.bottom()
+            && i64::from(other.y) < self.bottom()
+    }
+
+    /// Whether the areas share part of an edge, touching corners don't count
+    pub fn touches(&self, other: &Rect) -> bool {
+        let shared = |start_a: i32, end_a: i64, start_b: i32, end_b: i64| {
+            i64::from(start_a.max(start_b)) < end_a.min(end_b)
+        };
+
+        let side_by_side = self.right() == i64::from(other.x) || other.right() == i64::from(self.x);
+        let stacked = self.bottom() == i64::from(other.y) || other.bottom() == i64::from(self.y);
+
+        (side_by_side && shared(self.y, self.bottom(), other.y, other.bottom()))
+            || (stacked && shared(self.x, self.right(), other.x, other.right()))
+    }
+}
+
+/// Where a monitor which extends the desktop goes
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Placement {
+    pub id: Id,
+    pub rect: Rect,
+    pub primary: bool,
+}
+
+/// A validated desktop layout
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Layout {
+    /// Monitors which extend the desktop
+    pub placements: Vec<Placement>,
+    /// Monitors which duplicate the primary display
+    pub duplicates: Vec<Id>,
+}
+
+impl Layout {
+    /// Plan the layout of the enabled `monitors`.
+    ///
+    /// `displays` are the other displays on the desktop, e.g. physical ones.
+    /// They are moved along with the monitors if a monitor is primary.
+    /// Monitors without modes are left out, their size is the size of their
+    /// first mode.
+    pub fn plan(monitors: &[Monitor], displays: &[Rect]) -> Result<Self, error::LayoutError> {
+        let mut layout = Layout::default();
+
+        let monitors = monitors
+            .iter()
+            .filter(|monitor| monitor.enabled && !monitor.modes.is_empty());
+
+        let mut extended = Vec::new();
+        for monitor in monitors {
+            match monitor.topology {
+                Topology::Duplicate if monitor.primary => {
+                    return Err(error::LayoutError::PrimaryDuplicate(monitor.id));
+                }
+                Topology::Duplicate => layout.duplicates.push(monitor.id),
+                Topology::Extend => extended.push(monitor),
+            }
+        }
+
+        let primaries = extended
+            .iter()
+            .filter(|monitor| monitor.primary)
+            .map(|monitor| monitor.id)
+            .collect::<Vec<_>>();
+        if primaries.len() > 1 {
+            return Err(error::LayoutError::MultiplePrimary(primaries));
+        }
+
+        let mut unplaced = Vec::new();
+        for monitor in extended {
+            let Some(position) = monitor.position else {
+                unplaced.push(monitor);
+                continue;
+            };
+
+            layout.placements.push(Placement {
+                id: monitor.id,
+                rect: size_at(monitor, position.x, position.y),
+                primary: monitor.primary,
+            });
+        }
+
+        // in a row to the right of everything else, a primary monitor too
+        for monitor in unplaced {
+            let right = layout
+                .placements
+                .iter()
+                .map(|placement| &placement.rect)
+                .chain(displays)
+                .map(Rect::right)
+                .max();
+
+            let x = match right {
+                Some(right) => i32::try_from(right).map_err(|_| error::LayoutError::OutOfRange)?,
+                None => 0,
+            };
+            layout.placements.push(Placement {
+                id: monitor.id,
+                rect: size_at(monitor, x, 0),
+                primary: monitor.primary,
+            });
+        }
+
+        // move everything so the primary monitor is at the origin
+        let origin = layout
+            .placements
+            .iter()
+            .find(|placement| placement.primary)
+            .map(|placement| (placement.rect.x, placement.rect.y))
+            .unwrap_or_default();
+        let shift = |rect: &Rect| -> Result<Rect, error::LayoutError> {
+            let x = rect.x.checked_sub(origin.0);
+            let y = rect.y.checked_sub(origin.1);
+            let (x, y) = x.zip(y).ok_or(error::LayoutError::OutOfRange)?;
+            Ok(Rect { x, y, ..*rect })
+        };
+
+        for placement in &mut layout.placements {
+            placement.rect = shift(&placement.rect)?;
+        }
+        let displays = displays.iter().map(shift).collect::<Result<Vec<_>, _>>()?;
+
+        layout.validate(&displays)?;
+
+        Ok(layout)
+    }
+
+    fn validate(&self, displays: &[Rect]) -> Result<(), error::LayoutError> {
+        for (i, placement) in self.placements.iter().enumerate() {
+            for other in &self.placements[i + 1..] {
+                if placement.rect.overlaps(&other.rect) {
+                    return Err(error::LayoutError::Overlap(placement.id, other.id));
+                }
+            }
+
+            if displays.iter().any(|display| placement.rect.overlaps(display)) {
+                return Err(error::LayoutError::OverlapsDisplay(placement.id));
+            }
+        }
+
+        // every monitor has to be reachable from the first area over shared edges
+        let rects = displays
+            .iter()
+            .chain(self.placements.iter().map(|placement| &placement.rect))
+            .collect::<Vec<_>>();
+        let mut reached = vec![false; rects.len()];
+        let mut queue = VecDeque::from([0]);
+        while let Some(i) = queue.pop_front() {
+            if reached.get(i).copied() != Some(false) {
+                continue;
+            }
+            reached[i] = true;
+
+            for (j, rect) in rects.iter().enumerate() {
+                if !reached[j] && rects[i].touches(rect) {
+                    queue.push_back(j);
+                }
+            }
+        }
+
+        let disconnected = self
+            .placements
+            .iter()
+            .zip(&reached[displays.len()..])
+            .find(|(_, reached)| !**reached);
+        match disconnected {
+            Some((placement, _)) => Err(error::LayoutError::Disconnected(placement.id)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The area of `monitor` with its top left corner at `x`, `y`
+fn size_at(monitor: &Monitor, x: i32, y: i32) -> Rect {
+    let mode = &monitor.modes[0];
+
+    Rect {
+        x,
+        y,
+        width: mode.width,
+        height: mode.height,
+    }
+}
+
+pub mod error {
+    use thiserror::Error;
+
+    use crate::Id;
+
+    /// Error returned from [super::Layout::plan].
+    #[derive(Debug, Clone, PartialEq, Eq, Error)]
+    pub enum LayoutError {
+        #[error("Monitors {0:?} are all primary, but only one can be")]
+        MultiplePrimary(Vec<Id>),
+        #[error("Monitor {0} duplicates the primary display, so it cannot be primary")]
+        PrimaryDuplicate(Id),
+        #[error("Monitor {0} overlaps monitor {1}")]
+        Overlap(Id, Id),
+        #[error("Monitor {0} overlaps another display")]
+        OverlapsDisplay(Id),
+        #[error("Monitor {0} does not share an edge with the rest of the desktop")]
+        Disconnected(Id),
+        #[error("Position is out of range")]
+        OutOfRange,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mode, Position};
+
+    fn monitor(id: Id, width: Dimen, height: Dimen, position: Option<(i32, i32)>) -> Monitor {
+        Monitor {
+            id,
+            enabled: true,
+            modes: vec![Mode {
+                width,
+                height,
+                refresh_rates: vec![60],
+            }],
+            position: position.map(|(x, y)| Position { x, y }),
+            ..Default::default()
+        }
+    }
+
+    fn rect(x: i32, y: i32, width: Dimen, height: Dimen) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn rects(layout: &Layout) -> Vec<(Id, Rect)> {
+        layout
+            .placements
+            .iter()
+            .map(|placement| (placement.id, placement.rect))
+            .collect()
+    }
+
+    #[test]
+    fn auto_placement() {
+        let monitors = [
+            monitor(0, 1920, 1080, None),
+            monitor(1, 2560, 1440, Some((1920, -360))),
+            monitor(2, 1280, 720, None),
+        ];
+        // the primary display
+        let display = rect(0, 0, 1920, 1080);
+
+        let layout = Layout::plan(&monitors, &[display]).unwrap();
+        assert_eq!(
+            rects(&layout),
+            [
+                (1, rect(1920, -360, 2560, 1440)),
+                (0, rect(4480, 0, 1920, 1080)),
+                (2, rect(6400, 0, 1280, 720)),
+            ]
+        );
+    }
+
+    #[test]
+    fn primary_is_origin() {
+        let mut monitors = [
+            monitor(0, 1920, 1080, Some((1920, 0))),
+            monitor(1, 1920, 1080, Some((3840, 0))),
+        ];
+        monitors[0].primary = true;
+        // the display which was primary until now
+        let display = rect(0, 0, 1920, 1080);
+
+        let layout = Layout::plan(&monitors, &[display]).unwrap();
+        assert_eq!(
+            rects(&layout),
+            [
+                (0, rect(0, 0, 1920, 1080)),
+                (1, rect(1920, 0, 1920, 1080)),
+            ]
+        );
+        assert!(layout.placements[0].primary);
+    }
+
+    #[test]
+    fn unplaced_primary_is_origin() {
+        let mut monitors = [monitor(0, 1920, 1080, None)];
+        monitors[0].primary = true;
+        // the display which was primary until now
+        let display = rect(0, 0, 2560, 1440);
+
+        let layout = Layout::plan(&monitors, &[display]).unwrap();
+        assert_eq!(rects(&layout), [(0, rect(0, 0, 1920, 1080))]);
+        assert!(layout.placements[0].primary);
+    }
+
+    #[test]
+    fn duplicates_and_disabled() {
+        let mut monitors = [
+            monitor(0, 1920, 1080, None),
+            monitor(1, 1920, 1080, None),
+            monitor(2, 1920, 1080, None),
+            monitor(3, 1920, 1080, None),
+        ];
+        monitors[1].topology = Topology::Duplicate;
+        monitors[2].enabled = false;
+        monitors[3].modes.clear();
+
+        let layout = Layout::plan(&monitors, &[]).unwrap();
+        assert_eq!(rects(&layout), [(0, rect(0, 0, 1920, 1080))]);
+        assert_eq!(layout.duplicates, [1]);
+
+        monitors[1].primary = true;
+        assert_eq!(
+            Layout::plan(&monitors, &[]),
+            Err(error::LayoutError::PrimaryDuplicate(1))
+        );
+    }
+
+    #[test]
+    fn invalid_layouts() {
+        let mut monitors = [
+            monitor(0, 1920, 1080, Some((0, 0))),
+            monitor(1, 1920, 1080, Some((1000, 0))),
+        ];
+        assert_eq!(
+            Layout::plan(&monitors, &[]),
+            Err(error::LayoutError::Overlap(0, 1))
+        );
+
+        // touching corners only
+        monitors[1].position = Some(Position { x: 1920, y: 1080 });
+        assert_eq!(
+            Layout::plan(&monitors, &[]),
+            Err(error::LayoutError::Disconnected(1))
+        );
+
+        monitors[1].position = Some(Position { x: 1920, y: 1079 });
+        assert!(Layout::plan(&monitors, &[]).is_ok());
+
+        assert_eq!(
+            Layout::plan(&monitors, &[rect(1000, 1000, 100, 100)]),
+            Err(error::LayoutError::OverlapsDisplay(0))
+        );
+
+        monitors[0].primary = true;
+        monitors[1].primary = true;
+        assert_eq!(
+            Layout::plan(&monitors, &[]),
+            Err(error::LayoutError::MultiplePrimary(vec![0, 1]))
+        );
+    }
+
+    #[test]
+    fn touches() {
+        let a = rect(0, 0, 100, 100);
+
+        assert!(a.touches(&rect(100, 50, 100, 100)));
+        assert!(a.touches(&rect(-10, 100, 20, 20)));
+        assert!(!a.touches(&rect(100, 100, 10, 10)));
+        assert!(!a.touches(&rect(101, 0, 10, 10)));
+        assert!(!a.touches(&rect(50, 50, 10, 10)));
+    }
+}
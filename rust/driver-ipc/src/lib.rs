@@ -1,13 +1,32 @@
+pub mod backend;
+#[cfg(windows)]
 mod client;
+pub mod codec;
+pub mod config;
 mod core;
+#[cfg(windows)]
 mod driver_client;
+pub mod layout;
+pub mod persist;
+pub mod preset;
+pub mod rate_limit;
+pub mod reconcile;
+#[cfg(feature = "schema")]
+pub mod schema;
+pub mod selector;
+#[cfg(windows)]
 pub mod sync;
 
+#[cfg(windows)]
 pub use client::Client;
+pub use config::{DriverConfig, DEFAULT_CONFIG_PATH};
 pub use core::*;
-pub use driver_client::DriverClient;
+#[cfg(windows)]
+pub use driver_client::{error, DriverClient};
 
 #[cfg(test)]
+mod golden;
+#[cfg(all(test, windows))]
 mod mock;
 
 pub static DEFAULT_PIPE_NAME: &str = "virtualdisplaydriver";
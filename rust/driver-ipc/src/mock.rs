@@ -1,5 +1,6 @@
 use std::{io, sync::Arc};
 
+use serde::Serialize;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::windows::named_pipe,
@@ -9,11 +10,15 @@ use tokio::{
 
 use crate::*;
 
-use self::client::EOF;
+use crate::codec::EOF;
 
 pub struct MockServer {
     server: Arc<named_pipe::NamedPipeServer>,
     state: Vec<Monitor>,
+    capabilities: Capabilities,
+    access: Access,
+    events: EventFilter,
+    echo: bool,
     command_rx: broadcast::Receiver<ServerCommand>,
     command_tx: broadcast::Sender<ServerCommand>,
     notify_closed: Arc<Notify>,
@@ -76,6 +81,10 @@ impl MockServer {
         Self {
             server,
             state: vec![],
+            capabilities: Capabilities { max_monitors: 16 },
+            access: Access::Control,
+            events: EventFilter::default(),
+            echo: true,
             command_rx,
             command_tx,
             notify_closed,
@@ -86,6 +95,90 @@ impl MockServer {
         &self.state
     }
 
+    pub fn capabilities(&self) -> Capabilities {
+        self.capabilities
+    }
+
+    /// Refuse commands which need more than `access`, like the driver does
+    pub fn set_access(&mut self, access: Access) {
+        self.access = access;
+    }
+
+    /// Whether the client gets the events of its own changes
+    ///
+    /// The driver only sends them to the other clients, the mock sends them by
+    /// default so a single client can watch them.
+    pub fn set_echo(&mut self, echo: bool) {
+        self.echo = echo;
+    }
+
+    /// Health of a driver where every enabled monitor has a swap chain
+    pub fn health(&self) -> Health {
+        Health {
+            version: "0.0.0".to_owned(),
+            git_sha: "mock".to_owned(),
+            iddcx_min_version: "1.4".to_owned(),
+            uptime_ms: 0,
+            adapter_initialized: true,
+            clients: 1,
+            pipe_server: true,
+            swap_chains: self
+                .state
+                .iter()
+                .filter(|m| m.enabled)
+                .map(|m| SwapChainHealth {
+                    id: m.id,
+                    alive: true,
+                })
+                .collect(),
+        }
+    }
+
+    /// Log records of the driver, filtered like the driver does
+    pub fn logs(&self, since: Option<u64>, level: Option<LogLevel>) -> Vec<LogRecord> {
+        let records = [
+            (LogLevel::Info, "Initialized Virtual Display Driver"),
+            (LogLevel::Error, "Failed to create monitor"),
+            (LogLevel::Debug, "IPC: Client #1 connected to pipe"),
+        ];
+
+        records
+            .into_iter()
+            .zip(0..)
+            .map(|((level, message), seq)| LogRecord {
+                seq,
+                timestamp_ms: 0,
+                level,
+                target: "mock".to_owned(),
+                message: message.to_owned(),
+            })
+            .filter(|r| since.map_or(true, |since| r.seq > since))
+            .filter(|r| level.map_or(true, |level| r.level <= level))
+            .collect()
+    }
+
+    /// Tell the client it sent something invalid, like the driver does
+    /// before disconnecting it
+    pub async fn protocol_error(&mut self, error: ProtocolError) {
+        self.write(&ReplyCommand::ProtocolError(error)).await;
+    }
+
+    async fn write(&mut self, command: &impl Serialize) {
+        let server = unsafe {
+            (self.server.as_ref() as *const _ as *mut named_pipe::NamedPipeServer)
+                .as_mut()
+                .unwrap()
+        };
+
+        let mut message = serde_json::to_vec(command).unwrap();
+        message.push(EOF);
+
+        server
+            .write_all(&message)
+            .await
+            .expect("Failed to write message");
+    }
+
     pub fn check_next(&mut self, cb: impl FnOnce(ServerCommand) + Send + 'static) {
         let mut rx = self.command_tx.subscribe();
 
@@ -105,26 +198,82 @@ impl MockServer {
     pub async fn pump(&mut self) {
         let cmd = self.command_rx.recv().await.unwrap();
 
-        let server = unsafe {
-            (self.server.as_ref() as *const _ as *mut named_pipe::NamedPipeServer)
-                .as_mut()
-                .unwrap()
-        };
+        let required = cmd.required_access();
+        if self.access < required {
+            let denied = PermissionDenied {
+                command: cmd.name().to_owned(),
+                reason: DenyReason::Access { required },
+            };
+            self.write(&ReplyCommand::PermissionDenied(denied)).await;
+            return;
+        }
 
         let changed = match cmd {
             ServerCommand::Request(RequestCommand::State) => {
                 let reply = ReplyCommand::State(self.state.clone());
-                let mut reply = serde_json::to_vec(&reply).unwrap();
-                reply.push(EOF);
-
-                server
-                    .write_all(&reply)
-                    .await
-                    .expect("Failed to write reply");
+                self.write(&reply).await;
+                false
+            }
+            ServerCommand::Request(RequestCommand::Capabilities) => {
+                let reply = ReplyCommand::Capabilities(self.capabilities);
+                self.write(&reply).await;
+                false
+            }
+            ServerCommand::Request(RequestCommand::Config) => {
+                let reply = ReplyCommand::Config(DriverConfig::default());
+                self.write(&reply).await;
+                false
+            }
+            ServerCommand::Request(RequestCommand::Health) => {
+                let reply = ReplyCommand::Health(self.health());
+                self.write(&reply).await;
+                false
+            }
+            ServerCommand::Request(RequestCommand::Logs { since, level }) => {
+                let reply = ReplyCommand::Logs(self.logs(since, level));
+                self.write(&reply).await;
+                false
+            }
+            ServerCommand::Request(RequestCommand::Stats) => {
+                let stats = Stats {
+                    monitors: self
+                        .state
+                        .iter()
+                        .map(|m| MonitorStats {
+                            id: m.id,
+                            ..MonitorStats::default()
+                        })
+                        .collect(),
+                    recording: None,
+                };
+                let reply = ReplyCommand::Stats(stats);
+                self.write(&reply).await;
+                false
+            }
+            ServerCommand::Request(RequestCommand::Access) => {
+                let reply = ReplyCommand::Access(self.access);
+                self.write(&reply).await;
+                false
+            }
+            ServerCommand::Request(RequestCommand::Subscribe { kinds, monitor_ids }) => {
+                self.events = EventFilter { kinds, monitor_ids };
                 false
             }
             ServerCommand::Driver(DriverCommand::Notify(monitors)) => {
+                let enabled = monitors.iter().filter(|m| m.enabled).count();
+                let max_monitors = self.capabilities.max_monitors;
+
+                if enabled > max_monitors as usize {
+                    let rejection = Rejection::LimitExceeded {
+                        enabled: u32::try_from(enabled).unwrap_or(u32::MAX),
+                        max_monitors,
+                    };
+                    self.write(&ReplyCommand::Rejected(rejection)).await;
+                    return;
+                }
+
                 self.state = monitors;
+                self.write(&ReplyCommand::Notified).await;
                 true
             }
             ServerCommand::Driver(DriverCommand::Remove(ids)) => {
@@ -138,15 +287,13 @@ impl MockServer {
             _ => false,
         };
 
-        if changed {
-            let event = EventCommand::Changed(self.state.clone());
-            let mut event = serde_json::to_vec(&event).unwrap();
-            event.push(EOF);
+        // like the driver, leave out what the client did not subscribe to
+        let event = (changed && self.echo)
+            .then(|| self.events.apply(EventCommand::Changed(self.state.clone())))
+            .flatten();
 
-            server
-                .write_all(&event)
-                .await
-                .expect("Failed to write event");
+        if let Some(event) = event {
+            self.write(&event).await;
         }
     }
 }
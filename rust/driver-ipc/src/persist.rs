@@ -0,0 +1,288 @@
+//! Persisted monitor state.
+//!
+//! A [Store] holds any number of named entries, each a list of monitors. The
+//! entry [DEFAULT_ENTRY] is the one applied automatically: the user session
+//! service applies it from the user's [RegistryStore] on logon, the driver
+//! applies it from the machine's [RegistryStore] when it starts.
+
+mod file;
+mod memory;
+#[cfg(windows)]
+mod registry;
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::Monitor;
+
+pub use file::{FileFormat, FileStore};
+pub use memory::MemoryStore;
+#[cfg(windows)]
+pub use registry::{RegistryStore, Scope, PERSIST_KEY, PERSIST_VALUE};
+
+/// Version written by [encode].
+///
+/// Version 0 is the original format, a bare array of monitors. Every later
+/// version wraps the monitors in an envelope `{"version": n, "monitors": ..}`.
+/// Older versions are upgraded by [MIGRATIONS] when they are read.
+pub const PERSIST_VERSION: u32 = 1;
+
+/// Name of the entry which is applied automatically.
+pub static DEFAULT_ENTRY: &str = "default";
+
+/// Storage for persisted monitor state.
+pub trait Store: fmt::Debug + Send + Sync {
+    /// Read the entry `name`.
+    ///
+    /// Returns `None` if the entry does not exist.
+    fn load(&self, name: &str) -> Result<Option<Vec<Monitor>>, error::PersistError>;
+
+    /// Create or replace the entry `name`.
+    fn save(&self, name: &str, monitors: &[Monitor]) -> Result<(), error::PersistError>;
+
+    /// Names of all entries, sorted.
+    fn list(&self) -> Result<Vec<String>, error::PersistError>;
+
+    /// Remove the entry `name`.
+    ///
+    /// Returns `false` if the entry did not exist.
+    fn delete(&self, name: &str) -> Result<bool, error::PersistError>;
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct Envelope<M> {
+    version: u32,
+    monitors: M,
+}
+
+/// A migration upgrades the monitors of version `n` to version `n + 1`.
+type Migration = fn(Value) -> Result<Value, String>;
+
+/// Migrations, indexed by the version they upgrade from.
+///
+/// When [Monitor] or [crate::Mode] change in a way old data does not
+/// deserialize into, bump [PERSIST_VERSION] and add a migration here, plus a
+/// golden file for the new version in `persist/fixtures`.
+static MIGRATIONS: [Migration; PERSIST_VERSION as usize] = [migrate_v0];
+
+/// Version 0 stored the array without an envelope, the monitors themselves did
+/// not change.
+fn migrate_v0(monitors: Value) -> Result<Value, String> {
+    if monitors.is_array() {
+        Ok(monitors)
+    } else {
+        Err("expected an array of monitors".to_owned())
+    }
+}
+
+/// Serialize `monitors` in the current format.
+pub fn encode(monitors: &[Monitor]) -> Result<String, serde_json::Error> {
+    serde_json::to_string(&Envelope {
+        version: PERSIST_VERSION,
+        monitors,
+    })
+}
+
+/// Deserialize persisted monitors of any known version.
+pub fn decode(data: &str) -> Result<Vec<Monitor>, error::DecodeError> {
+    // version 0 has no envelope
+    if data.trim_start().starts_with('[') {
+        return migrate(0, serde_json::from_str(data)?);
+    }
+
+    let envelope = serde_json::from_str::<Envelope<Value>>(data)?;
+
+    migrate(envelope.version, envelope.monitors)
+}
+
+/// Upgrade `monitors` from `version` to [PERSIST_VERSION] and deserialize them.
+fn migrate(version: u32, mut monitors: Value) -> Result<Vec<Monitor>, error::DecodeError> {
+    if version > PERSIST_VERSION {
+        return Err(error::DecodeError::UnsupportedVersion(version));
+    }
+
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        monitors = migration(monitors).map_err(|reason| error::DecodeError::Migration {
+            from: from as u32,
+            reason,
+        })?;
+    }
+
+    serde_json::from_value(monitors)
+        .map_err(|source| error::DecodeError::Invalid { version, source })
+}
+
+/// Entry names are used as file and registry value names, so they are
+/// restricted to a safe subset.
+fn validate_name(name: &str) -> Result<(), error::PersistError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ' '))
+        && !name.starts_with('.')
+        && !name.ends_with(['.', ' ']);
+
+    if valid {
+        Ok(())
+    } else {
+        Err(error::PersistError::InvalidName(name.to_owned()))
+    }
+}
+
+pub mod error {
+    use std::io;
+
+    use thiserror::Error;
+
+    /// Error returned from [super::Store] operations.
+    #[derive(Debug, Error)]
+    pub enum PersistError {
+        #[error("Failed to access store: {0}")]
+        Io(#[from] io::Error),
+        #[error("Failed to serialize monitors: {0}")]
+        Serialize(#[from] serde_json::Error),
+        #[error("Failed to serialize monitors: {0}")]
+        SerializeToml(#[from] toml::ser::Error),
+        #[error("Failed to load entry: {0}")]
+        Decode(#[from] DecodeError),
+        #[error("Invalid entry name {0:?}: use letters, digits, '-', '_', '.' and spaces")]
+        InvalidName(String),
+    }
+
+    /// Error returned from [super::decode].
+    #[derive(Debug, Error)]
+    pub enum DecodeError {
+        #[error("Failed to deserialize monitors: {0}")]
+        Deserialize(#[from] serde_json::Error),
+        #[error("Failed to deserialize monitors: {0}")]
+        DeserializeToml(#[from] toml::de::Error),
+        #[error("Unsupported persisted state version {0}")]
+        UnsupportedVersion(u32),
+        #[error("Failed to migrate persisted state from version {from}: {reason}")]
+        Migration { from: u32, reason: String },
+        #[error("Persisted state of version {version} does not match its format: {source}")]
+        Invalid {
+            version: u32,
+            source: serde_json::Error,
+        },
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::Mode;
+
+    pub fn monitors() -> Vec<Monitor> {
+        vec![
+            Monitor {
+                id: 0,
+                name: Some("test".to_owned()),
+                enabled: true,
+                modes: vec![Mode {
+                    width: 1920,
+                    height: 1080,
+                    refresh_rates: vec![60, 120],
+                }],
+                ..Default::default()
+            },
+            Monitor {
+                id: 1,
+                enabled: false,
+                ..Default::default()
+            },
+        ]
+    }
+
+    /// Behaviour every [Store] must have, starting from an empty store.
+    pub fn check_store(store: &impl Store) {
+        assert!(store.list().unwrap().is_empty());
+        assert_eq!(store.load(DEFAULT_ENTRY).unwrap(), None);
+
+        store.save(DEFAULT_ENTRY, &monitors()).unwrap();
+        store.save("office", &monitors()[..1]).unwrap();
+
+        assert_eq!(store.list().unwrap(), [DEFAULT_ENTRY, "office"]);
+        assert_eq!(store.load(DEFAULT_ENTRY).unwrap(), Some(monitors()));
+        assert_eq!(
+            store.load("office").unwrap(),
+            Some(monitors()[..1].to_vec())
+        );
+
+        // replace
+        store.save("office", &[]).unwrap();
+        assert_eq!(store.load("office").unwrap(), Some(vec![]));
+
+        assert!(store.delete("office").unwrap());
+        assert!(!store.delete("office").unwrap());
+        assert_eq!(store.load("office").unwrap(), None);
+        assert_eq!(store.list().unwrap(), [DEFAULT_ENTRY]);
+
+        for name in ["", "../x", r"a\b", ".hidden", "x."] {
+            assert!(
+                matches!(
+                    store.save(name, &[]),
+                    Err(error::PersistError::InvalidName(_))
+                ),
+                "{name:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = encode(&monitors()).unwrap();
+        assert!(data.starts_with(r#"{"version":1,"#), "{data}");
+        assert_eq!(decode(&data).unwrap(), monitors());
+    }
+
+    #[test]
+    fn decodes_version_0() {
+        let data = serde_json::to_string(&monitors()).unwrap();
+        assert_eq!(decode(&data).unwrap(), monitors());
+    }
+
+    /// Data written by every released version must keep loading.
+    #[test]
+    fn decodes_golden_files() {
+        let golden = [
+            (0, include_str!("persist/fixtures/v0.json")),
+            (1, include_str!("persist/fixtures/v1.json")),
+        ];
+        assert_eq!(golden.len(), PERSIST_VERSION as usize + 1);
+
+        for (version, data) in golden {
+            assert_eq!(decode(data).unwrap(), monitors(), "version {version}");
+        }
+    }
+
+    #[test]
+    fn reports_failed_migration() {
+        let err = decode(r#"{"version":0,"monitors":{}}"#).unwrap_err();
+        assert!(
+            matches!(err, error::DecodeError::Migration { from: 0, .. }),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn reports_invalid_monitors() {
+        let err = decode(r#"{"version":1,"monitors":[{"id":"0"}]}"#).unwrap_err();
+        assert!(
+            matches!(err, error::DecodeError::Invalid { version: 1, .. }),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let err = decode(r#"{"version":2,"monitors":[]}"#).unwrap_err();
+        assert!(
+            matches!(err, error::DecodeError::UnsupportedVersion(2)),
+            "{err:?}"
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use super::{decode, encode, error::PersistError, migrate, validate_name, Envelope, Store};
+use crate::Monitor;
+
+/// File format used by a [FileStore].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FileFormat {
+    Json,
+    Toml,
+}
+
+impl FileFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            FileFormat::Json => "json",
+            FileFormat::Toml => "toml",
+        }
+    }
+}
+
+/// A [Store] which keeps every entry in its own file `{dir}/{name}.{ext}`.
+///
+/// The files use the same versioned format as the registry, so they can be
+/// shipped to other machines as is.
+#[derive(Debug, Clone)]
+pub struct FileStore {
+    dir: PathBuf,
+    format: FileFormat,
+}
+
+impl FileStore {
+    /// Store entries in `dir`. The directory is created on the first save.
+    pub fn new(dir: impl Into<PathBuf>, format: FileFormat) -> Self {
+        Self {
+            dir: dir.into(),
+            format,
+        }
+    }
+
+    /// Directory the entries are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{name}.{}", self.format.extension()))
+    }
+}
+
+impl Store for FileStore {
+    fn load(&self, name: &str) -> Result<Option<Vec<Monitor>>, PersistError> {
+        validate_name(name)?;
+
+        let data = match fs::read_to_string(self.path(name)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        let monitors = match self.format {
+            FileFormat::Json => decode(&data)?,
+            FileFormat::Toml => {
+                let envelope = toml::from_str::<Envelope<serde_json::Value>>(&data)
+                    .map_err(super::error::DecodeError::from)?;
+
+                migrate(envelope.version, envelope.monitors)?
+            }
+        };
+
+        Ok(Some(monitors))
+    }
+
+    fn save(&self, name: &str, monitors: &[Monitor]) -> Result<(), PersistError> {
+        validate_name(name)?;
+
+        let data = match self.format {
+            FileFormat::Json => encode(monitors)?,
+            FileFormat::Toml => toml::to_string(&Envelope {
+                version: super::PERSIST_VERSION,
+                monitors,
+            })?,
+        };
+
+        fs::create_dir_all(&self.dir)?;
+
+        // write to a temporary file first, so a crash never leaves a partial entry behind
+        let path = self.path(name);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, data)?;
+        fs::rename(&tmp_path, &path)?;
+
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, PersistError> {
+        let dir = match fs::read_dir(&self.dir) {
+            Ok(dir) => dir,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut names = Vec::new();
+        for entry in dir {
+            let path = entry?.path();
+
+            if path.extension().and_then(|ext| ext.to_str()) != Some(self.format.extension()) {
+                continue;
+            }
+
+            if let Some(name) = path.file_stem().and_then(|name| name.to_str()) {
+                if validate_name(name).is_ok() {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+
+        names.sort();
+
+        Ok(names)
+    }
+
+    fn delete(&self, name: &str) -> Result<bool, PersistError> {
+        validate_name(name)?;
+
+        match fs::remove_file(self.path(name)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persist::{tests::*, DEFAULT_ENTRY};
+
+    #[test]
+    fn json_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&FileStore::new(dir.path().join("store"), FileFormat::Json));
+    }
+
+    #[test]
+    fn toml_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&FileStore::new(dir.path().join("store"), FileFormat::Toml));
+    }
+
+    #[test]
+    fn json_store_reads_version_0() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("default.json"),
+            serde_json::to_string(&monitors()).unwrap(),
+        )
+        .unwrap();
+
+        let store = FileStore::new(dir.path(), FileFormat::Json);
+        assert_eq!(store.load(DEFAULT_ENTRY).unwrap(), Some(monitors()));
+    }
+
+    #[test]
+    fn toml_store_reads_golden_file() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("default.toml"),
+            include_str!("fixtures/v1.toml"),
+        )
+        .unwrap();
+
+        let store = FileStore::new(dir.path(), FileFormat::Toml);
+        assert_eq!(store.load(DEFAULT_ENTRY).unwrap(), Some(monitors()));
+    }
+
+    #[test]
+    fn list_ignores_other_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("notes.txt"), "").unwrap();
+        fs::write(dir.path().join("other.toml"), "").unwrap();
+
+        let store = FileStore::new(dir.path(), FileFormat::Json);
+        store.save("office", &monitors()).unwrap();
+
+        assert_eq!(store.list().unwrap(), ["office"]);
+    }
+}
@@ -0,0 +1,54 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use super::{error::PersistError, validate_name, Store};
+use crate::Monitor;
+
+/// A [Store] which only lives as long as the object.
+///
+/// Useful for tests and dry runs.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Mutex<BTreeMap<String, Vec<Monitor>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Store for MemoryStore {
+    fn load(&self, name: &str) -> Result<Option<Vec<Monitor>>, PersistError> {
+        Ok(self.entries.lock().unwrap().get(name).cloned())
+    }
+
+    fn save(&self, name: &str, monitors: &[Monitor]) -> Result<(), PersistError> {
+        validate_name(name)?;
+
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(name.to_owned(), monitors.to_vec());
+
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, PersistError> {
+        Ok(self.entries.lock().unwrap().keys().cloned().collect())
+    }
+
+    fn delete(&self, name: &str) -> Result<bool, PersistError> {
+        Ok(self.entries.lock().unwrap().remove(name).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persist::tests::check_store;
+
+    #[test]
+    fn memory_store() {
+        check_store(&MemoryStore::new());
+    }
+}
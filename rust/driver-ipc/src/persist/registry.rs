@@ -0,0 +1,143 @@
+use std::io;
+
+use winreg::{enums, RegKey};
+
+use super::{decode, encode, error::PersistError, validate_name, Store, DEFAULT_ENTRY};
+use crate::Monitor;
+
+/// Registry key the state is stored under.
+pub static PERSIST_KEY: &str = r"SOFTWARE\VirtualDisplayDriver";
+
+/// Registry value [DEFAULT_ENTRY] is stored in.
+///
+/// This is the original location of the persisted state, so state saved by
+/// older clients is still found and upgraded by [crate::persist::decode] (see
+/// `migrate_v0`). Older clients and services can not read what is written now,
+/// since it is wrapped in a versioned envelope.
+pub static PERSIST_VALUE: &str = "data";
+
+/// Subkey of [PERSIST_KEY] all other entries are stored in, one value each.
+static ENTRIES_KEY: &str = "Layouts";
+
+/// Where the state is persisted.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Scope {
+    /// `HKEY_CURRENT_USER`, applied by the user session service on logon.
+    User,
+    /// `HKEY_LOCAL_MACHINE`, applied by the driver on startup. Writing it
+    /// requires admin rights.
+    Machine,
+}
+
+/// A [Store] in the registry.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RegistryStore {
+    scope: Scope,
+}
+
+impl RegistryStore {
+    pub fn new(scope: Scope) -> Self {
+        Self { scope }
+    }
+
+    pub fn scope(&self) -> Scope {
+        self.scope
+    }
+
+    fn root(&self) -> RegKey {
+        match self.scope {
+            Scope::User => RegKey::predef(enums::HKEY_CURRENT_USER),
+            Scope::Machine => RegKey::predef(enums::HKEY_LOCAL_MACHINE),
+        }
+    }
+
+    fn entries_key() -> String {
+        format!(r"{PERSIST_KEY}\{ENTRIES_KEY}")
+    }
+
+    /// Key and value name of the entry `name`.
+    fn location(name: &str) -> (String, &str) {
+        if name == DEFAULT_ENTRY {
+            (PERSIST_KEY.to_owned(), PERSIST_VALUE)
+        } else {
+            (Self::entries_key(), name)
+        }
+    }
+
+    /// Open `key` with `flags`, `None` if it does not exist.
+    fn open(&self, key: &str, flags: u32) -> io::Result<Option<RegKey>> {
+        match self.root().open_subkey_with_flags(key, flags) {
+            Ok(key) => Ok(Some(key)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Store for RegistryStore {
+    fn load(&self, name: &str) -> Result<Option<Vec<Monitor>>, PersistError> {
+        validate_name(name)?;
+
+        let (key, value) = Self::location(name);
+        let Some(reg_key) = self.open(&key, enums::KEY_READ)? else {
+            return Ok(None);
+        };
+
+        let data = match reg_key.get_value::<String, _>(value) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Some(decode(&data)?))
+    }
+
+    fn save(&self, name: &str, monitors: &[Monitor]) -> Result<(), PersistError> {
+        validate_name(name)?;
+
+        let (key, value) = Self::location(name);
+        let (reg_key, _) = self.root().create_subkey(key)?;
+
+        reg_key.set_value(value, &encode(monitors)?)?;
+
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<String>, PersistError> {
+        let mut names = Vec::new();
+
+        if let Some(reg_key) = self.open(PERSIST_KEY, enums::KEY_READ)? {
+            if reg_key.get_raw_value(PERSIST_VALUE).is_ok() {
+                names.push(DEFAULT_ENTRY.to_owned());
+            }
+        }
+
+        if let Some(reg_key) = self.open(&Self::entries_key(), enums::KEY_READ)? {
+            for value in reg_key.enum_values() {
+                let (name, _) = value?;
+                if name != DEFAULT_ENTRY && validate_name(&name).is_ok() {
+                    names.push(name);
+                }
+            }
+        }
+
+        names.sort();
+
+        Ok(names)
+    }
+
+    fn delete(&self, name: &str) -> Result<bool, PersistError> {
+        validate_name(name)?;
+
+        let (key, value) = Self::location(name);
+        let Some(reg_key) = self.open(&key, enums::KEY_SET_VALUE)? else {
+            return Ok(false);
+        };
+
+        match reg_key.delete_value(value) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
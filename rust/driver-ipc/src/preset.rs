@@ -0,0 +1,311 @@
+//! Catalog of standard resolutions and device presets.
+//!
+//! ```ignore
+//! let mode = Mode::preset("4k")?.with_refresh(&[60, 120]);
+//! let mode = "MacBook Pro 14@120".parse::<Mode>()?;
+//! ```
+//!
+//! Preset names are matched case-insensitively, ignoring spaces, `-` and `_`,
+//! so `MacBook Pro 14`, `macbook-pro-14` and `macbookpro14` are the same.
+
+use std::str::FromStr;
+
+use crate::{Dimen, Mode, RefreshRate};
+
+/// Refresh rates used when none are given.
+pub const DEFAULT_REFRESH_RATES: &[RefreshRate] = &[60];
+
+/// Common refresh rate ladders, for use with [Mode::with_refresh].
+pub mod ladder {
+    use crate::RefreshRate;
+
+    /// Office monitors and TVs.
+    pub const STANDARD: &[RefreshRate] = &[60];
+    /// Film and broadcast frame rates.
+    pub const VIDEO: &[RefreshRate] = &[24, 25, 30, 50, 60];
+    /// Variable refresh rate laptops and tablets.
+    pub const PROMOTION: &[RefreshRate] = &[60, 120];
+    /// Gaming monitors.
+    pub const GAMING: &[RefreshRate] = &[60, 120, 144, 165, 240];
+}
+
+/// A named resolution with default refresh rates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Preset {
+    /// Canonical name.
+    pub name: &'static str,
+    /// Other names the preset can be found by.
+    pub aliases: &'static [&'static str],
+    pub width: Dimen,
+    pub height: Dimen,
+    pub refresh_rates: &'static [RefreshRate],
+}
+
+impl Preset {
+    const fn new(
+        name: &'static str,
+        aliases: &'static [&'static str],
+        width: Dimen,
+        height: Dimen,
+        refresh_rates: &'static [RefreshRate],
+    ) -> Self {
+        Self {
+            name,
+            aliases,
+            width,
+            height,
+            refresh_rates,
+        }
+    }
+
+    /// The preset as a mode with its default refresh rates.
+    pub fn mode(&self) -> Mode {
+        Mode {
+            width: self.width,
+            height: self.height,
+            refresh_rates: self.refresh_rates.to_vec(),
+        }
+    }
+}
+
+/// All presets, standard resolutions first, then ultrawides, then devices.
+pub static PRESETS: &[Preset] = &[
+    // 16:9
+    Preset::new("720p", &["hd"], 1280, 720, ladder::STANDARD),
+    Preset::new("900p", &["hd+"], 1600, 900, ladder::STANDARD),
+    Preset::new("1080p", &["fhd", "full-hd"], 1920, 1080, ladder::STANDARD),
+    Preset::new(
+        "1440p",
+        &["qhd", "wqhd", "2k"],
+        2560,
+        1440,
+        ladder::STANDARD,
+    ),
+    Preset::new("4k", &["uhd", "2160p"], 3840, 2160, ladder::STANDARD),
+    Preset::new("5k", &[], 5120, 2880, ladder::STANDARD),
+    Preset::new("8k", &["4320p"], 7680, 4320, ladder::STANDARD),
+    // 16:10
+    Preset::new("wxga", &["800p"], 1280, 800, ladder::STANDARD),
+    Preset::new("wuxga", &["1200p"], 1920, 1200, ladder::STANDARD),
+    Preset::new("wqxga", &["1600p"], 2560, 1600, ladder::STANDARD),
+    // 21:9 and 32:9
+    Preset::new("ultrawide-1080p", &["uwfhd"], 2560, 1080, ladder::STANDARD),
+    Preset::new("ultrawide-1440p", &["uwqhd"], 3440, 1440, ladder::STANDARD),
+    Preset::new(
+        "ultrawide-5k",
+        &["5k2k", "wuhd"],
+        5120,
+        2160,
+        ladder::STANDARD,
+    ),
+    Preset::new(
+        "super-ultrawide-1080p",
+        &["dfhd"],
+        3840,
+        1080,
+        ladder::STANDARD,
+    ),
+    Preset::new(
+        "super-ultrawide-1440p",
+        &["dqhd"],
+        5120,
+        1440,
+        ladder::STANDARD,
+    ),
+    // devices
+    Preset::new("macbook-air-13", &[], 2560, 1664, ladder::STANDARD),
+    Preset::new("macbook-air-15", &[], 2880, 1864, ladder::STANDARD),
+    Preset::new("macbook-pro-14", &[], 3024, 1964, ladder::PROMOTION),
+    Preset::new("macbook-pro-16", &[], 3456, 2234, ladder::PROMOTION),
+    Preset::new("ipad", &[], 2360, 1640, ladder::STANDARD),
+    Preset::new("ipad-pro-11", &[], 2388, 1668, ladder::PROMOTION),
+    Preset::new("ipad-pro-12.9", &[], 2732, 2048, ladder::PROMOTION),
+    Preset::new("surface-pro", &[], 2880, 1920, ladder::PROMOTION),
+    Preset::new("steam-deck", &[], 1280, 800, ladder::STANDARD),
+];
+
+/// Find a preset by name or alias.
+pub fn find(name: &str) -> Option<&'static Preset> {
+    let name = normalize(name);
+
+    PRESETS.iter().find(|preset| {
+        normalize(preset.name) == name || preset.aliases.iter().any(|a| normalize(a) == name)
+    })
+}
+
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '_'))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+impl Mode {
+    /// The mode of preset `name` with its default refresh rates.
+    pub fn preset(name: &str) -> Result<Self, error::UnknownPreset> {
+        find(name)
+            .map(Preset::mode)
+            .ok_or_else(|| error::UnknownPreset(name.to_owned()))
+    }
+
+    /// Replace the refresh rates.
+    #[must_use]
+    pub fn with_refresh(mut self, refresh_rates: &[RefreshRate]) -> Self {
+        self.refresh_rates = refresh_rates.to_vec();
+        self
+    }
+}
+
+/// Parse a resolution, which is either `WIDTHxHEIGHT` or a preset name.
+///
+/// Returns the preset's refresh rates, or `None` for an explicit resolution.
+pub fn parse_resolution(
+    s: &str,
+) -> Result<(Dimen, Dimen, Option<&'static [RefreshRate]>), error::ParseModeError> {
+    if let Some(preset) = find(s) {
+        return Ok((preset.width, preset.height, Some(preset.refresh_rates)));
+    }
+
+    let Some((width, height)) = s.split_once('x') else {
+        return Err(error::ParseModeError::UnknownResolution(s.to_owned()));
+    };
+
+    let parse = |dimen: &str| {
+        dimen
+            .parse()
+            .map_err(|_| error::ParseModeError::InvalidNumber(dimen.to_owned()))
+    };
+
+    Ok((parse(width)?, parse(height)?, None))
+}
+
+/// Parses `WIDTHxHEIGHT` or a preset name, optionally followed by `@` and
+/// refresh rates separated by `/`, e.g. `1920x1080@60/120` or `4k@144`.
+///
+/// Without refresh rates, a preset keeps its default ones and an explicit
+/// resolution gets [DEFAULT_REFRESH_RATES].
+impl FromStr for Mode {
+    type Err = error::ParseModeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (resolution, refresh_rates) = match s.split_once('@') {
+            Some((resolution, refresh_rates)) => (resolution, Some(refresh_rates)),
+            None => (s, None),
+        };
+
+        let (width, height, preset_refresh_rates) = parse_resolution(resolution)?;
+
+        let refresh_rates = match refresh_rates {
+            Some(refresh_rates) => refresh_rates
+                .split('/')
+                .map(|rr| {
+                    rr.parse()
+                        .map_err(|_| error::ParseModeError::InvalidNumber(rr.to_owned()))
+                })
+                .collect::<Result<_, _>>()?,
+            None => preset_refresh_rates
+                .unwrap_or(DEFAULT_REFRESH_RATES)
+                .to_vec(),
+        };
+
+        Ok(Mode {
+            width,
+            height,
+            refresh_rates,
+        })
+    }
+}
+
+pub mod error {
+    use thiserror::Error;
+
+    /// Error returned from [crate::Mode::preset].
+    #[derive(Debug, Clone, PartialEq, Eq, Error)]
+    #[error("Unknown preset `{0}`")]
+    pub struct UnknownPreset(pub String);
+
+    /// Error returned when parsing a [crate::Mode].
+    #[derive(Debug, Clone, PartialEq, Eq, Error)]
+    pub enum ParseModeError {
+        #[error("Unknown resolution `{0}`, expected WIDTHxHEIGHT or a preset like `4k`")]
+        UnknownResolution(String),
+        #[error("Invalid number `{0}`")]
+        InvalidNumber(String),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preset_with_refresh() {
+        let mode = Mode::preset("4k").unwrap().with_refresh(&[60, 120]);
+        assert_eq!(
+            mode,
+            Mode {
+                width: 3840,
+                height: 2160,
+                refresh_rates: vec![60, 120],
+            }
+        );
+
+        assert_eq!(
+            Mode::preset("8K"),
+            Ok(Mode {
+                width: 7680,
+                height: 4320,
+                refresh_rates: vec![60],
+            })
+        );
+        assert_eq!(
+            Mode::preset("16k"),
+            Err(error::UnknownPreset("16k".to_owned()))
+        );
+    }
+
+    #[test]
+    fn find_normalizes_names() {
+        let preset = find("MacBook Pro 14").unwrap();
+        assert_eq!(preset.name, "macbook-pro-14");
+        assert_eq!(find("macbookpro14"), Some(preset));
+        assert_eq!(find("UWQHD").unwrap().name, "ultrawide-1440p");
+        assert_eq!(find("2K").unwrap().name, "1440p");
+    }
+
+    #[test]
+    fn names_are_unique() {
+        let mut names = PRESETS
+            .iter()
+            .flat_map(|preset| std::iter::once(&preset.name).chain(preset.aliases))
+            .map(|name| normalize(name))
+            .collect::<Vec<_>>();
+        let count = names.len();
+
+        names.sort();
+        names.dedup();
+
+        assert_eq!(names.len(), count);
+    }
+
+    #[test]
+    fn parse() {
+        let parse = |s: &str| {
+            s.parse::<Mode>()
+                .map(|mode| (mode.width, mode.height, mode.refresh_rates))
+        };
+
+        assert_eq!(parse("1920x1080"), Ok((1920, 1080, vec![60])));
+        assert_eq!(parse("1920x1080@60/120"), Ok((1920, 1080, vec![60, 120])));
+        assert_eq!(parse("4k@144"), Ok((3840, 2160, vec![144])));
+        assert_eq!(parse("macbook-pro-14"), Ok((3024, 1964, vec![60, 120])));
+        assert_eq!(
+            parse("foo@60"),
+            Err(error::ParseModeError::UnknownResolution("foo".to_owned()))
+        );
+        assert_eq!(
+            parse("4k@fast"),
+            Err(error::ParseModeError::InvalidNumber("fast".to_owned()))
+        );
+    }
+}
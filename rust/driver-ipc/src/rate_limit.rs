@@ -0,0 +1,106 @@
+//! Limiting how fast a client may send messages.
+//!
+//! Every client has a bucket of `rate` tokens which refills at `rate` tokens
+//! per second. Each message takes a token, so a client may send a burst of up
+//! to `rate` messages and `rate` messages per second after that. A message
+//! without a token is not dropped, it has to wait until its token is refilled,
+//! which stops the reader from reading and so slows down the client.
+
+use std::time::{Duration, Instant};
+
+/// Token bucket of a client, see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct RateLimit {
+    rate: f64,
+    /// Negative while messages wait for their token
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimit {
+    /// Create a full bucket allowing `rate` messages per second.
+    pub fn new(rate: u32, now: Instant) -> Self {
+        let rate = f64::from(rate.max(1));
+
+        Self {
+            rate,
+            tokens: rate,
+            last: now,
+        }
+    }
+
+    /// Take a token for a message arriving at `now`, and return how long the
+    /// message has to wait for it.
+    pub fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = self.last.max(now);
+
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate) - 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::{collection::vec, prelude::*};
+
+    use super::*;
+
+    #[test]
+    fn bursts_then_throttles() {
+        let start = Instant::now();
+        let mut limit = RateLimit::new(10, start);
+
+        for _ in 0..10 {
+            assert_eq!(limit.take(start), Duration::ZERO);
+        }
+
+        // the 11th to 20th message wait for their token in turn
+        for i in 1..=10 {
+            let wait = limit.take(start).as_secs_f64();
+            assert!((wait - f64::from(i) / 10.0).abs() < 1e-9, "{i}: {wait}");
+        }
+
+        // after a few quiet seconds the bucket is full again
+        let later = start + Duration::from_secs(3);
+        for _ in 0..10 {
+            assert_eq!(limit.take(later), Duration::ZERO);
+        }
+        assert!(limit.take(later) > Duration::ZERO);
+    }
+
+    proptest! {
+        #[test]
+        fn never_exceeds_rate(
+            rate in 1..1000u32,
+            gaps in vec(0..50_000u64, 1..500),
+        ) {
+            let start = Instant::now();
+            let mut limit = RateLimit::new(rate, start);
+
+            // messages are processed in order, each once its wait is over
+            let mut arrival = start;
+            let mut processed = Vec::new();
+            for gap in gaps {
+                arrival += Duration::from_micros(gap);
+                let ready = processed.last().map_or(arrival, |&last: &Instant| last.max(arrival));
+                processed.push(ready + limit.take(ready));
+            }
+
+            // at most a burst plus `rate` messages per second in any window
+            for (i, first) in processed.iter().enumerate() {
+                for (j, last) in processed.iter().enumerate().skip(i) {
+                    let window = last.duration_since(*first).as_secs_f64();
+                    let allowed = f64::from(rate) + window * f64::from(rate) + 1e-6;
+                    #[allow(clippy::cast_precision_loss)]
+                    let count = (j - i + 1) as f64;
+                    prop_assert!(count <= allowed, "{count} messages in {window}s");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,454 @@
+//! Bring the driver to a desired state with as few changes as possible.
+//!
+//! [Plan::new] compares the live monitors with the desired ones. Monitors are
+//! matched by [Monitor::id]. Applying the plan keeps every monitor which does
+//! not need to change untouched, so the driver neither reattaches nor
+//! reconfigures it.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Color, Id, Mode, Monitor, Orientation, PhysicalSize, Position, Topology};
+
+/// What to do with live monitors which are not part of the desired state.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    /// Leave them as they are.
+    #[default]
+    KeepUnknown,
+    /// Remove them.
+    RemoveUnknown,
+}
+
+/// A single change of a [Plan].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Change {
+    Create { monitor: Monitor },
+    Remove { id: Id },
+    Rename { id: Id, name: Option<String> },
+    SetKey { id: Id, key: Option<String> },
+    UpdateModes { id: Id, modes: Vec<Mode> },
+    SetPhysicalSize {
+        id: Id,
+        physical_size: Option<PhysicalSize>,
+    },
+    SetOrientation {
+        id: Id,
+        orientation: Orientation,
+    },
+    SetLayout {
+        id: Id,
+        position: Option<Position>,
+        primary: bool,
+        topology: Topology,
+    },
+    SetColor {
+        id: Id,
+        color: Color,
+    },
+    Enable { id: Id },
+    Disable { id: Id },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Create { monitor } => write!(f, "create monitor {}", monitor.id),
+            Change::Remove { id } => write!(f, "remove monitor {id}"),
+            Change::Rename {
+                id,
+                name: Some(name),
+            } => {
+                write!(f, "rename monitor {id} to {name:?}")
+            }
+            Change::Rename { id, name: None } => write!(f, "remove name of monitor {id}"),
+            Change::SetKey { id, key: Some(key) } => {
+                write!(f, "set key of monitor {id} to {key:?}")
+            }
+            Change::SetKey { id, key: None } => write!(f, "remove key of monitor {id}"),
+            Change::UpdateModes { id, modes } => {
+                write!(f, "update modes of monitor {id} to ")?;
+                for (i, mode) in modes.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}x{}", mode.width, mode.height)?;
+                    for (i, rate) in mode.refresh_rates.iter().enumerate() {
+                        write!(f, "{}{rate}", if i == 0 { "@" } else { "/" })?;
+                    }
+                }
+                Ok(())
+            }
+            Change::SetPhysicalSize {
+                id,
+                physical_size: Some(size),
+            } => write!(f, "set physical size of monitor {id} to {size}"),
+            Change::SetPhysicalSize {
+                id,
+                physical_size: None,
+            } => write!(f, "remove physical size of monitor {id}"),
+            Change::SetOrientation { id, orientation } => {
+                write!(f, "set orientation of monitor {id} to {orientation}")
+            }
+            Change::SetLayout {
+                id,
+                position,
+                primary,
+                topology,
+            } => {
+                write!(f, "set layout of monitor {id} to {topology}")?;
+                if let Some(position) = position {
+                    write!(f, " at {position}")?;
+                }
+                if *primary {
+                    write!(f, " as primary")?;
+                }
+                Ok(())
+            }
+            Change::SetColor { id, color } => write!(f, "set color of monitor {id} to {color}"),
+            Change::Enable { id } => write!(f, "enable monitor {id}"),
+            Change::Disable { id } => write!(f, "disable monitor {id}"),
+        }
+    }
+}
+
+/// Changes which turn the live state into the desired state.
+///
+/// Removals come first, so their capacity is free before anything is created
+/// or enabled.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Plan {
+    pub changes: Vec<Change>,
+}
+
+impl Plan {
+    /// Compute the changes from `current` to `desired`.
+    pub fn new(current: &[Monitor], desired: &[Monitor], policy: Policy) -> Self {
+        let mut changes = Vec::new();
+
+        if policy == Policy::RemoveUnknown {
+            for monitor in current {
+                if !desired.iter().any(|m| m.id == monitor.id) {
+                    changes.push(Change::Remove { id: monitor.id });
+                }
+            }
+        }
+
+        for monitor in desired {
+            let id = monitor.id;
+
+            let Some(live) = current.iter().find(|m| m.id == id) else {
+                changes.push(Change::Create {
+                    monitor: monitor.clone(),
+                });
+                continue;
+            };
+
+            if live.enabled && !monitor.enabled {
+                changes.push(Change::Disable { id });
+            }
+
+            if live.name != monitor.name {
+                changes.push(Change::Rename {
+                    id,
+                    name: monitor.name.clone(),
+                });
+            }
+
+            if live.key != monitor.key {
+                changes.push(Change::SetKey {
+                    id,
+                    key: monitor.key.clone(),
+                });
+            }
+
+            if live.modes != monitor.modes {
+                changes.push(Change::UpdateModes {
+                    id,
+                    modes: monitor.modes.clone(),
+                });
+            }
+
+            if live.physical_size != monitor.physical_size {
+                changes.push(Change::SetPhysicalSize {
+                    id,
+                    physical_size: monitor.physical_size,
+                });
+            }
+
+            if live.orientation != monitor.orientation {
+                changes.push(Change::SetOrientation {
+                    id,
+                    orientation: monitor.orientation,
+                });
+            }
+
+            if (live.position, live.primary, live.topology)
+                != (monitor.position, monitor.primary, monitor.topology)
+            {
+                changes.push(Change::SetLayout {
+                    id,
+                    position: monitor.position,
+                    primary: monitor.primary,
+                    topology: monitor.topology,
+                });
+            }
+
+            if live.color != monitor.color {
+                changes.push(Change::SetColor {
+                    id,
+                    color: monitor.color,
+                });
+            }
+
+            if !live.enabled && monitor.enabled {
+                changes.push(Change::Enable { id });
+            }
+        }
+
+        Self { changes }
+    }
+
+    /// Whether `current` already is the desired state.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// The state after applying the plan to `current`.
+    ///
+    /// Monitors keep their order, created monitors are appended.
+    pub fn apply(&self, current: &[Monitor]) -> Vec<Monitor> {
+        let mut monitors = current.to_vec();
+
+        for change in &self.changes {
+            match change {
+                Change::Create { monitor } => monitors.push(monitor.clone()),
+                Change::Remove { id } => monitors.retain(|m| m.id != *id),
+                Change::Rename { id, name } => {
+                    if let Some(m) = monitors.iter_mut().find(|m| m.id == *id) {
+                        m.name.clone_from(name);
+                    }
+                }
+                Change::SetKey { id, key } => {
+                    if let Some(m) = monitors.iter_mut().find(|m| m.id == *id) {
+                        m.key.clone_from(key);
+                    }
+                }
+                Change::UpdateModes { id, modes } => {
+                    if let Some(m) = monitors.iter_mut().find(|m| m.id == *id) {
+                        m.modes.clone_from(modes);
+                    }
+                }
+                Change::SetPhysicalSize { id, physical_size } => {
+                    if let Some(m) = monitors.iter_mut().find(|m| m.id == *id) {
+                        m.physical_size = *physical_size;
+                    }
+                }
+                Change::SetOrientation { id, orientation } => {
+                    if let Some(m) = monitors.iter_mut().find(|m| m.id == *id) {
+                        m.orientation = *orientation;
+                    }
+                }
+                Change::SetLayout {
+                    id,
+                    position,
+                    primary,
+                    topology,
+                } => {
+                    if let Some(m) = monitors.iter_mut().find(|m| m.id == *id) {
+                        m.position = *position;
+                        m.primary = *primary;
+                        m.topology = *topology;
+                    }
+                }
+                Change::SetColor { id, color } => {
+                    if let Some(m) = monitors.iter_mut().find(|m| m.id == *id) {
+                        m.color = *color;
+                    }
+                }
+                Change::Enable { id } | Change::Disable { id } => {
+                    if let Some(m) = monitors.iter_mut().find(|m| m.id == *id) {
+                        m.enabled = matches!(change, Change::Enable { .. });
+                    }
+                }
+            }
+        }
+
+        monitors
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "nothing to do");
+        }
+
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{change}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BitDepth, ColorFormat, Hdr};
+
+    fn monitor(id: Id, enabled: bool, width: u32) -> Monitor {
+        Monitor {
+            id,
+            enabled,
+            modes: vec![Mode {
+                width,
+                height: 1080,
+                refresh_rates: vec![60],
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn nothing_to_do() {
+        let state = [monitor(0, true, 1920), monitor(1, false, 1920)];
+
+        let plan = Plan::new(&state, &state, Policy::RemoveUnknown);
+        assert!(plan.is_empty());
+        assert_eq!(plan.apply(&state), state);
+    }
+
+    #[test]
+    fn keeps_unknown() {
+        let current = [monitor(0, true, 1920), monitor(1, true, 1920)];
+        let desired = [monitor(1, false, 2560), monitor(2, true, 1920)];
+
+        let plan = Plan::new(&current, &desired, Policy::KeepUnknown);
+        assert_eq!(
+            plan.changes,
+            [
+                Change::Disable { id: 1 },
+                Change::UpdateModes {
+                    id: 1,
+                    modes: desired[0].modes.clone()
+                },
+                Change::Create {
+                    monitor: desired[1].clone()
+                },
+            ]
+        );
+        assert_eq!(
+            plan.apply(&current),
+            [current[0].clone(), desired[0].clone(), desired[1].clone()]
+        );
+    }
+
+    #[test]
+    fn removes_unknown() {
+        let current = [monitor(0, true, 1920), monitor(1, false, 1920)];
+        let mut desired = [monitor(1, true, 1920)];
+        desired[0].name = Some("office".to_owned());
+        desired[0].key = Some("left".to_owned());
+        desired[0].physical_size = Some(PhysicalSize::Dpi(144));
+        desired[0].orientation = Orientation::Portrait;
+        desired[0].primary = true;
+
+        let plan = Plan::new(&current, &desired, Policy::RemoveUnknown);
+        assert_eq!(
+            plan.changes,
+            [
+                Change::Remove { id: 0 },
+                Change::Rename {
+                    id: 1,
+                    name: Some("office".to_owned())
+                },
+                Change::SetKey {
+                    id: 1,
+                    key: Some("left".to_owned())
+                },
+                Change::SetPhysicalSize {
+                    id: 1,
+                    physical_size: Some(PhysicalSize::Dpi(144))
+                },
+                Change::SetOrientation {
+                    id: 1,
+                    orientation: Orientation::Portrait
+                },
+                Change::SetLayout {
+                    id: 1,
+                    position: None,
+                    primary: true,
+                    topology: Topology::Extend
+                },
+                Change::Enable { id: 1 },
+            ]
+        );
+        assert_eq!(plan.apply(&current), desired);
+    }
+
+    #[test]
+    fn display() {
+        let current = [monitor(0, true, 1920)];
+        let desired = [monitor(1, true, 2560)];
+
+        let plan = Plan::new(&current, &desired, Policy::RemoveUnknown);
+        assert_eq!(plan.to_string(), "remove monitor 0\ncreate monitor 1");
+        assert_eq!(Plan::default().to_string(), "nothing to do");
+
+        let change = Change::UpdateModes {
+            id: 0,
+            modes: vec![Mode {
+                width: 1920,
+                height: 1080,
+                refresh_rates: vec![60, 120],
+            }],
+        };
+        assert_eq!(
+            change.to_string(),
+            "update modes of monitor 0 to 1920x1080@60/120"
+        );
+
+        let change = Change::SetPhysicalSize {
+            id: 0,
+            physical_size: Some(PhysicalSize::Millimeters {
+                width: 597,
+                height: 336,
+            }),
+        };
+        assert_eq!(
+            change.to_string(),
+            "set physical size of monitor 0 to 597x336 mm"
+        );
+
+        let change = Change::SetLayout {
+            id: 0,
+            position: Some(Position { x: -1920, y: 0 }),
+            primary: true,
+            topology: Topology::Extend,
+        };
+        assert_eq!(
+            change.to_string(),
+            "set layout of monitor 0 to extend at -1920,0 as primary"
+        );
+
+        let change = Change::SetColor {
+            id: 0,
+            color: Color {
+                bit_depth: BitDepth::Ten,
+                format: ColorFormat::Rgb,
+                hdr: Some(Hdr::default()),
+            },
+        };
+        assert_eq!(
+            change.to_string(),
+            "set color of monitor 0 to 10-bit rgb HDR 0.05-1000 nits dci-p3"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! JSON Schema of the IPC protocol.
+//!
+//! Every message is a JSON document followed by an EOT byte (`0x04`), unless
+//! the client switched to a binary encoding, see [crate::codec]. Clients send
+//! [ServerCommand]s to the driver and receive [ClientCommand]s back.
+//!
+//! The schemas are published in `driver-ipc/schema`, and a test fails if they
+//! are out of date. To regenerate them after changing a message, run
+//!
+//! ```text
+//! UPDATE_SCHEMA=1 cargo test -p driver-ipc --features schema
+//! ```
+//!
+//! Changing a schema may break external clients, see the golden fixtures in
+//! `driver-ipc/fixtures` for the messages they rely on.
+
+use schemars::{schema_for, Schema};
+
+use crate::{ClientCommand, ServerCommand};
+
+/// Schema of the messages clients send to the driver.
+pub fn server_command() -> Schema {
+    schema_for!(ServerCommand)
+}
+
+/// Schema of the messages the driver sends to clients.
+pub fn client_command() -> Schema {
+    schema_for!(ClientCommand)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn check(name: &str, schema: &Schema) {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("schema")
+            .join(format!("{name}.schema.json"));
+        let json = serde_json::to_string_pretty(schema).unwrap() + "\n";
+
+        if std::env::var_os("UPDATE_SCHEMA").is_some() {
+            std::fs::write(&path, json).unwrap();
+            return;
+        }
+
+        let published = std::fs::read_to_string(&path).unwrap_or_default();
+        assert!(
+            published == json,
+            "{} is out of date, run `UPDATE_SCHEMA=1 cargo test -p driver-ipc --features schema`",
+            path.display()
+        );
+    }
+
+    #[test]
+    fn published_schemas_are_up_to_date() {
+        check("server-command", &server_command());
+        check("client-command", &client_command());
+    }
+}
@@ -0,0 +1,368 @@
+//! Selecting monitors.
+//!
+//! ## Syntax
+//!
+//! A selector is a list of terms separated by `,`. A monitor is selected if it
+//! matches every term.
+//!
+//! | Term                   | Matches                                        |
+//! |------------------------|------------------------------------------------|
+//! | `all`                  | every monitor                                  |
+//! | `id:3`                 | the monitor with ID 3                          |
+//! | `key:office-*`         | monitors whose key matches the glob            |
+//! | `name:test-*`          | monitors whose name matches the glob           |
+//! | `enabled`, `disabled`  | monitors in that state                         |
+//! | `mode:3840x2160`       | monitors with that resolution                  |
+//! | `mode:3840x2160@120`   | monitors with that resolution and refresh rate |
+//! | anything else          | see below                                      |
+//!
+//! Globs support `*` for any amount of characters and `?` for exactly one.
+//!
+//! A term without a prefix is an exact query as accepted by
+//! [DriverClient::find_id](crate::DriverClient::find_id): the monitor with that
+//! key, else the one with that name, else the one with that ID. It matches at
+//! most one monitor.
+
+use std::{fmt, str::FromStr};
+
+use crate::{Dimen, Id, Monitor, RefreshRate};
+
+/// A parsed selector, see the [module documentation](self) for the syntax.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Selector {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Term {
+    All,
+    Id(Id),
+    Key(String),
+    Name(String),
+    Enabled(bool),
+    Mode {
+        width: Dimen,
+        height: Dimen,
+        refresh_rate: Option<RefreshRate>,
+    },
+    Query(String),
+}
+
+impl Selector {
+    /// IDs of all monitors in `monitors` the selector matches, in order.
+    pub fn select(&self, monitors: &[Monitor]) -> Vec<Id> {
+        monitors
+            .iter()
+            .filter(|monitor| {
+                self.terms
+                    .iter()
+                    .all(|term| term.matches(monitors, monitor))
+            })
+            .map(|monitor| monitor.id)
+            .collect()
+    }
+}
+
+impl Term {
+    fn matches(&self, monitors: &[Monitor], monitor: &Monitor) -> bool {
+        match self {
+            Term::All => true,
+            Term::Id(id) => monitor.id == *id,
+            Term::Key(pattern) => monitor.key.as_deref().is_some_and(|key| glob(pattern, key)),
+            Term::Name(pattern) => monitor
+                .name
+                .as_deref()
+                .is_some_and(|name| glob(pattern, name)),
+            Term::Enabled(enabled) => monitor.enabled == *enabled,
+            Term::Mode {
+                width,
+                height,
+                refresh_rate,
+            } => monitor.modes.iter().any(|mode| {
+                mode.width == *width
+                    && mode.height == *height
+                    && refresh_rate.map_or(true, |rr| mode.refresh_rates.contains(&rr))
+            }),
+            Term::Query(query) => find_id(monitors, query) == Some(monitor.id),
+        }
+    }
+}
+
+/// The monitor with key `query`, else the one with name `query`, else the one
+/// with ID `query`.
+pub(crate) fn find_id(monitors: &[Monitor], query: &str) -> Option<Id> {
+    let by_key = || {
+        monitors
+            .iter()
+            .find(|monitor| monitor.key.as_deref() == Some(query))
+    };
+    let by_name = || {
+        monitors
+            .iter()
+            .find(|monitor| monitor.name.as_deref() == Some(query))
+    };
+    let by_id = || {
+        let id = query.parse::<Id>().ok()?;
+        monitors.iter().find(|monitor| monitor.id == id)
+    };
+
+    by_key()
+        .or_else(by_name)
+        .or_else(by_id)
+        .map(|monitor| monitor.id)
+}
+
+impl FromStr for Selector {
+    type Err = error::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let terms = s
+            .split(',')
+            .map(|term| term.trim().parse())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { terms })
+    }
+}
+
+impl FromStr for Term {
+    type Err = error::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let term = match s.split_once(':') {
+            _ if s.is_empty() => return Err(error::ParseError::Empty),
+            _ if s == "all" => Term::All,
+            _ if s == "enabled" => Term::Enabled(true),
+            _ if s == "disabled" => Term::Enabled(false),
+            Some(("id", id)) => Term::Id(
+                id.parse()
+                    .map_err(|_| error::ParseError::InvalidId(id.to_owned()))?,
+            ),
+            Some(("key", pattern)) => Term::Key(pattern.to_owned()),
+            Some(("name", pattern)) => Term::Name(pattern.to_owned()),
+            Some(("mode", mode)) => {
+                parse_mode(mode).ok_or_else(|| error::ParseError::InvalidMode(mode.to_owned()))?
+            }
+            _ => Term::Query(s.to_owned()),
+        };
+
+        Ok(term)
+    }
+}
+
+/// Parse `WxH` or `WxH@R`.
+fn parse_mode(s: &str) -> Option<Term> {
+    let (resolution, refresh_rate) = match s.split_once('@') {
+        Some((resolution, refresh_rate)) => (resolution, Some(refresh_rate.parse().ok()?)),
+        None => (s, None),
+    };
+    let (width, height) = resolution.split_once('x')?;
+
+    Some(Term::Mode {
+        width: width.parse().ok()?,
+        height: height.parse().ok()?,
+        refresh_rate,
+    })
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+
+            match term {
+                Term::All => write!(f, "all")?,
+                Term::Id(id) => write!(f, "id:{id}")?,
+                Term::Key(pattern) => write!(f, "key:{pattern}")?,
+                Term::Name(pattern) => write!(f, "name:{pattern}")?,
+                Term::Enabled(true) => write!(f, "enabled")?,
+                Term::Enabled(false) => write!(f, "disabled")?,
+                Term::Mode {
+                    width,
+                    height,
+                    refresh_rate,
+                } => {
+                    write!(f, "mode:{width}x{height}")?;
+                    if let Some(refresh_rate) = refresh_rate {
+                        write!(f, "@{refresh_rate}")?;
+                    }
+                }
+                Term::Query(query) => write!(f, "{query}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Match `text` against `pattern`, where `*` matches any amount of characters
+/// and `?` exactly one.
+fn glob(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+
+    let (mut p, mut t) = (0, 0);
+    // position of the last `*` and the text position it currently covers up to
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // let the last `*` cover one more character
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+pub mod error {
+    use thiserror::Error;
+
+    /// Error returned when parsing a [super::Selector].
+    #[derive(Debug, Clone, PartialEq, Eq, Error)]
+    pub enum ParseError {
+        #[error("Empty selector term")]
+        Empty,
+        #[error("Invalid monitor ID `{0}`")]
+        InvalidId(String),
+        #[error("Invalid mode `{0}`, expected WIDTHxHEIGHT or WIDTHxHEIGHT@REFRESH_RATE")]
+        InvalidMode(String),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Mode;
+
+    fn monitors() -> Vec<Monitor> {
+        let monitor = |id, name: &str, enabled, width, height| Monitor {
+            id,
+            name: Some(name.to_owned()),
+            enabled,
+            modes: vec![Mode {
+                width,
+                height,
+                refresh_rates: vec![60, 120],
+            }],
+            ..Default::default()
+        };
+
+        vec![
+            monitor(0, "test-a", true, 1920, 1080),
+            monitor(1, "test-b", false, 3840, 2160),
+            monitor(2, "office", true, 3840, 2160),
+            monitor(3, "0", true, 1280, 720),
+        ]
+    }
+
+    fn select(selector: &str) -> Vec<Id> {
+        selector.parse::<Selector>().unwrap().select(&monitors())
+    }
+
+    #[test]
+    fn terms() {
+        assert_eq!(select("all"), [0, 1, 2, 3]);
+        assert_eq!(select("id:2"), [2]);
+        assert_eq!(select("name:test-*"), [0, 1]);
+        assert_eq!(select("name:test-?"), [0, 1]);
+        assert_eq!(select("name:*"), [0, 1, 2, 3]);
+        assert_eq!(select("enabled"), [0, 2, 3]);
+        assert_eq!(select("disabled"), [1]);
+        assert_eq!(select("mode:3840x2160"), [1, 2]);
+        assert_eq!(select("mode:3840x2160@120"), [1, 2]);
+        assert_eq!(select("mode:3840x2160@144"), [] as [Id; 0]);
+        assert_eq!(select("key:*"), [] as [Id; 0]);
+    }
+
+    #[test]
+    fn combined_terms() {
+        assert_eq!(select("name:test-*,enabled"), [0]);
+        assert_eq!(select("enabled, mode:3840x2160"), [2]);
+    }
+
+    #[test]
+    fn bare_query() {
+        assert_eq!(select("office"), [2]);
+        // names take precedence over IDs, `id:` is explicit
+        assert_eq!(select("0"), [3]);
+        assert_eq!(select("id:0"), [0]);
+        assert_eq!(select("1"), [1]);
+        assert_eq!(select("missing"), [] as [Id; 0]);
+    }
+
+    #[test]
+    fn find_id_precedence() {
+        let monitor = |id, name: &str, key: Option<&str>| Monitor {
+            id,
+            name: Some(name.to_owned()),
+            key: key.map(str::to_owned),
+            enabled: true,
+            ..Default::default()
+        };
+        let monitors = [
+            monitor(0, "foo", None),
+            monitor(1, "bar", Some("foo")),
+            monitor(2, "1", None),
+        ];
+
+        assert_eq!(find_id(&monitors, "foo"), Some(1));
+        assert_eq!(find_id(&monitors, "bar"), Some(1));
+        assert_eq!(find_id(&monitors, "1"), Some(2));
+        assert_eq!(find_id(&monitors, "0"), Some(0));
+        assert_eq!(find_id(&monitors, "baz"), None);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!("".parse::<Selector>(), Err(error::ParseError::Empty));
+        assert_eq!(
+            "id:x".parse::<Selector>(),
+            Err(error::ParseError::InvalidId("x".to_owned()))
+        );
+        assert_eq!(
+            "mode:1920".parse::<Selector>(),
+            Err(error::ParseError::InvalidMode("1920".to_owned()))
+        );
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for selector in [
+            "all",
+            "id:1,enabled",
+            "name:test-*,mode:1920x1080@60",
+            "office",
+        ] {
+            assert_eq!(selector.parse::<Selector>().unwrap().to_string(), selector);
+        }
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob("", ""));
+        assert!(glob("*", ""));
+        assert!(glob("a*b*c", "aXbYYc"));
+        assert!(glob("a*c", "abcbc"));
+        assert!(!glob("a*c", "abcb"));
+        assert!(!glob("a?", "a"));
+        assert!(!glob("test", "test-a"));
+    }
+}
@@ -4,7 +4,10 @@ use tokio::sync::{mpsc, oneshot};
 use tokio_stream::StreamExt;
 
 use super::RUNTIME;
-use crate::{client::error, Client as AsyncClient, EventCommand, Id, Monitor};
+use crate::{
+    client::error, Access, Capabilities, Client as AsyncClient, DriverConfig, Encoding,
+    EventCommand, EventFilter, Id, Health, LogFilter, LogLevel, LogRecord, Monitor, Stats,
+};
 
 /// Client for interacting with the Virtual Display Driver.
 ///
@@ -37,8 +40,25 @@ impl Client {
         Ok(Self(client))
     }
 
+    /// Connect to driver on pipe with specified name, and ask it to encode
+    /// all messages with `encoding`, see [crate::codec].
+    ///
+    /// Falls back to JSON if the driver does not support `encoding`.
+    pub fn connect_with_encoding(
+        name: &str,
+        encoding: Encoding,
+    ) -> Result<Self, error::ConnectionError> {
+        let client = RUNTIME.block_on(AsyncClient::connect_with_encoding(name, encoding))?;
+        Ok(Self(client))
+    }
+
+    /// Encoding of all messages on this connection.
+    pub fn encoding(&self) -> Encoding {
+        self.0.encoding()
+    }
+
     /// Send new state to the driver.
-    pub fn notify(&self, monitors: &[Monitor]) -> Result<(), error::SendError> {
+    pub fn notify(&self, monitors: &[Monitor]) -> Result<(), error::RequestError> {
         RUNTIME.block_on(self.0.notify(monitors))
     }
 
@@ -84,6 +104,21 @@ impl Client {
         EventsSubscription::start_subscriber(cb, stream)
     }
 
+    /// Only receive the events matching `filter` from the driver, and pass
+    /// them to `cb`, see [Client::add_event_receiver].
+    ///
+    /// The driver leaves all other events out, including for the other
+    /// receivers. The filter is shared between all copies of this client, and
+    /// replaced by the next call.
+    pub fn subscribe(
+        &self,
+        filter: EventFilter,
+        cb: impl FnMut(Result<EventCommand, error::ReceiveError>) + Send + panic::UnwindSafe + 'static,
+    ) -> Result<EventsSubscription, error::SendError> {
+        let stream = RUNTIME.block_on(self.0.subscribe(filter))?;
+        Ok(EventsSubscription::start_subscriber(cb, stream))
+    }
+
     /// Request the current state of the driver.
     ///
     /// Returns [IpcError::Timeout] if the driver does not respond within 5
@@ -92,13 +127,96 @@ impl Client {
         RUNTIME.block_on(self.0.request_state())
     }
 
+    /// Request the capabilities of the virtual display adapter.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_capabilities(&self) -> Result<Capabilities, error::RequestError> {
+        RUNTIME.block_on(self.0.request_capabilities())
+    }
+
+    /// Request the effective configuration of the driver.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_config(&self) -> Result<DriverConfig, error::RequestError> {
+        RUNTIME.block_on(self.0.request_config())
+    }
+
+    /// Request the frame and swap chain counters of the driver.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_stats(&self) -> Result<Stats, error::RequestError> {
+        RUNTIME.block_on(self.0.request_stats())
+    }
+
+    /// Request build and liveness information of the driver. Use it to check
+    /// that the driver is up and responding.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn health(&self) -> Result<Health, error::RequestError> {
+        RUNTIME.block_on(self.0.health())
+    }
+
+    /// Request the access this client has to the driver.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn access(&self) -> Result<Access, error::RequestError> {
+        RUNTIME.block_on(self.0.access())
+    }
+
+    /// Request the recent records of the driver log, oldest first.
+    ///
+    /// Only records at least as severe as `level` and newer than the record
+    /// with sequence number `since` are returned. The driver keeps a limited
+    /// amount of records, older ones are lost.
+    ///
+    /// Returns [error::RequestError::Timeout] if the driver does not respond within 5
+    /// seconds.
+    pub fn request_logs(
+        &self,
+        since: Option<u64>,
+        level: Option<LogLevel>,
+    ) -> Result<Vec<LogRecord>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_logs(since, level))
+    }
+
+    /// Stream new records of the driver log which are at least as severe as
+    /// `level`, or stop streaming them with `None`.
+    ///
+    /// The records arrive as [EventCommand::Log] in [Client::add_event_receiver].
+    pub fn subscribe_logs(&self, level: Option<LogLevel>) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.subscribe_logs(level))
+    }
+
+    /// Change which records the driver logs, both to its loggers and to
+    /// [Client::request_logs].
+    ///
+    /// The filter lasts until the driver restarts, unless
+    /// [DriverConfig::persist_log_filter] is set.
+    pub fn set_log_filter(&self, filter: LogFilter) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_log_filter(filter))
+    }
+
     /// Write `monitors` to the registry for current user.
     ///
-    /// Next time the driver is started, it will load this state from the
-    /// registry. This might be after a reboot or a driver restart.
+    /// Next time the user logs on, the user session service will load this
+    /// state from the registry.
     pub fn persist(monitors: &[Monitor]) -> Result<(), error::PersistError> {
         AsyncClient::persist(monitors)
     }
+
+    /// Write `monitors` to the registry for the whole machine. Requires admin
+    /// rights.
+    ///
+    /// Next time the driver is started, it will load this state from the
+    /// registry. This might be after a reboot or a driver restart.
+    pub fn persist_machine(monitors: &[Monitor]) -> Result<(), error::PersistError> {
+        AsyncClient::persist_machine(monitors)
+    }
 }
 
 pub struct EventsSubscription {
@@ -244,6 +362,14 @@ mod test {
     use super::*;
     use crate::mock::*;
 
+    /// Notify an empty state, while the server answers on another thread.
+    fn notify(client: &Client, server: &mut MockServer) {
+        thread::scope(|s| {
+            s.spawn(|| RUNTIME.block_on(server.pump()));
+            client.notify(&[]).unwrap();
+        });
+    }
+
     #[test]
     fn event_receiver_not_canceled_after_drop() {
         const PIPE_NAME: &str = "virtualdisplaydriver-sync-event_receiver_not_canceled_after_drop";
@@ -263,8 +389,7 @@ mod test {
 
         drop(sub);
 
-        client.notify(&[]).unwrap();
-        RUNTIME.block_on(server.pump());
+        notify(&client, &mut server);
 
         // Give time for the callback to be run
         sleep(std::time::Duration::from_millis(100));
@@ -287,8 +412,7 @@ mod test {
             panic!("Panic2 in callback");
         });
 
-        client.notify(&[]).unwrap();
-        RUNTIME.block_on(server.pump());
+        notify(&client, &mut server);
 
         // Give time for the callback to be run
         sleep(std::time::Duration::from_millis(100));
@@ -319,16 +443,14 @@ mod test {
             }
         });
 
-        client.notify(&[]).unwrap();
-        RUNTIME.block_on(server.pump());
+        notify(&client, &mut server);
         sleep(std::time::Duration::from_millis(100));
 
         assert!(sub.cancel().expect("Callback should not panic"));
         assert!(!sub.cancel().expect("Callback should not panic"));
         assert!(!sub.cancel().expect("Callback should not panic"));
 
-        client.notify(&[]).unwrap();
-        RUNTIME.block_on(server.pump());
+        notify(&client, &mut server);
         sleep(std::time::Duration::from_millis(100));
 
         assert!(matches!(
@@ -372,8 +494,7 @@ mod test {
 
         *shared_sub.lock().unwrap() = Some(sub);
 
-        client.notify(&[]).unwrap();
-        RUNTIME.block_on(server.pump());
+        notify(&client, &mut server);
         sleep(std::time::Duration::from_millis(100));
 
         assert!(
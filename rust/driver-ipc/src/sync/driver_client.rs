@@ -1,6 +1,11 @@
 use super::{client::EventsSubscription, RUNTIME};
 use crate::{
-    driver_client::error, DriverClient as AsyncDriverClient, EventCommand, Id, Mode, Monitor,
+    driver_client::error,
+    persist::Store,
+    reconcile::{Plan, Policy},
+    selector::Selector,
+    Access, DriverClient as AsyncDriverClient, EventCommand, Health, Id, LogFilter, LogLevel,
+    LogRecord, Mode, Monitor, Stats,
 };
 
 /// Abstraction layer over [Client].
@@ -35,18 +40,20 @@ impl DriverClient {
     ///
     /// ## Query syntax
     ///
-    /// The query can either be a monitor name or an ID.
+    /// The query can either be a monitor key, a monitor name or an ID.
     ///
-    /// The name has precedence over the ID.
+    /// The key has precedence over the name, which has precedence over the ID.
+    /// Keys are unique and stable, so they are the most reliable way to
+    /// address a monitor.
     ///
     /// ### Example
     /// ```ignore
     /// // Monitors are:
-    /// //   { id: 0, name: Some("foo") }
-    /// //   { id: 1, name: Some("bar") }
-    /// //   { id: 2, name: Some("1") }
+    /// //   { id: 0, name: Some("foo"), key: None }
+    /// //   { id: 1, name: Some("bar"), key: Some("foo") }
+    /// //   { id: 2, name: Some("1"), key: None }
     ///
-    /// assert_eq!(client.find_id("foo"), Some(0));
+    /// assert_eq!(client.find_id("foo"), Some(1));
     /// assert_eq!(client.find_id("bar"), Some(1));
     /// assert_eq!(client.find_id("1"), Some(2));
     /// assert_eq!(client.find_id("0"), Some(0));
@@ -56,6 +63,16 @@ impl DriverClient {
         self.0.find_id(query)
     }
 
+    /// Get the IDs of all monitors matching a selector, in order.
+    ///
+    /// See [crate::selector] for the syntax.
+    ///
+    /// Note: Client state might be stale. To synchronize with the driver,
+    /// manually call [DriverClient::refresh_state].
+    pub fn select(&self, selector: &Selector) -> Vec<Id> {
+        self.0.select(selector)
+    }
+
     /// Manually synchronize with the driver.
     pub fn refresh_state(&mut self) -> &[Monitor] {
         self.0.refresh_state()
@@ -121,10 +138,86 @@ impl DriverClient {
     ///
     /// State changes of the client are not automatically sent to the driver.
     /// You must manually call this method to send changes to the driver.
-    pub fn notify(&mut self) -> Result<(), error::SendError> {
+    pub fn notify(&mut self) -> Result<(), error::RequestError> {
         RUNTIME.block_on(self.0.notify())
     }
 
+    /// Request the frame and swap chain counters of the driver.
+    pub fn request_stats(&self) -> Result<Stats, error::RequestError> {
+        RUNTIME.block_on(self.0.request_stats())
+    }
+
+    /// Request build and liveness information of the driver.
+    pub fn health(&self) -> Result<Health, error::RequestError> {
+        RUNTIME.block_on(self.0.health())
+    }
+
+    /// Request the access this client has to the driver.
+    pub fn access(&self) -> Result<Access, error::RequestError> {
+        RUNTIME.block_on(self.0.access())
+    }
+
+    /// Request the recent records of the driver log, oldest first.
+    ///
+    /// Only records at least as severe as `level` and newer than the record
+    /// with sequence number `since` are returned.
+    pub fn request_logs(
+        &self,
+        since: Option<u64>,
+        level: Option<LogLevel>,
+    ) -> Result<Vec<LogRecord>, error::RequestError> {
+        RUNTIME.block_on(self.0.request_logs(since, level))
+    }
+
+    /// Stream new records of the driver log which are at least as severe as
+    /// `level`, or stop streaming them with `None`.
+    ///
+    /// The records arrive as [EventCommand::Log] in
+    /// [DriverClient::add_event_receiver].
+    pub fn subscribe_logs(&self, level: Option<LogLevel>) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.subscribe_logs(level))
+    }
+
+    /// Change which records the driver logs, both to its loggers and to
+    /// [DriverClient::request_logs].
+    ///
+    /// The filter lasts until the driver restarts, unless
+    /// `persist_log_filter` is configured.
+    pub fn set_log_filter(&self, filter: LogFilter) -> Result<(), error::SendError> {
+        RUNTIME.block_on(self.0.set_log_filter(filter))
+    }
+
+    /// Compute the changes [DriverClient::reconcile] would make, without
+    /// making them.
+    ///
+    /// Note: This requests the state of the driver and replaces the client
+    /// state with it first.
+    pub fn plan(
+        &mut self,
+        desired: &[Monitor],
+        policy: Policy,
+    ) -> Result<Plan, error::ReconcileError> {
+        RUNTIME.block_on(self.0.plan(desired, policy))
+    }
+
+    /// Bring the driver to the `desired` state, changing only what differs.
+    ///
+    /// Unlike [DriverClient::set_monitors] followed by [DriverClient::notify],
+    /// monitors which are already in their desired state are not touched, and
+    /// `policy` decides whether monitors missing from `desired` are removed.
+    ///
+    /// Returns the applied plan. Nothing is sent if it is empty.
+    ///
+    /// Note: This requests the state of the driver and replaces the client
+    /// state with it first.
+    pub fn reconcile(
+        &mut self,
+        desired: &[Monitor],
+        policy: Policy,
+    ) -> Result<Plan, error::ReconcileError> {
+        RUNTIME.block_on(self.0.reconcile(desired, policy))
+    }
+
     /// Find the monitor with the given ID.
     ///
     /// Note: Client state might be stale. To synchronize with the driver,
@@ -202,13 +295,63 @@ impl DriverClient {
 
     /// Write client state to the registry for current user.
     ///
-    /// Next time the driver is started, it will load this state from the
-    /// registry. This might be after a reboot or a driver restart.
+    /// Next time the user logs on, the user session service will load this
+    /// state from the registry.
     pub fn persist(&self) -> Result<(), error::PersistError> {
         self.0.persist()
     }
 
-    /// Get the closest available free ID.
+    /// Write client state to the registry for the whole machine. Requires
+    /// admin rights.
+    ///
+    /// Next time the driver is started, it will load this state from the
+    /// registry. This might be after a reboot or a driver restart.
+    pub fn persist_machine(&self) -> Result<(), error::PersistError> {
+        self.0.persist_machine()
+    }
+
+    /// Use `store` for profiles instead of the registry of the current user.
+    pub fn set_profile_store(&mut self, store: impl Store + 'static) {
+        self.0.set_profile_store(store);
+    }
+
+    /// Save client state as profile `name`, replacing any existing profile
+    /// with that name.
+    pub fn save_profile(&self, name: &str) -> Result<(), error::PersistError> {
+        self.0.save_profile(name)
+    }
+
+    /// Replace client state with profile `name` and send it to the driver.
+    pub fn load_profile(&mut self, name: &str) -> Result<(), error::ProfileError> {
+        RUNTIME.block_on(self.0.load_profile(name))
+    }
+
+    /// Names of all saved profiles, sorted.
+    pub fn list_profiles(&self) -> Result<Vec<String>, error::PersistError> {
+        self.0.list_profiles()
+    }
+
+    /// Delete profile `name`.
+    ///
+    /// Returns `false` if the profile did not exist.
+    pub fn delete_profile(&self, name: &str) -> Result<bool, error::PersistError> {
+        self.0.delete_profile(name)
+    }
+
+    /// Make profile `name` the default profile, or remove the default profile
+    /// if `name` is `None`.
+    ///
+    /// The default profile is applied by the user session service when the
+    /// user logs on. It is a copy, later changes to profile `name` do not
+    /// change the default profile.
+    pub fn set_default_profile(&self, name: Option<&str>) -> Result<(), error::ProfileError> {
+        self.0.set_default_profile(name)
+    }
+
+    /// Get an available ID.
+    ///
+    /// Returns `preferred_id` if it is free, or `None` if it is taken. Without
+    /// a preferred ID, returns the lowest free ID.
     ///
     /// Note: Client state might be stale. To synchronize with the driver,
     /// manually call [DriverClient::refresh_state].
@@ -5,7 +5,7 @@ mod win_logger;
 
 use std::error::Error;
 
-use log::{Level, Log};
+use log::{Level, Log, Metadata};
 
 use crate::win_debug::WinDebugLogger;
 use crate::win_logger::WinLogger;
@@ -15,6 +15,8 @@ pub struct DriverLogger {
     pub level: Level,
     win_debug: Option<WinDebugLogger>,
     win_logger: Option<WinLogger>,
+    sinks: Vec<Box<dyn Log>>,
+    filter: Option<fn(&Metadata) -> bool>,
 }
 
 impl DriverLogger {
@@ -24,6 +26,8 @@ impl DriverLogger {
             level,
             win_logger: None,
             win_debug: None,
+            sinks: Vec::new(),
+            filter: None,
         }
     }
 
@@ -37,6 +41,20 @@ impl DriverLogger {
         Ok(self)
     }
 
+    /// Also log to `logger`, e.g. to keep records in memory
+    pub fn sink(&mut self, logger: impl Log + 'static) -> &mut Self {
+        self.sinks.push(Box::new(logger));
+        self
+    }
+
+    /// Decide which records are logged with `filter`, which may change its
+    /// mind at runtime. Records above `level` are still dropped, so pass
+    /// `Level::Trace` to leave the decision to `filter` alone.
+    pub fn filter(&mut self, filter: fn(&Metadata) -> bool) -> &mut Self {
+        self.filter = Some(filter);
+        self
+    }
+
     pub fn init(self) -> Result<(), Box<dyn Error>> {
         let level = self.level;
 
@@ -52,7 +70,7 @@ impl DriverLogger {
 
 impl Log for DriverLogger {
     fn enabled(&self, metadata: &log::Metadata) -> bool {
-        metadata.level() <= self.level
+        metadata.level() <= self.level && self.filter.map_or(true, |filter| filter(metadata))
     }
 
     fn log(&self, record: &log::Record) {
@@ -67,6 +85,10 @@ impl Log for DriverLogger {
         if let Some(logger) = self.win_logger.as_ref() {
             logger.log(record);
         }
+
+        for sink in &self.sinks {
+            sink.log(record);
+        }
     }
 
     fn flush(&self) {
@@ -77,5 +99,9 @@ impl Log for DriverLogger {
         if let Some(logger) = self.win_logger.as_ref() {
             logger.flush();
         }
+
+        for sink in &self.sinks {
+            sink.flush();
+        }
     }
 }
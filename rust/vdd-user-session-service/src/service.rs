@@ -1,9 +1,12 @@
 use std::{ffi::OsString, io::ErrorKind, sync::mpsc, time::Duration};
 
 use driver_ipc::{
+    error::ProfileError,
+    persist::DEFAULT_ENTRY,
     sync::{Client, DriverClient},
-    Monitor,
 };
+use driver_logger::DriverLogger;
+use log::{error, Level};
 use windows::Win32::{
     Foundation::{CloseHandle, HANDLE},
     Security::{ImpersonateLoggedOnUser, SE_TCB_NAME},
@@ -18,10 +21,6 @@ use windows_service::{
     service_control_handler::{self, ServiceControlHandlerResult},
     service_dispatcher,
 };
-use winreg::{
-    enums::{HKEY_CURRENT_USER, KEY_READ},
-    RegKey,
-};
 
 use crate::{set_privileges::set_privilege, SERVICE_NAME, SERVICE_TYPE};
 
@@ -34,6 +33,12 @@ pub fn start_service() -> windows_service::Result<()> {
 
 #[allow(clippy::needless_pass_by_value)]
 fn service_main(arguments: Vec<OsString>) {
+    let mut logger = DriverLogger::new(Level::Info);
+    // without an event source, errors are only lost, so keep running regardless
+    if logger.name(SERVICE_NAME).is_ok() {
+        _ = logger.init();
+    }
+
     if let Err(_e) = run_service(&arguments) {
         // error handling
     }
@@ -142,28 +147,22 @@ fn get_current_session() -> Result<u32, ()> {
 
 fn notify(session_id: u32) -> Result<(), ServiceControlHandlerResult> {
     impersonate_user(session_id, || {
-        let hklm = RegKey::predef(HKEY_CURRENT_USER);
-        let key = r"SOFTWARE\VirtualDisplayDriver";
-
-        let Ok(driver_settings) = hklm.open_subkey_with_flags(key, KEY_READ) else {
-            return Err(ServiceControlHandlerResult::NoError);
-        };
-
-        let monitors = driver_settings
-            .get_value::<String, _>("data")
-            .map(|data| serde_json::from_str::<Vec<Monitor>>(&data).unwrap_or_default())
-            .unwrap_or_default();
-
         let Ok(mut client) = DriverClient::new() else {
             return Err(ServiceControlHandlerResult::NoError);
         };
 
-        if client.set_monitors(&monitors).is_err() {
-            return Err(ServiceControlHandlerResult::NoError);
+        // the user's default profile, stored in their registry
+        match client.load_profile(DEFAULT_ENTRY) {
+            Ok(()) => (),
+            Err(ProfileError::NotFound(_)) => return Err(ServiceControlHandlerResult::NoError),
+            Err(e) => {
+                // never replace the user's monitors with an empty list because their
+                // persisted state could not be read
+                error!("Failed to apply default profile of session {session_id}: {e}");
+                return Err(ServiceControlHandlerResult::NoError);
+            }
         }
 
-        _ = client.notify();
-
         Ok(())
     })
 }
@@ -7,7 +7,11 @@ use lazy_format::lazy_format;
 use owo_colors::OwoColorize;
 use serde::{Deserialize, Serialize};
 
-use driver_ipc::{sync::DriverClient, Id, Monitor};
+use driver_ipc::{
+    layout::Layout, reconcile::Policy, selector::Selector, sync::DriverClient, BitDepth, Color,
+    ColorFormat, ColorPrimaries, EventCommand, Hdr, Id, LogFilter, LogLevel, LogRecord, Monitor,
+    Orientation, PhysicalSize, Position, Topology,
+};
 
 #[derive(Debug, Parser)]
 struct Args {
@@ -43,14 +47,136 @@ enum Command {
     Remove(RemoveCommand),
     /// Remove all virtual monitors.
     RemoveAll,
+    /// Set the physical size of virtual monitors, which Windows derives the
+    /// DPI scaling from.
+    SetSize(SetSizeCommand),
+    /// Set the orientation of virtual monitors.
+    SetOrientation(SetOrientationCommand),
+    /// Set the bit depth, color format and HDR support that virtual monitors
+    /// report to the OS. Only their EDID changes, frames are still rendered
+    /// in 8-bit SDR.
+    SetColor(SetColorCommand),
+    /// Set where virtual monitors go on the desktop.
+    Place(PlaceCommand),
+    /// Show where virtual monitors go on the desktop, as planned from their
+    /// position, primary and duplicate settings.
+    Layout,
+    /// Show frame and swap chain counters of the virtual monitors and the
+    /// active recording.
+    Stats,
+    /// Check that the driver is up, and show its version and uptime.
+    Status,
+    /// Show recent records of the driver log.
+    Logs(LogsCommand),
+    /// Change which records the driver logs, until it restarts.
+    SetLogLevel(SetLogLevelCommand),
     /// Persist changes to current user
-    Persist,
+    Persist(PersistCommand),
+    /// List resolution presets which can be used in place of `WIDTHxHEIGHT`.
+    Presets,
+    /// Change virtual monitors to match a file, as written by `list --json`.
+    Apply(ApplyCommand),
+    /// Save, load and manage named monitor profiles.
+    #[clap(subcommand)]
+    Profile(ProfileCommand),
+}
+
+#[derive(Debug, Parser)]
+struct LogsCommand {
+    /// Only show records at least as severe as this, `error`, `warn`,
+    /// `info`, `debug` or `trace`.
+    #[clap(long)]
+    level: Option<LogLevel>,
+
+    /// Only show records after the one with this sequence number.
+    #[clap(long)]
+    since: Option<u64>,
+
+    /// Keep showing new records as the driver logs them.
+    #[clap(short, long)]
+    follow: bool,
+}
+
+#[derive(Debug, Parser)]
+struct SetLogLevelCommand {
+    /// Least severe level logged, `error`, `warn`, `info`, `debug` or
+    /// `trace`.
+    level: LogLevel,
+
+    /// Level of a module and its submodules, e.g.
+    /// `virtual_display_driver::ipc=trace` or `trace_log=warn`. May be
+    /// repeated.
+    #[clap(long = "target", value_parser = parse_target)]
+    targets: Vec<(String, LogLevel)>,
+}
+
+fn parse_target(s: &str) -> eyre::Result<(String, LogLevel)> {
+    let (target, level) = s
+        .split_once('=')
+        .ok_or_else(|| eyre!("invalid target {s:?}, expected a string like \"trace_log=warn\""))?;
+    let level = level.parse().map_err(|e: String| eyre!(e))?;
+
+    Ok((target.to_owned(), level))
+}
+
+#[derive(Debug, Parser)]
+struct ApplyCommand {
+    /// JSON file with the desired virtual monitors.
+    file: std::path::PathBuf,
+
+    /// Remove virtual monitors which are not in the file.
+    #[clap(long)]
+    prune: bool,
+
+    /// Only show the changes, don't make them.
+    #[clap(long)]
+    dry_run: bool,
+}
+
+#[derive(Debug, Parser)]
+enum ProfileCommand {
+    /// Save the current virtual monitors as a profile.
+    Save(ProfileNameCommand),
+    /// Replace the current virtual monitors with a profile.
+    Load(ProfileNameCommand),
+    /// List saved profiles.
+    List,
+    /// Delete a profile.
+    Delete(ProfileNameCommand),
+    /// Set the profile applied when the current user logs on.
+    Default(ProfileDefaultCommand),
+}
+
+#[derive(Debug, Parser)]
+struct ProfileNameCommand {
+    /// Name of the profile.
+    name: String,
+}
+
+#[derive(Debug, Parser)]
+struct ProfileDefaultCommand {
+    /// Name of the profile to apply on logon.
+    #[clap(required_unless_present = "clear")]
+    name: Option<String>,
+
+    /// Remove the default profile instead.
+    #[clap(long, conflicts_with = "name")]
+    clear: bool,
+}
+
+#[derive(Debug, Parser)]
+struct PersistCommand {
+    /// Persist for the whole machine instead, so the driver restores the
+    /// monitors when it starts. Requires admin rights.
+    #[clap(long)]
+    machine: bool,
 }
 
 #[derive(Debug, Parser)]
 struct AddCommand {
     /// One or more resolutions/refresh rates to add to the virtual monitor.
-    /// Example values: `1920x1080`, `3840x2160@120`, `1280x720@60/120`.
+    /// Example values: `1920x1080`, `3840x2160@120`, `1280x720@60/120`,
+    /// `4k@144`. Run `presets` for all preset names.
     mode: Vec<mode::Mode>,
 
     /// Manual ID to set for the monitor. Must not conflict with an
@@ -62,25 +188,167 @@ struct AddCommand {
     #[clap(long)]
     name: Option<String>,
 
+    /// Optional stable key for the virtual monitor, e.g. a UUID. Must not
+    /// conflict with an existing virtual monitor's key.
+    #[clap(long)]
+    key: Option<String>,
+
     /// Set the virtual monitor to disabled on creation.
     #[clap(long)]
     disabled: bool,
+
+    #[clap(flatten)]
+    size: SizeOptions,
+
+    /// Orientation of the desktop, either in degrees or `landscape`,
+    /// `portrait`, `landscape-flipped` or `portrait-flipped`. Modes are given
+    /// as seen on the desktop, e.g. `1080x1920` for `portrait`.
+    #[clap(long, default_value_t)]
+    orientation: Orientation,
+
+    #[clap(flatten)]
+    layout: LayoutOptions,
+
+    #[clap(flatten)]
+    color: ColorOptions,
+}
+
+#[derive(Debug, Parser)]
+struct ColorOptions {
+    /// Bits per color component, either 6, 8, 10, 12, 14 or 16.
+    #[clap(long, default_value_t)]
+    bit_depth: BitDepth,
+
+    /// Color encodings supported besides RGB, either `rgb`, `ycbcr444` or
+    /// `ycbcr422`.
+    #[clap(long, default_value_t)]
+    color_format: ColorFormat,
+
+    /// Report HDR10 support.
+    #[clap(long)]
+    hdr: bool,
+
+    /// Peak luminance in nits for HDR, e.g. `1000`.
+    #[clap(long, requires = "hdr")]
+    max_luminance: Option<f32>,
+
+    /// Black level in nits for HDR, e.g. `0.05`.
+    #[clap(long, requires = "hdr")]
+    min_luminance: Option<f32>,
+
+    /// Gamut for HDR, either `bt709`, `dci-p3` or `bt2020`.
+    #[clap(long, requires = "hdr")]
+    primaries: Option<ColorPrimaries>,
+}
+
+impl ColorOptions {
+    fn color(&self) -> Color {
+        let hdr = self.hdr.then(|| {
+            let default = Hdr::default();
+            Hdr {
+                max_luminance: self.max_luminance.unwrap_or(default.max_luminance),
+                min_luminance: self.min_luminance.unwrap_or(default.min_luminance),
+                primaries: self.primaries.unwrap_or(default.primaries),
+            }
+        });
+
+        Color {
+            bit_depth: self.bit_depth,
+            format: self.color_format,
+            hdr,
+        }
+    }
+}
+
+#[derive(Debug, Parser)]
+struct LayoutOptions {
+    /// Position of the top left corner on the desktop, e.g. `1920,0`. The
+    /// primary display is at `0,0`. Without a position, the virtual monitor is
+    /// placed to the right of the others.
+    #[clap(long, value_parser = parse_position, allow_hyphen_values = true)]
+    position: Option<Position>,
+
+    /// Make the virtual monitor the primary display.
+    #[clap(long, conflicts_with = "duplicate")]
+    primary: bool,
+
+    /// Show the same as the primary display instead of extending the desktop.
+    #[clap(long)]
+    duplicate: bool,
+}
+
+impl LayoutOptions {
+    fn topology(&self) -> Topology {
+        if self.duplicate {
+            Topology::Duplicate
+        } else {
+            Topology::Extend
+        }
+    }
+}
+
+fn parse_position(s: &str) -> eyre::Result<Position> {
+    let (x, y) = s
+        .split_once(',')
+        .ok_or_else(|| eyre!("invalid position {s:?}, expected a string like \"1920,0\""))?;
+    let x = x
+        .parse()
+        .with_context(|| format!("invalid x in {s:?}, expected a number"))?;
+    let y = y
+        .parse()
+        .with_context(|| format!("invalid y in {s:?}, expected a number"))?;
+
+    Ok(Position { x, y })
+}
+
+#[derive(Debug, Parser)]
+struct SizeOptions {
+    /// Physical size in millimeters, e.g. `597x336`.
+    #[clap(long, value_parser = parse_size, conflicts_with = "dpi")]
+    size: Option<PhysicalSize>,
+
+    /// Physical size as dots per inch of the first mode, e.g. `163`.
+    #[clap(long)]
+    dpi: Option<u32>,
+}
+
+impl SizeOptions {
+    fn physical_size(&self) -> Option<PhysicalSize> {
+        self.size.or(self.dpi.map(PhysicalSize::Dpi))
+    }
+}
+
+fn parse_size(s: &str) -> eyre::Result<PhysicalSize> {
+    let (width, height) = s
+        .split_once('x')
+        .ok_or_else(|| eyre!("invalid size {s:?}, expected a string like \"597x336\""))?;
+    let width = width
+        .parse()
+        .with_context(|| format!("invalid width in {s:?}, expected a number"))?;
+    let height = height
+        .parse()
+        .with_context(|| format!("invalid height in {s:?}, expected a number"))?;
+
+    Ok(PhysicalSize::Millimeters { width, height })
 }
 
 #[derive(Debug, Parser)]
 struct AddModeCommand {
-    /// ID or name of the virtual monitor to add a mode to.
-    id: String,
+    /// Virtual monitors to add the modes to. Either an ID, key or name, or a
+    /// selector like `name:test-*`, `enabled` or `mode:1920x1080`.
+    selector: Selector,
 
     /// One or more resolutions/refresh rates to add to the virtual monitor.
-    /// Example values: `1920x1080`, `3840x2160@120`, `1280x720@60/120`.
+    /// Example values: `1920x1080`, `3840x2160@120`, `1280x720@60/120`,
+    /// `4k@144`. Run `presets` for all preset names.
     mode: Vec<mode::Mode>,
 }
 
 #[derive(Debug, Parser)]
 struct RemoveModeCommand {
-    /// ID or name of the virtual monitor to add a mode to.
-    id: String,
+    /// Virtual monitors to remove the mode from. Either an ID, key or name, or
+    /// a selector like `name:test-*`, `enabled` or `mode:1920x1080`.
+    selector: Selector,
 
     /// A resolution and optional refresh rate to remove from the virtual
     /// monitor. Omitting the refresh rate will remove the resolution, including
@@ -91,20 +359,67 @@ struct RemoveModeCommand {
 
 #[derive(Debug, Parser)]
 struct EnableCommand {
-    // The ID or name of the monitor to enable.
-    id: String,
+    // Virtual monitors to enable. Either an ID, key or name, or a selector like
+    // `name:test-*`, `disabled` or `mode:1920x1080`.
+    selector: Vec<Selector>,
 }
 
 #[derive(Debug, Parser)]
 struct DisableCommand {
-    // The ID or name of the monitor to disable.
-    id: String,
+    // Virtual monitors to disable. Either an ID, key or name, or a selector
+    // like `name:test-*`, `enabled` or `mode:1920x1080`.
+    selector: Vec<Selector>,
+}
+
+#[derive(Debug, Parser)]
+struct SetSizeCommand {
+    /// Virtual monitors to change. Either an ID, key or name, or a selector
+    /// like `name:test-*`, `enabled` or `mode:1920x1080`.
+    selector: Selector,
+
+    /// Without `--size` or `--dpi`, the physical size is removed.
+    #[clap(flatten)]
+    size: SizeOptions,
+}
+
+#[derive(Debug, Parser)]
+struct SetOrientationCommand {
+    /// Virtual monitors to change. Either an ID, key or name, or a selector
+    /// like `name:test-*`, `enabled` or `mode:1920x1080`.
+    selector: Selector,
+
+    /// Orientation of the desktop, either in degrees or `landscape`,
+    /// `portrait`, `landscape-flipped` or `portrait-flipped`. Windows has to
+    /// use the same display orientation.
+    orientation: Orientation,
+}
+
+#[derive(Debug, Parser)]
+struct SetColorCommand {
+    /// Virtual monitors to change. Either an ID, key or name, or a selector
+    /// like `name:test-*`, `enabled` or `mode:1920x1080`.
+    selector: Selector,
+
+    /// Without options, the monitors are set to 8-bit RGB without HDR.
+    #[clap(flatten)]
+    color: ColorOptions,
+}
+
+#[derive(Debug, Parser)]
+struct PlaceCommand {
+    /// Virtual monitors to change. Either an ID, key or name, or a selector
+    /// like `name:test-*`, `enabled` or `mode:1920x1080`.
+    selector: Selector,
+
+    #[clap(flatten)]
+    layout: LayoutOptions,
 }
 
 #[derive(Debug, Parser)]
 struct RemoveCommand {
-    // One or more monitor IDs or names to remove.
-    id: Vec<String>,
+    // Virtual monitors to remove. Either an ID, key or name, or a selector like
+    // `name:test-*`, `disabled` or `all`.
+    selector: Vec<Selector>,
 }
 
 fn main() -> eyre::Result<()> {
@@ -119,7 +434,7 @@ fn main() -> eyre::Result<()> {
             add(&mut client, &options, command)?;
         }
         Command::AddMode(command) => {
-            add_mode(&mut client, &options, command)?;
+            add_mode(&mut client, &options, &command)?;
         }
         Command::RemoveMode(command) => {
             remove_mode(&mut client, &options, &command)?;
@@ -136,16 +451,207 @@ fn main() -> eyre::Result<()> {
         Command::RemoveAll => {
             remove_all(&mut client, &options)?;
         }
-        Command::Persist => {
-            persist(&mut client)?;
+        Command::SetSize(command) => {
+            set_size(&mut client, &options, &command)?;
+        }
+        Command::SetOrientation(command) => {
+            set_orientation(&mut client, &options, &command)?;
+        }
+        Command::SetColor(command) => {
+            set_color(&mut client, &options, &command)?;
+        }
+        Command::Place(command) => {
+            place(&mut client, &options, &command)?;
+        }
+        Command::Layout => {
+            layout(&client, &options)?;
+        }
+        Command::Stats => {
+            stats(&client, &options)?;
+        }
+        Command::Status => {
+            status(&client, &options)?;
+        }
+        Command::Logs(command) => {
+            logs(&client, &options, &command)?;
+        }
+        Command::SetLogLevel(command) => {
+            set_log_level(&client, &options, &command)?;
+        }
+        Command::Persist(command) => {
+            persist(&mut client, &command)?;
+        }
+        Command::Presets => {
+            presets(&options)?;
+        }
+        Command::Apply(command) => {
+            apply(&mut client, &options, &command)?;
+        }
+        Command::Profile(command) => {
+            profile(&mut client, &options, &command)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn persist(client: &mut DriverClient, command: &PersistCommand) -> eyre::Result<()> {
+    if command.machine {
+        client.persist_machine()?;
+    } else {
+        client.persist()?;
+    }
+
+    Ok(())
+}
+
+fn presets(opts: &GlobalOptions) -> eyre::Result<()> {
+    let presets = driver_ipc::preset::PRESETS
+        .iter()
+        .map(|preset| PresetOutput {
+            name: preset.name,
+            aliases: preset.aliases,
+            mode: preset.mode(),
+        })
+        .collect::<Vec<_>>();
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &presets)?;
+    } else {
+        println!("{}", "Presets".underline());
+        for preset in &presets {
+            let aliases = if preset.aliases.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", preset.aliases.join(", "))
+            };
+            println!(
+                "{} {}{}: {}",
+                "-".dimmed(),
+                preset.name.green(),
+                aliases.dimmed(),
+                mode::Mode::from(preset.mode.clone()).blue(),
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn apply(
+    client: &mut DriverClient,
+    opts: &GlobalOptions,
+    command: &ApplyCommand,
+) -> eyre::Result<()> {
+    let file = std::fs::File::open(&command.file)
+        .with_context(|| format!("Failed to open {}", command.file.display()))?;
+    let desired: Vec<Monitor> = serde_json::from_reader(std::io::BufReader::new(file))
+        .with_context(|| format!("Failed to parse {}", command.file.display()))?;
+
+    let policy = if command.prune {
+        Policy::RemoveUnknown
+    } else {
+        Policy::KeepUnknown
+    };
+
+    let plan = if command.dry_run {
+        client.plan(&desired, policy)?
+    } else {
+        client.reconcile(&desired, policy)?
+    };
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &plan)?;
+    } else if plan.is_empty() {
+        println!("Virtual monitors already match.");
+    } else {
+        if command.dry_run {
+            println!("{}", "Planned changes".underline());
+        } else {
+            println!("{}", "Applied changes".underline());
+        }
+        for change in &plan.changes {
+            println!("{} {change}", "-".dimmed());
         }
     }
 
     Ok(())
 }
 
-fn persist(client: &mut DriverClient) -> eyre::Result<()> {
-    client.persist()?;
+fn profile(
+    client: &mut DriverClient,
+    opts: &GlobalOptions,
+    command: &ProfileCommand,
+) -> eyre::Result<()> {
+    match command {
+        ProfileCommand::Save(command) => {
+            client.save_profile(&command.name)?;
+
+            if opts.json {
+                let mut stdout = std::io::stdout().lock();
+                serde_json::to_writer_pretty(&mut stdout, &command.name)?;
+            } else {
+                println!("Saved profile {}.", command.name.green());
+            }
+        }
+        ProfileCommand::Load(command) => {
+            client.load_profile(&command.name)?;
+
+            if opts.json {
+                let mut stdout = std::io::stdout().lock();
+                serde_json::to_writer_pretty(&mut stdout, &client.monitors())?;
+            } else {
+                println!("Loaded profile {}.", command.name.green());
+            }
+        }
+        ProfileCommand::List => {
+            let profiles = client.list_profiles()?;
+
+            if opts.json {
+                let mut stdout = std::io::stdout().lock();
+                serde_json::to_writer_pretty(&mut stdout, &profiles)?;
+            } else if !profiles.is_empty() {
+                println!("{}", "Profiles".underline());
+                for profile in &profiles {
+                    println!("{} {}", "-".dimmed(), profile.green());
+                }
+            } else {
+                println!("No profiles found.");
+            }
+        }
+        ProfileCommand::Delete(command) => {
+            if !client.delete_profile(&command.name)? {
+                bail!("Profile `{}` not found", command.name);
+            }
+
+            if opts.json {
+                let mut stdout = std::io::stdout().lock();
+                serde_json::to_writer_pretty(&mut stdout, &command.name)?;
+            } else {
+                println!("Deleted profile {}.", command.name.green());
+            }
+        }
+        ProfileCommand::Default(command) => {
+            let name = if command.clear {
+                None
+            } else {
+                command.name.as_deref()
+            };
+            client.set_default_profile(name)?;
+
+            if opts.json {
+                let mut stdout = std::io::stdout().lock();
+                serde_json::to_writer_pretty(&mut stdout, &name)?;
+            } else if let Some(name) = name {
+                println!("Set default profile to {}.", name.green());
+            } else {
+                println!("Removed default profile.");
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -166,12 +672,38 @@ fn list(client: &mut DriverClient, opts: &GlobalOptions) -> eyre::Result<()> {
                 Some(name) => (" {}{name}{}", "[".dimmed(), "]".dimmed()),
                 None => "",
             });
+            let key_label = lazy_format!(match (&monitor.key) {
+                Some(key) => (" {}{}", "key=".dimmed(), key.blue()),
+                None => "",
+            });
+            let size_label = lazy_format!(match (&monitor.physical_size) {
+                Some(size) => (" {}{}", "size=".dimmed(), size.blue()),
+                None => "",
+            });
+            let orientation_label = lazy_format!(
+                if monitor.orientation == Orientation::Landscape => ""
+                else => (" {}{}", "orientation=".dimmed(), monitor.orientation.blue())
+            );
+            let color_label = lazy_format!(
+                if monitor.color == Color::default() => ""
+                else => (" {}{}", "color=".dimmed(), monitor.color.blue())
+            );
+            let position_label = lazy_format!(match (&monitor.position) {
+                Some(position) => (" {}{}", "at=".dimmed(), position.blue()),
+                None => "",
+            });
+            let layout_label = lazy_format!(
+                if monitor.primary => (" {}", "(primary)".blue())
+                else if monitor.topology == Topology::Duplicate => (" {}", "(duplicate)".blue())
+                else => ""
+            );
             let disabled_label = lazy_format!(if monitor.enabled => ""
             else =>
                 (" {}", "(disabled)".red())
             );
             println!(
-                "Monitor {}{name_label}{disabled_label}:",
+                "Monitor {}{name_label}{key_label}{size_label}{orientation_label}{color_label}\
+                 {position_label}{layout_label}{disabled_label}:",
                 monitor.id.green(),
             );
 
@@ -218,7 +750,14 @@ fn add(client: &mut DriverClient, opts: &GlobalOptions, command: AddCommand) ->
         id,
         enabled: !command.disabled,
         name: command.name,
+        key: command.key,
         modes,
+        physical_size: command.size.physical_size(),
+        orientation: command.orientation,
+        position: command.layout.position,
+        primary: command.layout.primary,
+        topology: command.layout.topology(),
+        color: command.color.color(),
     };
 
     client.add(new_monitor)?;
@@ -244,29 +783,42 @@ fn add(client: &mut DriverClient, opts: &GlobalOptions, command: AddCommand) ->
 fn add_mode(
     client: &mut DriverClient,
     opts: &GlobalOptions,
-    command: AddModeCommand,
+    command: &AddModeCommand,
 ) -> eyre::Result<()> {
-    let Some((id, new_modes)) = client.find_monitor_mut_query(&command.id, |monitor| {
-        let id = monitor.id;
-
-        let existing_modes = monitor.modes.iter().cloned().map(mode::Mode::from);
-        let new_modes = mode::merge(existing_modes.chain(command.mode));
-        let new_modes: Vec<driver_ipc::Mode> =
-            new_modes.into_iter().map(driver_ipc::Mode::from).collect();
-
-        monitor.modes.clone_from(&new_modes);
-        (id, new_modes)
-    }) else {
-        bail!("Monitor `{}` not found", command.id);
-    };
+    let ids = select(client, std::slice::from_ref(&command.selector))?;
+
+    let mut outcomes = Vec::new();
+    for id in ids {
+        let new_modes = client
+            .find_monitor_mut(id, |monitor| {
+                let existing_modes = monitor.modes.iter().cloned().map(mode::Mode::from);
+                let new_modes = mode::merge(existing_modes.chain(command.mode.iter().cloned()));
+                let new_modes: Vec<driver_ipc::Mode> =
+                    new_modes.into_iter().map(driver_ipc::Mode::from).collect();
+
+                monitor.modes.clone_from(&new_modes);
+                new_modes
+            })
+            .ok_or(eyre!("Monitor {id} has duplicate modes"))?;
+
+        outcomes.push(ModesOutcome {
+            id,
+            modes: new_modes,
+        });
+    }
 
     client.notify()?;
 
     if opts.json {
         let mut stdout = std::io::stdout().lock();
-        serde_json::to_writer_pretty(&mut stdout, &new_modes)?;
+        serde_json::to_writer_pretty(&mut stdout, &outcomes)?;
     } else {
-        println!("Added modes to virtual monitor with ID {}.", id.green());
+        for outcome in &outcomes {
+            println!(
+                "Added modes to virtual monitor with ID {}.",
+                outcome.id.green()
+            );
+        }
     }
 
     Ok(())
@@ -277,34 +829,44 @@ fn remove_mode(
     opts: &GlobalOptions,
     command: &RemoveModeCommand,
 ) -> eyre::Result<()> {
-    let (id, new_modes) = client
-        .find_monitor_mut_query(
-            &command.id,
-            |monitor: &mut Monitor| -> eyre::Result<(Id, Vec<driver_ipc::Mode>)> {
-                let id = monitor.id;
-
-                let modes = monitor.modes.iter().cloned().map(mode::Mode::from);
-                let new_modes = mode::remove(modes, &command.mode)?;
-                let new_modes: Vec<driver_ipc::Mode> =
-                    new_modes.into_iter().map(driver_ipc::Mode::from).collect();
+    let ids = select(client, std::slice::from_ref(&command.selector))?;
 
-                monitor.modes.clone_from(&new_modes);
-                eyre::Result::Ok((id, new_modes))
-            },
-        )
-        .ok_or(eyre!("Monitor `{}` not found", command.id))??;
+    let mut outcomes = Vec::new();
+    for id in ids {
+        let new_modes = client
+            .find_monitor_mut(
+                id,
+                |monitor: &mut Monitor| -> eyre::Result<Vec<driver_ipc::Mode>> {
+                    let modes = monitor.modes.iter().cloned().map(mode::Mode::from);
+                    let new_modes = mode::remove(modes, &command.mode)?;
+                    let new_modes: Vec<driver_ipc::Mode> =
+                        new_modes.into_iter().map(driver_ipc::Mode::from).collect();
+
+                    monitor.modes.clone_from(&new_modes);
+                    eyre::Result::Ok(new_modes)
+                },
+            )
+            .ok_or(eyre!("Monitor {id} has duplicate modes"))??;
+
+        outcomes.push(ModesOutcome {
+            id,
+            modes: new_modes,
+        });
+    }
 
     client.notify()?;
 
     if opts.json {
         let mut stdout = std::io::stdout().lock();
-        serde_json::to_writer_pretty(&mut stdout, &new_modes)?;
+        serde_json::to_writer_pretty(&mut stdout, &outcomes)?;
     } else {
-        println!(
-            "Removed mode {} from virtual monitor with ID {}.",
-            command.mode.blue(),
-            id.green()
-        );
+        for outcome in &outcomes {
+            println!(
+                "Removed mode {} from virtual monitor with ID {}.",
+                command.mode.blue(),
+                outcome.id.green()
+            );
+        }
     }
 
     Ok(())
@@ -315,21 +877,23 @@ fn enable(
     opts: &GlobalOptions,
     command: &EnableCommand,
 ) -> eyre::Result<()> {
-    let outcome = set_enabled(client, &command.id, true)?;
+    let outcomes = set_enabled(client, &command.selector, true)?;
 
     if opts.json {
         let mut stdout = std::io::stdout().lock();
-        serde_json::to_writer_pretty(&mut stdout, &outcome)?;
+        serde_json::to_writer_pretty(&mut stdout, &outcomes)?;
     } else {
-        let footnote = if outcome.toggled {
-            ""
-        } else {
-            " (was already enabled)"
-        };
-        println!(
-            "Enabled virtual monitor with ID {}{footnote}.",
-            outcome.monitor.id.green()
-        );
+        for outcome in &outcomes {
+            let footnote = if outcome.toggled {
+                ""
+            } else {
+                " (was already enabled)"
+            };
+            println!(
+                "Enabled virtual monitor with ID {}{footnote}.",
+                outcome.monitor.id.green()
+            );
+        }
     }
 
     Ok(())
@@ -340,21 +904,23 @@ fn disable(
     opts: &GlobalOptions,
     command: &DisableCommand,
 ) -> eyre::Result<()> {
-    let outcome = set_enabled(client, &command.id, false)?;
+    let outcomes = set_enabled(client, &command.selector, false)?;
 
     if opts.json {
         let mut stdout = std::io::stdout().lock();
-        serde_json::to_writer_pretty(&mut stdout, &outcome)?;
+        serde_json::to_writer_pretty(&mut stdout, &outcomes)?;
     } else {
-        let footnote = if outcome.toggled {
-            ""
-        } else {
-            " (was already disabled)"
-        };
-        println!(
-            "Disabled virtual monitor with ID {}{footnote}.",
-            outcome.monitor.id.green()
-        );
+        for outcome in &outcomes {
+            let footnote = if outcome.toggled {
+                ""
+            } else {
+                " (was already disabled)"
+            };
+            println!(
+                "Disabled virtual monitor with ID {}{footnote}.",
+                outcome.monitor.id.green()
+            );
+        }
     }
 
     Ok(())
@@ -365,16 +931,361 @@ fn remove(
     opts: &GlobalOptions,
     command: &RemoveCommand,
 ) -> eyre::Result<()> {
-    client.remove_query(&command.id)?;
+    let ids = select(client, &command.selector)?;
+
+    client.remove(&ids);
     client.notify()?;
 
     if opts.json {
         let mut stdout = std::io::stdout().lock();
-        serde_json::to_writer_pretty(&mut stdout, &command.id)?;
-    } else if command.id.len() == 1 {
+        serde_json::to_writer_pretty(&mut stdout, &ids)?;
+    } else if ids.len() == 1 {
         println!("Removed virtual monitor.");
     } else {
-        println!("Removed {} virtual monitors.", command.id.len());
+        println!("Removed {} virtual monitors.", ids.len());
+    }
+
+    Ok(())
+}
+
+fn set_size(
+    client: &mut DriverClient,
+    opts: &GlobalOptions,
+    command: &SetSizeCommand,
+) -> eyre::Result<()> {
+    let ids = select(client, std::slice::from_ref(&command.selector))?;
+    let physical_size = command.size.physical_size();
+
+    for &id in &ids {
+        client
+            .find_monitor_mut(id, |monitor| monitor.physical_size = physical_size)
+            .ok_or(eyre!("Monitor {id} not found"))?;
+    }
+
+    client.notify()?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &ids)?;
+    } else {
+        for id in &ids {
+            println!(
+                "Set physical size of virtual monitor with ID {}.",
+                id.green()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn set_orientation(
+    client: &mut DriverClient,
+    opts: &GlobalOptions,
+    command: &SetOrientationCommand,
+) -> eyre::Result<()> {
+    let ids = select(client, std::slice::from_ref(&command.selector))?;
+
+    for &id in &ids {
+        client
+            .find_monitor_mut(id, |monitor| monitor.orientation = command.orientation)
+            .ok_or(eyre!("Monitor {id} not found"))?;
+    }
+
+    client.notify()?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &ids)?;
+    } else {
+        for id in &ids {
+            println!(
+                "Set orientation of virtual monitor with ID {} to {}.",
+                id.green(),
+                command.orientation.blue()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn set_color(
+    client: &mut DriverClient,
+    opts: &GlobalOptions,
+    command: &SetColorCommand,
+) -> eyre::Result<()> {
+    let ids = select(client, std::slice::from_ref(&command.selector))?;
+    let color = command.color.color();
+
+    for &id in &ids {
+        client
+            .find_monitor_mut(id, |monitor| monitor.color = color)
+            .ok_or(eyre!("Monitor {id} not found"))?;
+    }
+
+    client.notify()?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &ids)?;
+    } else {
+        for id in &ids {
+            println!(
+                "Set color of virtual monitor with ID {} to {}.",
+                id.green(),
+                color.blue()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn place(
+    client: &mut DriverClient,
+    opts: &GlobalOptions,
+    command: &PlaceCommand,
+) -> eyre::Result<()> {
+    let ids = select(client, std::slice::from_ref(&command.selector))?;
+
+    for &id in &ids {
+        client
+            .find_monitor_mut(id, |monitor| {
+                monitor.position = command.layout.position;
+                monitor.primary = command.layout.primary;
+                monitor.topology = command.layout.topology();
+            })
+            .ok_or(eyre!("Monitor {id} not found"))?;
+    }
+
+    client.notify()?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &ids)?;
+    } else {
+        for id in &ids {
+            println!("Placed virtual monitor with ID {}.", id.green());
+        }
+    }
+
+    Ok(())
+}
+
+fn layout(client: &DriverClient, opts: &GlobalOptions) -> eyre::Result<()> {
+    let layout = Layout::plan(client.monitors(), &[])?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &layout)?;
+    } else if layout.placements.is_empty() && layout.duplicates.is_empty() {
+        println!("No enabled virtual monitors found.");
+    } else {
+        println!("{}", "Layout".underline());
+        for placement in &layout.placements {
+            let primary_label = lazy_format!(
+                if placement.primary => (" {}", "(primary)".blue())
+                else => ""
+            );
+            let rect = placement.rect;
+            println!(
+                "{} Monitor {}: {}x{} at {},{}{primary_label}",
+                "-".dimmed(),
+                placement.id.green(),
+                rect.width,
+                rect.height,
+                rect.x,
+                rect.y,
+            );
+        }
+        for id in &layout.duplicates {
+            println!(
+                "{} Monitor {}: duplicates the primary display",
+                "-".dimmed(),
+                id.green()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn stats(client: &DriverClient, opts: &GlobalOptions) -> eyre::Result<()> {
+    let stats = client.request_stats()?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &stats)?;
+        return Ok(());
+    }
+
+    if stats.monitors.is_empty() {
+        println!("No virtual monitors received a swap chain yet.");
+    } else {
+        println!("{}", "Monitors".underline());
+        for monitor in &stats.monitors {
+            let surface = lazy_format!(match (&monitor.surface) {
+                Some(s) => ("{}x{} {}{}", s.width, s.height, "format=".dimmed(), s.format.blue()),
+                None => ("{}", "no frame yet".dimmed()),
+            });
+            println!("{} Monitor {}: {surface}", "-".dimmed(), monitor.id.green());
+            println!(
+                "  {} acquired, {} captured, {} pending waits, {} swap chain errors",
+                monitor.frames_acquired,
+                monitor.frames_captured,
+                monitor.pending_waits,
+                monitor.swap_chain_errors,
+            );
+        }
+    }
+
+    if let Some(recording) = stats.recording {
+        println!("{}", "Recording".underline());
+        println!(
+            "{} {} frames sent, {} dropped in {:.1}s",
+            "-".dimmed(),
+            recording.frames_sent,
+            recording.frames_dropped,
+            std::time::Duration::from_millis(recording.duration_ms).as_secs_f64(),
+        );
+    }
+
+    Ok(())
+}
+
+fn status(client: &DriverClient, opts: &GlobalOptions) -> eyre::Result<()> {
+    let health = client.health()?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &health)?;
+        return Ok(());
+    }
+
+    let yes_no = |ok: bool| lazy_format!(if ok => ("{}", "yes".green()) else => ("{}", "no".red()));
+    let uptime = std::time::Duration::from_secs(health.uptime_ms / 1000);
+
+    println!("{}", "Driver".underline());
+    println!(
+        "{} Version: {} @ {}",
+        "-".dimmed(),
+        health.version.green(),
+        health.git_sha
+    );
+    println!("{} IddCx: {} or newer", "-".dimmed(), health.iddcx_min_version);
+    println!(
+        "{} Uptime: {}h {}m {}s",
+        "-".dimmed(),
+        uptime.as_secs() / 3600,
+        uptime.as_secs() / 60 % 60,
+        uptime.as_secs() % 60
+    );
+    println!(
+        "{} Adapter initialized: {}",
+        "-".dimmed(),
+        yes_no(health.adapter_initialized)
+    );
+    println!(
+        "{} Pipe server: {} ({} clients)",
+        "-".dimmed(),
+        yes_no(health.pipe_server),
+        health.clients
+    );
+    println!("{} Access: {}", "-".dimmed(), client.access()?);
+
+    if !health.swap_chains.is_empty() {
+        println!("{}", "Swap chains".underline());
+        for swap_chain in &health.swap_chains {
+            println!(
+                "{} Monitor {}: alive {}",
+                "-".dimmed(),
+                swap_chain.id.green(),
+                yes_no(swap_chain.alive)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn logs(client: &DriverClient, opts: &GlobalOptions, command: &LogsCommand) -> eyre::Result<()> {
+    let print = |record: &LogRecord| -> eyre::Result<()> {
+        if opts.json {
+            // one record per line, so it can be followed
+            println!("{}", serde_json::to_string(record)?);
+        } else {
+            let level = match record.level {
+                LogLevel::Error => record.level.red().to_string(),
+                LogLevel::Warn => record.level.yellow().to_string(),
+                LogLevel::Info => record.level.green().to_string(),
+                LogLevel::Debug | LogLevel::Trace => record.level.dimmed().to_string(),
+            };
+            println!(
+                "{} {level:<5} {} {}",
+                record.seq.dimmed(),
+                record.target.dimmed(),
+                record.message
+            );
+        }
+
+        Ok(())
+    };
+
+    // subscribe first, so no record between the request and the subscription
+    // gets lost
+    let (tx, rx) = std::sync::mpsc::channel();
+    let _subscription = if command.follow {
+        let subscription = client.add_event_receiver(move |event| {
+            if let Ok(EventCommand::Log(record)) = event {
+                _ = tx.send(record);
+            }
+        });
+        client.subscribe_logs(Some(command.level.unwrap_or(LogLevel::Trace)))?;
+        Some(subscription)
+    } else {
+        None
+    };
+
+    let mut last = command.since;
+    for record in client.request_logs(command.since, command.level)? {
+        print(&record)?;
+        last = Some(record.seq);
+    }
+
+    if command.follow {
+        for record in rx {
+            // skip records already in the reply
+            if last.is_some_and(|last| record.seq <= last) {
+                continue;
+            }
+            print(&record)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn set_log_level(
+    client: &DriverClient,
+    opts: &GlobalOptions,
+    command: &SetLogLevelCommand,
+) -> eyre::Result<()> {
+    let filter = LogFilter {
+        level: command.level,
+        targets: command.targets.iter().cloned().collect(),
+    };
+
+    client.set_log_filter(filter.clone())?;
+
+    if opts.json {
+        let mut stdout = std::io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &filter)?;
+    } else {
+        println!("Set driver log level to {}.", filter.level.green());
+        for (target, level) in &filter.targets {
+            println!("{} {target}: {}", "-".dimmed(), level.green());
+        }
     }
 
     Ok(())
@@ -394,25 +1305,61 @@ fn remove_all(client: &mut DriverClient, opts: &GlobalOptions) -> eyre::Result<(
     Ok(())
 }
 
+/// IDs of all monitors matching any of `selectors`, without duplicates.
+///
+/// Fails if a selector matches no monitor.
+fn select(client: &DriverClient, selectors: &[Selector]) -> eyre::Result<Vec<Id>> {
+    let mut ids = Vec::new();
+
+    for selector in selectors {
+        let matched = client.select(selector);
+        if matched.is_empty() {
+            bail!("No monitor matches `{selector}`");
+        }
+
+        for id in matched {
+            if !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
 fn set_enabled(
     client: &mut DriverClient,
-    query: &str,
+    selectors: &[Selector],
     enabled: bool,
-) -> eyre::Result<EnableDisableOutcome> {
-    let monitor = client
-        .find_monitor_query(query)
-        .ok_or(eyre!("Monitor matching `{query}` not found"))?
-        .clone();
+) -> eyre::Result<Vec<EnableDisableOutcome>> {
+    let ids = select(client, selectors)?;
+
+    let outcomes = ids
+        .iter()
+        .filter_map(|&id| client.find_monitor(id))
+        .map(|monitor| EnableDisableOutcome {
+            monitor: monitor.clone(),
+            toggled: enabled != monitor.enabled,
+        })
+        .collect();
 
-    client.set_enabled_query(&[query], enabled)?;
+    client.set_enabled(&ids, enabled);
     client.notify()?;
 
-    let should_toggle = enabled != monitor.enabled;
+    Ok(outcomes)
+}
+
+#[derive(Debug, Serialize)]
+struct PresetOutput {
+    name: &'static str,
+    aliases: &'static [&'static str],
+    mode: driver_ipc::Mode,
+}
 
-    Ok(EnableDisableOutcome {
-        monitor,
-        toggled: should_toggle,
-    })
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
+struct ModesOutcome {
+    id: Id,
+    modes: Vec<driver_ipc::Mode>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq)]
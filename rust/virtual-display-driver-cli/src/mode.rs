@@ -6,8 +6,8 @@ use joinery::JoinableIterator as _;
 const DEFAULT_REFRESH_RATE: driver_ipc::RefreshRate = 60;
 
 /// Represent a mode as specified by the user as a CLI argument. Can be parsed
-/// from a string such as `1920x1080` or `3840x2160@60/120`, or converted
-/// from/to the type [`driver_ipc::Mode`].
+/// from a string such as `1920x1080`, `3840x2160@60/120` or `4k@144` (see
+/// [`driver_ipc::preset`]), or converted from/to the type [`driver_ipc::Mode`].
 ///
 /// This type is very similar to [`driver_ipc::Mode`], but with a few key
 /// differences:
@@ -86,15 +86,23 @@ impl std::str::FromStr for Mode {
             None => (s, None),
         };
 
-        let (width, height) = resolution.split_once('x').ok_or_else(|| {
-            eyre::eyre!("invalid resolution in {s:?}, expected a string like \"1920x1080\"",)
-        })?;
-        let width = width
-            .parse()
-            .with_context(|| format!("invalid width in {s:?}, expected a number"))?;
-        let height = height
-            .parse()
-            .with_context(|| format!("invalid height in {s:?}, expected a number"))?;
+        let (width, height) = if let Some(preset) = driver_ipc::preset::find(resolution) {
+            (preset.width, preset.height)
+        } else {
+            let (width, height) = resolution.split_once('x').ok_or_else(|| {
+                eyre::eyre!(
+                    "invalid resolution in {s:?}, expected a string like \"1920x1080\" or a preset like \"4k\"",
+                )
+            })?;
+            let width = width
+                .parse()
+                .with_context(|| format!("invalid width in {s:?}, expected a number"))?;
+            let height = height
+                .parse()
+                .with_context(|| format!("invalid height in {s:?}, expected a number"))?;
+
+            (width, height)
+        };
 
         let refresh_rates = match refresh_rate_list {
             Some(refresh_rate_list) => refresh_rate_list
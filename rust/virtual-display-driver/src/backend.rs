@@ -0,0 +1,171 @@
+//! The [DisplayBackend] talking to IddCx
+
+use anyhow::anyhow;
+use driver_ipc::{
+    backend::{Capture, DisplayBackend, FlattenModes, Recording},
+    Id, Monitor,
+};
+use wdf_umdf::{IddCxMonitorDeparture, IddCxMonitorUpdateModes};
+use wdf_umdf_sys::{IDARG_IN_UPDATEMODES, IDDCX_UPDATE_REASON};
+
+use crate::{
+    callbacks::target_mode,
+    config::config,
+    context::{ContextError, DeviceContext},
+    ipc::{MonitorObject, ADAPTER, MONITOR_MODES, RECORDING_STATE},
+    recording::{RecordingConfig, RecordingSession},
+};
+
+/// Creates the monitors of [MONITOR_MODES] and captures their frames
+///
+/// [MONITOR_MODES] only holds connected monitors, the IddCx callbacks look
+/// them up there.
+pub struct IddCxBackend;
+
+impl DisplayBackend for IddCxBackend {
+    type Error = ContextError;
+
+    fn arrive(&mut self, monitor: &Monitor) -> Result<(), Self::Error> {
+        let adapter = ADAPTER
+            .get()
+            .ok_or(anyhow!("Adapter is not initialized"))?
+            .0
+            .as_ptr();
+
+        // the os reads the monitor while it arrives
+        {
+            let mut lock = MONITOR_MODES.lock().unwrap();
+            lock.retain(|m| m.data.id != monitor.id);
+            lock.push(MonitorObject {
+                object: None,
+                data: monitor.clone(),
+            });
+        }
+
+        // context.create_monitor locks MONITOR_MODES again
+        let mut created = Ok(());
+        let cb = |context: &mut DeviceContext| created = context.create_monitor(monitor.id);
+        let result = unsafe { DeviceContext::get_mut(adapter.cast(), cb) };
+
+        let result = result.map_err(ContextError::from).and(created);
+        if result.is_err() {
+            MONITOR_MODES
+                .lock()
+                .unwrap()
+                .retain(|m| m.data.id != monitor.id);
+        }
+
+        result
+    }
+
+    fn depart(&mut self, id: Id) -> Result<(), Self::Error> {
+        let object = {
+            let mut lock = MONITOR_MODES.lock().unwrap();
+            let Some(index) = lock.iter().position(|m| m.data.id == id) else {
+                return Ok(());
+            };
+
+            lock.remove(index).object
+        };
+
+        if let Some(mut object) = object {
+            let object = unsafe { object.as_mut() };
+            unsafe { IddCxMonitorDeparture(object) }?;
+        }
+
+        Ok(())
+    }
+
+    fn update_modes(&mut self, monitor: &Monitor) -> Result<(), Self::Error> {
+        let mut lock = MONITOR_MODES.lock().unwrap();
+
+        let Some(mon) = lock.iter_mut().find(|mon| mon.data.id == monitor.id) else {
+            return Err(anyhow!("Monitor {} is not connected", monitor.id).into());
+        };
+
+        let Some(mut obj) = mon.object else {
+            return Err(anyhow!("Monitor {} is not connected", monitor.id).into());
+        };
+
+        // the os queries the target modes from here
+        mon.data = monitor.clone();
+
+        let orientation = monitor.orientation;
+        let mut target_modes = monitor
+            .modes
+            .flatten()
+            .map(|mode| {
+                let (width, height) = orientation.rotate(mode.width, mode.height);
+                target_mode(width, height, mode.refresh_rate)
+            })
+            .collect::<Vec<_>>();
+
+        let update_modes = IDARG_IN_UPDATEMODES {
+            Reason: IDDCX_UPDATE_REASON::IDDCX_UPDATE_REASON_OTHER,
+            #[allow(clippy::cast_possible_truncation)]
+            TargetModeCount: target_modes.len() as u32,
+            pTargetModes: target_modes.as_mut_ptr(),
+        };
+
+        // the os may query the target modes again, which requires the lock
+        drop(lock);
+
+        let obj = unsafe { obj.as_mut() };
+        unsafe { IddCxMonitorUpdateModes(obj, &update_modes) }?;
+
+        Ok(())
+    }
+
+    fn start_capture(&mut self, capture: &Capture) -> Result<(), Self::Error> {
+        let mut state = RECORDING_STATE.lock().unwrap();
+
+        state.active = true;
+        state.monitor_ids = capture.monitor_ids.iter().copied().collect();
+
+        // Start MP4 recording if output path provided
+        state.session = capture.output_path.as_ref().map(|path| {
+            crate::swap_chain_processor::trace_log(&format!(
+                "IPC: Creating RecordingSession path={path:?} fps={:?}",
+                capture.fps
+            ));
+
+            let config = config();
+            RecordingSession::start(RecordingConfig {
+                output_path: path.clone(),
+                fps: capture.fps.unwrap_or(config.recording_fps),
+                bitrate: config.recording_bitrate,
+            })
+        });
+
+        crate::swap_chain_processor::trace_log(&format!(
+            "IPC: RecordingState now active={}, monitors={:?}, has_session={}",
+            state.active,
+            state.monitor_ids,
+            state.session.is_some()
+        ));
+
+        Ok(())
+    }
+
+    fn stop_capture(&mut self) -> Option<Recording> {
+        // the swap chain threads lock the state for every frame, so the
+        // session is stopped without holding it
+        let session = {
+            let mut state = RECORDING_STATE.lock().unwrap();
+            state.active = false;
+            state.monitor_ids.clear();
+            state.session.take()
+        };
+
+        let result = session?.stop()?;
+        Some(Recording {
+            path: result.path,
+            frames: result.frames,
+            duration_ms: result.duration_ms,
+        })
+    }
+
+    fn shm_name(&self, id: Id) -> String {
+        format!("Global\\VDD_Frame_{id}")
+    }
+}
@@ -3,6 +3,7 @@ use std::{
     ptr::NonNull,
 };
 
+use driver_ipc::backend::FlattenModes;
 use log::error;
 use wdf_umdf_sys::{
     DISPLAYCONFIG_VIDEO_SIGNAL_INFO__bindgen_ty_1,
@@ -19,7 +20,7 @@ use wdf_umdf_sys::{
 use crate::{
     context::{DeviceContext, MonitorContext},
     edid::Edid,
-    ipc::{AdapterObject, FlattenModes, ADAPTER, MONITOR_MODES},
+    ipc::{AdapterObject, ADAPTER, MONITOR_MODES},
 };
 
 pub extern "C-unwind" fn adapter_init_finished(
@@ -162,12 +163,17 @@ pub extern "C-unwind" fn parse_monitor_description(
         )
     };
 
+    // the monitor reports its unrotated modes, the OS rotates the desktop
+    let orientation = monitor.data.orientation;
+
     for (mode, out_mode) in monitor.data.modes.flatten().zip(monitor_modes.iter_mut()) {
+        let (width, height) = orientation.rotate(mode.width, mode.height);
+
         out_mode.write(IDDCX_MONITOR_MODE {
             #[allow(clippy::cast_possible_truncation)]
             Size: mem::size_of::<IDDCX_MONITOR_MODE>() as u32,
             Origin: IDDCX_MONITOR_MODE_ORIGIN::IDDCX_MONITOR_MODE_ORIGIN_MONITORDESCRIPTOR,
-            MonitorVideoSignalInfo: display_info(mode.width, mode.height, mode.refresh_rate),
+            MonitorVideoSignalInfo: display_info(width, height, mode.refresh_rate),
         });
     }
 
@@ -279,7 +285,8 @@ pub extern "C-unwind" fn monitor_query_modes(
             .flatten()
             .zip(out_target_modes.iter_mut())
         {
-            let target_mode = target_mode(mode.width, mode.height, mode.refresh_rate);
+            let (width, height) = monitor.data.orientation.rotate(mode.width, mode.height);
+            let target_mode = target_mode(width, height, mode.refresh_rate);
 
             out_target.write(target_mode);
         }
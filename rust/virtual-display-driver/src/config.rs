@@ -0,0 +1,115 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, RwLock},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use driver_ipc::{DriverConfig, DEFAULT_CONFIG_PATH};
+use log::{info, warn};
+use winreg::{enums::HKEY_LOCAL_MACHINE, RegKey};
+
+/// Registry key (under HKLM) which may override the config file location
+const CONFIG_KEY: &str = r"SOFTWARE\VirtualDisplayDriver";
+
+/// How often the config file is checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Effective driver configuration, loaded on first use
+static CONFIG: LazyLock<RwLock<Arc<DriverConfig>>> = LazyLock::new(|| {
+    let config = read(&config_path()).unwrap_or_default();
+    RwLock::new(Arc::new(config))
+});
+
+/// Get the effective driver configuration
+pub fn config() -> Arc<DriverConfig> {
+    CONFIG.read().unwrap().clone()
+}
+
+/// Location of the config file
+///
+/// This is the `ConfigPath` registry value if set, otherwise [`DEFAULT_CONFIG_PATH`]
+fn config_path() -> PathBuf {
+    let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+
+    hklm.open_subkey(CONFIG_KEY)
+        .and_then(|key| key.get_value::<String, _>("ConfigPath"))
+        .map_or_else(|_| PathBuf::from(DEFAULT_CONFIG_PATH), PathBuf::from)
+}
+
+/// Location of the log filter kept across restarts, next to the config file
+pub fn log_filter_path() -> PathBuf {
+    config_path().with_file_name("log_filter.json")
+}
+
+/// Read and validate the config file
+///
+/// A missing file results in the default configuration. Returns `None` if the
+/// file could not be read or is invalid.
+fn read(path: &Path) -> Option<DriverConfig> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Some(DriverConfig::default()),
+        Err(e) => {
+            warn!("Failed to read config {}: {e}", path.display());
+            return None;
+        }
+    };
+
+    match DriverConfig::from_json(&json) {
+        Ok(config) => Some(config),
+        Err(e) => {
+            warn!("Invalid config {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// Watch the config file and apply changes to the hot reloadable settings
+///
+/// An invalid file keeps the current configuration. Changes to settings which
+/// require a restart are logged and ignored.
+pub fn watch() {
+    // make sure the initial config is loaded before the file is watched
+    LazyLock::force(&CONFIG);
+
+    thread::spawn(|| {
+        let path = config_path();
+        let mut last_modified = modified(&path);
+
+        loop {
+            thread::sleep(RELOAD_INTERVAL);
+
+            let modified = modified(&path);
+            if modified == last_modified {
+                continue;
+            }
+
+            last_modified = modified;
+
+            let Some(new) = read(&path) else {
+                continue;
+            };
+
+            let mut lock = CONFIG.write().unwrap();
+
+            let (config, ignored) = lock.reload(&new);
+            if !ignored.is_empty() {
+                warn!("Config changes to {ignored:?} require a driver restart to take effect");
+            }
+
+            if config.log_filter != lock.log_filter {
+                crate::log_filter::apply(config.log_filter.clone());
+            }
+
+            info!("Reloaded config {}", path.display());
+
+            *lock = Arc::new(config);
+        }
+    });
+}
@@ -5,6 +5,10 @@ use std::{
 };
 
 use anyhow::anyhow;
+use driver_ipc::{
+    persist::{RegistryStore, Scope, Store, DEFAULT_ENTRY},
+    Orientation,
+};
 use log::{error, warn};
 use wdf_umdf::{
     IddCxAdapterInitAsync, IddCxError, IddCxMonitorArrival,
@@ -28,15 +32,13 @@ use windows::{
 };
 
 use crate::{
+    config::{self, config},
     direct_3d_device::Direct3DDevice,
     edid::Edid,
-    ipc::{startup, MONITOR_MODES},
+    ipc::{notify, startup, MONITOR_MODES},
     swap_chain_processor::SwapChainProcessor,
 };
 
-// Maximum amount of monitors that can be connected
-pub const MAX_MONITORS: u8 = 16;
-
 pub struct DeviceContext {
     device: WDFDEVICE,
     adapter: Option<IDDCX_ADAPTER>,
@@ -51,6 +53,9 @@ unsafe impl Sync for DeviceContext {}
 pub struct MonitorContext {
     device: IDDCX_MONITOR,
     monitor_id: u32,
+    orientation: Orientation,
+    /// Whether the monitor reports HDR10 in its EDID
+    hdr: bool,
     swap_chain_processor: Option<SwapChainProcessor>,
 }
 
@@ -102,7 +107,7 @@ impl DeviceContext {
             #[allow(clippy::cast_possible_truncation)]
             Size: size_of::<IDDCX_ADAPTER_CAPS>() as u32,
 
-            MaxMonitorsSupported: u32::from(MAX_MONITORS),
+            MaxMonitorsSupported: config().max_monitors,
 
             EndPointDiagnostics: IDDCX_ENDPOINT_DIAGNOSTIC_INFO {
                 #[allow(clippy::cast_possible_truncation)]
@@ -141,6 +146,16 @@ impl DeviceContext {
     }
 
     pub fn finish_init() -> NTSTATUS {
+        // pick up changes to the config file while running
+        config::watch();
+
+        // restore the monitors persisted for the whole machine
+        match RegistryStore::new(Scope::Machine).load(DEFAULT_ENTRY) {
+            Ok(Some(monitors)) => notify(monitors),
+            Ok(None) => (),
+            Err(e) => error!("Failed to load persisted monitors: {e}"),
+        }
+
         // start the socket listener to listen for messages from the client
         startup();
 
@@ -152,8 +167,28 @@ impl DeviceContext {
         let mut attr =
             WDF_OBJECT_ATTRIBUTES::init_context_type(unsafe { MonitorContext::get_type_info() });
 
+        let (size_mm, orientation, color) = MONITOR_MODES
+            .lock()
+            .map_err(|_| anyhow!("Failed to lock mutex"))?
+            .iter()
+            .find(|monitor| monitor.data.id == index)
+            .map(|monitor| {
+                (
+                    monitor.data.physical_size_mm(),
+                    monitor.data.orientation,
+                    monitor.data.color,
+                )
+            })
+            .unwrap_or_default();
+
         // use the edid serial number to represent the monitor index for later identification
-        let mut edid = Edid::generate_with(index);
+        // the size is unrotated like the monitor modes
+        let mut edid = Edid::generate_with(
+            index,
+            size_mm.map(|(width, height)| orientation.rotate(width, height)),
+            &color,
+        );
+        let hdr = color.hdr.is_some();
 
         let mut monitor_info = IDDCX_MONITOR_INFO {
             #[allow(clippy::cast_possible_truncation)]
@@ -212,7 +247,8 @@ impl DeviceContext {
         }
 
         unsafe {
-            let context = MonitorContext::new(monitor_create_out.MonitorObject, index);
+            let context =
+                MonitorContext::new(monitor_create_out.MonitorObject, index, orientation, hdr);
             context.init(monitor_create_out.MonitorObject as WDFOBJECT)?;
         }
 
@@ -240,10 +276,17 @@ impl DeviceContext {
 }
 
 impl MonitorContext {
-    pub fn new(device: IDDCX_MONITOR, monitor_id: u32) -> Self {
+    pub fn new(
+        device: IDDCX_MONITOR,
+        monitor_id: u32,
+        orientation: Orientation,
+        hdr: bool,
+    ) -> Self {
         Self {
             device,
             monitor_id,
+            orientation,
+            hdr,
             swap_chain_processor: None,
         }
     }
@@ -276,7 +319,8 @@ impl MonitorContext {
                 self.monitor_id
             ));
 
-            let mut processor = SwapChainProcessor::new(self.monitor_id);
+            let mut processor =
+                SwapChainProcessor::new(self.monitor_id, self.orientation, self.hdr);
 
             processor.run(swap_chain, device, new_frame_event);
 
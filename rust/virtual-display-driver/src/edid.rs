@@ -1,6 +1,7 @@
 use std::{array::TryFromSliceError, ops::Deref};
 
 use bytemuck::{Pod, Zeroable};
+use driver_ipc::{Color, ColorFormat, Hdr};
 
 const _EDID: [u8; 128] = [
     0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x0D, 0x19, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
@@ -15,6 +16,21 @@ const _EDID: [u8; 128] = [
 
 const EDID_LEN: usize = _EDID.len();
 
+/// Offset of the maximum image size in cm in the basic display parameters
+const MAX_IMAGE_SIZE: usize = 21;
+/// Offset of the image size in mm in the detailed timing descriptor
+const DTD_IMAGE_SIZE: usize = 66;
+/// Offset of the EDID revision, 3 for EDID 1.3
+const REVISION: usize = 19;
+/// Offset of the video input definition, which holds the bit depth in 1.4
+const VIDEO_INPUT: usize = 20;
+/// Offset of the feature support, which holds the color encodings in 1.4
+const FEATURES: usize = 24;
+/// Offset of the chromaticity coordinates
+const CHROMATICITY: usize = 25;
+/// Offset of the number of extension blocks
+const EXTENSION_COUNT: usize = 126;
+
 static EDID: AlignedEdid<EDID_LEN> = AlignedEdid {
     data: _EDID,
     _align: [],
@@ -59,16 +75,37 @@ pub struct Edid {
 }
 
 impl Edid {
-    pub fn generate_with(serial: u32) -> Vec<u8> {
+    /// Generate the EDID with `serial`, an optional physical size in mm and
+    /// the color capabilities
+    pub fn generate_with(serial: u32, size_mm: Option<(u32, u32)>, color: &Color) -> Vec<u8> {
         // change serial number in the header
         let mut header = *EDID;
         header.serial_number = serial;
 
-        header.generate()
+        let mut edid = header.generate();
+
+        if let Some((width, height)) = size_mm {
+            Self::set_image_size(&mut edid, width, height);
+        }
+
+        // keep the EDID 1.3 of the default monitor as it is
+        if *color != Color::default() {
+            Self::set_color(&mut edid, color);
+        }
+
+        if let Some(hdr) = &color.hdr {
+            edid[EXTENSION_COUNT] = 1;
+            edid.extend(Self::cta_extension(color.format, hdr));
+        }
+
+        Self::gen_checksum(&mut edid);
+
+        edid
     }
 
     pub fn get_serial(edid: &[u8]) -> Result<u32, TryFromSliceError> {
-        let edid = AlignedEdid::<EDID_LEN>::new(edid)?;
+        // extension blocks follow the base block
+        let edid = AlignedEdid::<EDID_LEN>::new(edid.get(..EDID_LEN).unwrap_or(edid))?;
         Ok(edid.serial_number)
     }
 
@@ -86,6 +123,119 @@ impl Edid {
         edid
     }
 
+    /// Write the image size into the basic display parameters and the detailed
+    /// timing descriptor, clamped to what the fields can hold
+    #[allow(clippy::cast_possible_truncation)]
+    fn set_image_size(data: &mut [u8], width: u32, height: u32) {
+        // cm, 0 means undefined
+        let cm = |mm: u32| (mm.saturating_add(5) / 10).clamp(1, 255) as u8;
+        data[MAX_IMAGE_SIZE] = cm(width);
+        data[MAX_IMAGE_SIZE + 1] = cm(height);
+
+        // mm, 12 bits each with the upper 4 bits packed into a third byte
+        let (width, height) = (width.min(0xFFF), height.min(0xFFF));
+        data[DTD_IMAGE_SIZE] = width as u8;
+        data[DTD_IMAGE_SIZE + 1] = height as u8;
+        data[DTD_IMAGE_SIZE + 2] = ((width >> 8) << 4 | height >> 8) as u8;
+    }
+
+    /// Upgrade to EDID 1.4 to report the bit depth and color encodings, and
+    /// write the chromaticity of the HDR primaries
+    fn set_color(data: &mut [u8], color: &Color) {
+        data[REVISION] = 4;
+
+        // digital, bit depth in bits 6-4 starting at 6 bits, interface undefined
+        data[VIDEO_INPUT] = 0x80 | ((color.bit_depth.bits() / 2 - 2) << 4);
+
+        let encodings = match color.format {
+            ColorFormat::Rgb => 0b00,
+            ColorFormat::YCbCr444 => 0b01,
+            // 4:2:2 implies 4:4:4
+            ColorFormat::YCbCr422 => 0b11,
+        };
+        data[FEATURES] = (data[FEATURES] & !0b1_1000) | (encodings << 3);
+
+        if let Some(hdr) = &color.hdr {
+            Self::set_chromaticity(data, hdr.primaries.chromaticity());
+        }
+    }
+
+    /// Write the xy coordinates of red, green, blue and white as 10-bit
+    /// fractions, with the low 2 bits of each packed into the first two bytes
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn set_chromaticity(data: &mut [u8], chromaticity: [(f32, f32); 4]) {
+        let coordinates = chromaticity
+            .iter()
+            .flat_map(|&(x, y)| [x, y])
+            .map(|c| (c * 1024.0).round().clamp(0.0, 1023.0) as u16);
+
+        let mut low = [0u8; 2];
+        for (i, c) in coordinates.enumerate() {
+            data[CHROMATICITY + 2 + i] = (c >> 2) as u8;
+            low[i / 4] |= ((c & 0b11) as u8) << (6 - 2 * (i % 4));
+        }
+        data[CHROMATICITY..CHROMATICITY + 2].copy_from_slice(&low);
+    }
+
+    /// A CTA-861 extension block with the HDR static metadata and BT.2020
+    /// colorimetry, which the OS requires for HDR10. It only advertises HDR,
+    /// the adapter doesn't opt into the IddCx 1.10 HDR callbacks.
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn cta_extension(format: ColorFormat, hdr: &Hdr) -> Vec<u8> {
+        // extended tag data blocks
+        const EXTENDED_TAG: u8 = 7 << 5;
+        const COLORIMETRY: u8 = 0x05;
+        const HDR_STATIC_METADATA: u8 = 0x06;
+
+        // traditional gamma SDR and SMPTE ST 2084
+        const EOTF: u8 = 0b0000_0101;
+        // static metadata type 1
+        const METADATA_TYPE: u8 = 0b0000_0001;
+
+        let ycbcr = format != ColorFormat::Rgb;
+
+        // max luminance is 50 * 2^(cv / 32) nits
+        let max_luminance = hdr.max_luminance.max(50.0);
+        let max_cv = (32.0 * (max_luminance / 50.0).log2()).round().clamp(0.0, 255.0) as u8;
+        // min luminance is max * (cv / 255)^2 / 100 nits
+        let min_cv = (255.0 * (hdr.min_luminance.max(0.0) * 100.0 / max_luminance).sqrt())
+            .round()
+            .clamp(0.0, 255.0) as u8;
+
+        let blocks = [
+            // BT2020RGB, and BT2020YCC with YCbCr
+            EXTENDED_TAG | 3,
+            COLORIMETRY,
+            0x80 | (u8::from(ycbcr) << 6),
+            0x00,
+            // max frame-average luminance is the same as the max luminance
+            EXTENDED_TAG | 6,
+            HDR_STATIC_METADATA,
+            EOTF,
+            METADATA_TYPE,
+            max_cv,
+            max_cv,
+            min_cv,
+        ];
+
+        let mut block = vec![0u8; EDID_LEN];
+        // tag and revision
+        block[0] = 0x02;
+        block[1] = 0x03;
+        // no detailed timing descriptors after the data blocks
+        block[2] = 4 + blocks.len() as u8;
+        // YCbCr 4:4:4 and 4:2:2 support
+        block[3] = match format {
+            ColorFormat::Rgb => 0,
+            ColorFormat::YCbCr444 => 0b0010_0000,
+            ColorFormat::YCbCr422 => 0b0011_0000,
+        };
+        block[4..4 + blocks.len()].copy_from_slice(&blocks);
+
+        Self::gen_checksum(&mut block);
+        block
+    }
+
     fn gen_checksum(data: &mut [u8]) {
         // important, this is the bare minimum length
         assert!(data.len() >= 128);
@@ -103,3 +253,96 @@ impl Edid {
         data[127] = checksum;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use driver_ipc::{BitDepth, ColorPrimaries};
+
+    use super::*;
+
+    fn checksum_ok(edid: &[u8]) -> bool {
+        edid.iter().copied().map(u32::from).sum::<u32>() % 256 == 0
+    }
+
+    #[test]
+    fn default_size() {
+        let edid = Edid::generate_with(3, None, &Color::default());
+
+        assert_eq!(Edid::get_serial(&edid).unwrap(), 3);
+        assert_eq!(edid[MAX_IMAGE_SIZE..][..2], [0x32, 0x1F]);
+        assert_eq!(edid[DTD_IMAGE_SIZE..][..3], [0, 0, 0]);
+        assert!(checksum_ok(&edid));
+    }
+
+    #[test]
+    fn physical_size() {
+        let edid = Edid::generate_with(0, Some((597, 336)), &Color::default());
+
+        assert_eq!(edid[MAX_IMAGE_SIZE..][..2], [60, 34]);
+        // 597 = 0x255, 336 = 0x150
+        assert_eq!(edid[DTD_IMAGE_SIZE..][..3], [0x55, 0x50, 0x21]);
+        assert!(checksum_ok(&edid));
+
+        let edid = Edid::generate_with(0, Some((5000, 2)), &Color::default());
+        assert_eq!(edid[MAX_IMAGE_SIZE..][..2], [255, 1]);
+        assert_eq!(edid[DTD_IMAGE_SIZE..][..3], [0xFF, 0x02, 0xF0]);
+        assert!(checksum_ok(&edid));
+    }
+
+    #[test]
+    fn color() {
+        let edid = Edid::generate_with(0, None, &Color::default());
+        assert_eq!(edid.len(), EDID_LEN);
+        assert_eq!(edid[REVISION], 3);
+        assert_eq!(edid[VIDEO_INPUT], 0x80);
+
+        let color = Color {
+            bit_depth: BitDepth::Ten,
+            format: ColorFormat::YCbCr444,
+            hdr: None,
+        };
+        let edid = Edid::generate_with(0, None, &color);
+        assert_eq!(edid.len(), EDID_LEN);
+        assert_eq!(edid[REVISION], 4);
+        assert_eq!(edid[VIDEO_INPUT], 0xB0);
+        assert_eq!(edid[FEATURES] & 0b1_1000, 0b0_1000);
+        assert_eq!(edid[EXTENSION_COUNT], 0);
+        assert!(checksum_ok(&edid));
+    }
+
+    #[test]
+    fn hdr() {
+        let color = Color {
+            bit_depth: BitDepth::Ten,
+            format: ColorFormat::Rgb,
+            hdr: Some(Hdr {
+                max_luminance: 1000.0,
+                min_luminance: 0.05,
+                primaries: ColorPrimaries::Bt709,
+            }),
+        };
+        let edid = Edid::generate_with(7, None, &color);
+
+        assert_eq!(edid.len(), 2 * EDID_LEN);
+        assert_eq!(Edid::get_serial(&edid).unwrap(), 7);
+        assert_eq!(edid[EXTENSION_COUNT], 1);
+        assert!(checksum_ok(&edid[..EDID_LEN]));
+
+        // red is (0.640, 0.330) = (0b1010001111, 0b0101010010) / 1024 and
+        // white is (0.3127, 0.3290) = (0b0101000000, 0b0101010001) / 1024
+        assert_eq!(edid[CHROMATICITY + 2..][..2], [0b1010_0011, 0b0101_0100]);
+        assert_eq!(edid[CHROMATICITY] >> 4, 0b11_10);
+        assert_eq!(edid[CHROMATICITY + 8..][..2], [0b0101_0000, 0b0101_0100]);
+        assert_eq!(edid[CHROMATICITY + 1] & 0xF, 0b00_01);
+
+        let cta = &edid[EDID_LEN..];
+        assert_eq!(cta[..4], [0x02, 0x03, 15, 0]);
+        // BT2020RGB colorimetry
+        assert_eq!(cta[4..8], [0xE3, 0x05, 0x80, 0x00]);
+        // PQ, 1000 nits is 50 * 2^(138 / 32) and 0.05 nits is
+        // 1000 * (18 / 255)^2 / 100
+        assert_eq!(cta[8..15], [0xE6, 0x06, 0x05, 0x01, 138, 138, 18]);
+        assert!(cta[15..127].iter().all(|&b| b == 0));
+        assert!(checksum_ok(cta));
+    }
+}
@@ -2,6 +2,7 @@ use std::fs::File;
 use std::io::BufWriter;
 use std::time::Instant;
 
+use driver_ipc::Orientation;
 use log::info;
 
 /// BGRA to YUV420 planar converter with pre-allocated buffers.
@@ -75,12 +76,48 @@ impl YuvConverter {
     }
 }
 
+/// Rotate a tightly packed BGRA frame upright.
+///
+/// With a rotated desktop, the OS renders it rotated clockwise by
+/// `orientation` into frames of the unrotated monitor mode. This undoes that
+/// rotation and returns the pixels with their new width and height.
+pub fn rotate_upright(
+    bgra: &[u8],
+    width: u32,
+    height: u32,
+    orientation: Orientation,
+) -> (Vec<u8>, u32, u32) {
+    let (w, h) = (width as usize, height as usize);
+    debug_assert_eq!(bgra.len(), w * h * 4);
+
+    let (out_w, out_h) = orientation.rotate(w, h);
+    let mut out = vec![0u8; bgra.len()];
+
+    for y in 0..out_h {
+        for x in 0..out_w {
+            let (src_x, src_y) = match orientation {
+                Orientation::Landscape => (x, y),
+                Orientation::Portrait => (w - 1 - y, x),
+                Orientation::LandscapeFlipped => (w - 1 - x, h - 1 - y),
+                Orientation::PortraitFlipped => (y, h - 1 - x),
+            };
+
+            let src = (src_y * w + src_x) * 4;
+            let dst = (y * out_w + x) * 4;
+            out[dst..dst + 4].copy_from_slice(&bgra[src..src + 4]);
+        }
+    }
+
+    let (out_w, out_h) = orientation.rotate(width, height);
+    (out, out_w, out_h)
+}
+
 /// MP4 encoder that accepts raw BGRA frames, encodes them via OpenH264, and
 /// muxes the resulting H.264 NAL units into an MP4 container via muxide.
 ///
 /// Typical usage:
 /// ```ignore
-/// let mut enc = Mp4Encoder::new("output.mp4", 1920, 1080, 30)?;
+/// let mut enc = Mp4Encoder::new("output.mp4", 1920, 1080, 30, 2_000_000)?;
 /// enc.encode_frame(&bgra_data)?;
 /// // ... more frames ...
 /// let (path, frames, duration_ms) = enc.finish()?;
@@ -103,13 +140,19 @@ impl Mp4Encoder {
     /// - `output_path`: filesystem path for the output .mp4 file
     /// - `width`, `height`: frame dimensions in pixels (must be even)
     /// - `fps`: target frame rate (used for bitrate calculation and muxer timing)
+    /// - `bitrate`: target bitrate in bits per second
     ///
-    /// The encoder is configured with a bitrate of 2 Mbps and the OpenH264
-    /// library is loaded from the bundled source.
-    pub fn new(output_path: &str, width: u32, height: u32, fps: u32) -> Result<Self, String> {
+    /// The OpenH264 library is loaded from the bundled source.
+    pub fn new(
+        output_path: &str,
+        width: u32,
+        height: u32,
+        fps: u32,
+        bitrate: u32,
+    ) -> Result<Self, String> {
         // Configure OpenH264 encoder
         let config = openh264::encoder::EncoderConfig::new()
-            .set_bitrate_bps(2_000_000)
+            .set_bitrate_bps(bitrate)
             .max_frame_rate(fps as f32);
 
         let api = openh264::OpenH264API::from_source();
@@ -351,7 +394,7 @@ mod tests {
             .flat_map(|_| [255u8, 0, 0, 255])
             .collect();
 
-        let mut enc = Mp4Encoder::new(path_str, width, height, 5).unwrap();
+        let mut enc = Mp4Encoder::new(path_str, width, height, 5, 2_000_000).unwrap();
         enc.encode_frame(&bgra).unwrap();
         let (out_path, frames, _dur) = enc.finish().unwrap();
 
@@ -372,7 +415,7 @@ mod tests {
         let width = 128u32;
         let height = 128u32;
 
-        let mut enc = Mp4Encoder::new(path_str, width, height, 10).unwrap();
+        let mut enc = Mp4Encoder::new(path_str, width, height, 10, 2_000_000).unwrap();
 
         for i in 0..30 {
             let bgra: Vec<u8> = (0..width * height)
@@ -398,7 +441,7 @@ mod tests {
         let path = dir.join("vdd_test_bad_size.mp4");
         let path_str = path.to_str().unwrap();
 
-        let mut enc = Mp4Encoder::new(path_str, 64, 64, 30).unwrap();
+        let mut enc = Mp4Encoder::new(path_str, 64, 64, 30, 2_000_000).unwrap();
         let too_small = vec![0u8; 100];
         let result = enc.encode_frame(&too_small);
         assert!(result.is_err());
@@ -410,4 +453,32 @@ mod tests {
         let _ = enc.finish();
         let _ = std::fs::remove_file(path_str);
     }
+
+    /// A 3x2 frame with one distinct byte per pixel, `a` is top left:
+    ///
+    /// ```text
+    /// a b c
+    /// d e f
+    /// ```
+    fn pixels(frame: &[u8]) -> Vec<u8> {
+        frame.chunks(4).map(|px| px[0]).collect()
+    }
+
+    #[test]
+    fn rotate_upright_frames() {
+        let frame = b"aaaabbbbccccddddeeeeffff";
+
+        let (out, w, h) = rotate_upright(frame, 3, 2, Orientation::Landscape);
+        assert_eq!((pixels(&out), w, h), (b"abcdef".to_vec(), 3, 2));
+
+        // the desktop was rendered rotated clockwise, rotate it back
+        let (out, w, h) = rotate_upright(frame, 3, 2, Orientation::Portrait);
+        assert_eq!((pixels(&out), w, h), (b"cfbead".to_vec(), 2, 3));
+
+        let (out, w, h) = rotate_upright(frame, 3, 2, Orientation::LandscapeFlipped);
+        assert_eq!((pixels(&out), w, h), (b"fedcba".to_vec(), 3, 2));
+
+        let (out, w, h) = rotate_upright(frame, 3, 2, Orientation::PortraitFlipped);
+        assert_eq!((pixels(&out), w, h), (b"daebfc".to_vec(), 2, 3));
+    }
 }
@@ -1,4 +1,7 @@
-use std::time::{Duration, Instant};
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
 
 use driver_logger::DriverLogger;
 use log::{error, info, Level};
@@ -34,11 +37,12 @@ extern "C-unwind" fn DriverEntry(
     // It always starts immediately when the computer is already booted up.
     // If you have a better solution, please by all means open an issue report
     let init_log = || {
-        let mut logger = DriverLogger::new(if cfg!(debug_assertions) {
-            Level::Debug
-        } else {
-            Level::Info
-        });
+        // which records are logged is up to the runtime filter
+        let mut logger = DriverLogger::new(Level::Trace);
+
+        logger
+            .filter(crate::log_filter::log_enabled)
+            .sink(crate::logs::RingLogger);
 
         if cfg!(debug_assertions) {
             logger.debug();
@@ -52,6 +56,8 @@ extern "C-unwind" fn DriverEntry(
             .into();
 
         if status == NTSTATUS::STATUS_SUCCESS {
+            crate::log_filter::init();
+
             info!(
                 "Initialized Virtual Display Driver v{} @ {}",
                 env!("CARGO_PKG_VERSION"),
@@ -109,6 +115,9 @@ extern "C-unwind" fn DriverEntry(
     // set the panic hook to capture and log panics
     crate::panic::set_hook();
 
+    // uptime counts from here
+    LazyLock::force(&crate::health::STARTED);
+
     let mut attributes = WDF_OBJECT_ATTRIBUTES::init();
 
     let mut config = WDF_DRIVER_CONFIG::init(Some(driver_add));
@@ -0,0 +1,66 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        LazyLock,
+    },
+    time::Instant,
+};
+
+use driver_ipc::Health;
+use wdf_umdf_sys::IddMinimumVersionRequired;
+
+use crate::ipc::ADAPTER;
+
+/// When the driver was loaded, forced in `DriverEntry`
+pub static STARTED: LazyLock<Instant> = LazyLock::new(Instant::now);
+/// Connected IPC clients
+pub static CLIENTS: AtomicU32 = AtomicU32::new(0);
+/// Whether the pipe server accepts new clients
+pub static PIPE_SERVER: AtomicBool = AtomicBool::new(false);
+
+/// Sets a flag while alive, and clears it again when dropped, also when a
+/// thread unwinds from a panic
+pub struct Running<'a>(&'a AtomicBool);
+
+impl<'a> Running<'a> {
+    pub fn new(flag: &'a AtomicBool) -> Self {
+        flag.store(true, Ordering::Relaxed);
+        Self(flag)
+    }
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Relaxed);
+    }
+}
+
+/// Counts a connected IPC client until dropped
+pub struct Client;
+
+impl Client {
+    pub fn connect() -> Self {
+        CLIENTS.fetch_add(1, Ordering::Relaxed);
+        Self
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        CLIENTS.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub fn snapshot() -> Health {
+    Health {
+        version: env!("CARGO_PKG_VERSION").to_owned(),
+        git_sha: env!("VERGEN_GIT_SHA").to_owned(),
+        iddcx_min_version: format!("1.{IddMinimumVersionRequired}"),
+        #[allow(clippy::cast_possible_truncation)]
+        uptime_ms: STARTED.elapsed().as_millis() as u64,
+        adapter_initialized: ADAPTER.get().is_some(),
+        clients: CLIENTS.load(Ordering::Relaxed),
+        pipe_server: PIPE_SERVER.load(Ordering::Relaxed),
+        swap_chains: crate::stats::swap_chains(),
+    }
+}
@@ -1,36 +1,44 @@
 use std::{
     collections::HashSet,
     mem::size_of,
-    ptr::{addr_of_mut, NonNull},
-    sync::{LazyLock, Mutex, OnceLock},
+    ptr::NonNull,
+    sync::{Arc, LazyLock, Mutex, OnceLock},
     thread,
+    time::Instant,
 };
 
 use driver_ipc::{
-    Dimen, DriverCommand, EventCommand, Mode, Monitor, RefreshRate, ReplyCommand,
-    RequestCommand, ServerCommand,
+    backend::DriverCore,
+    codec::{error::FrameError, FrameBuffer},
+    rate_limit::RateLimit,
+    Access, DenyReason, DriverCommand, DriverConfig, Encoding, EventCommand, EventFilter,
+    LogFilter, Monitor, PermissionDenied, ProtocolError, ReplyCommand, RequestCommand,
+    ServerCommand,
 };
-use log::{error, warn};
+use log::{error, info, warn};
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt as _},
     net::windows::named_pipe::{NamedPipeServer, ServerOptions},
     sync::broadcast::{self, error::RecvError, Sender},
-    task,
+    task, time,
 };
-use wdf_umdf::IddCxMonitorDeparture;
 use wdf_umdf_sys::{IDDCX_ADAPTER__, IDDCX_MONITOR__};
-use windows::Win32::{
-    Security::{
-        InitializeSecurityDescriptor, SetSecurityDescriptorDacl, PSECURITY_DESCRIPTOR,
-        SECURITY_ATTRIBUTES, SECURITY_DESCRIPTOR,
-    },
-    System::SystemServices::SECURITY_DESCRIPTOR_REVISION1,
-};
+use windows::Win32::Security::SECURITY_ATTRIBUTES;
 
-use crate::context::DeviceContext;
-use crate::recording::{RecordingConfig, RecordingSession};
+use crate::backend::IddCxBackend;
+use crate::config::config;
+use crate::logs::Subscription;
+use crate::recording::RecordingSession;
+use crate::security::{self, SecurityDescriptor};
 
 pub static ADAPTER: OnceLock<AdapterObject> = OnceLock::new();
+/// Monitors and recording state as seen by the clients
+///
+/// Never locked by the IddCx callbacks, so it can be held while calling into
+/// IddCx, unlike [MONITOR_MODES].
+pub static CORE: LazyLock<Mutex<DriverCore<IddCxBackend>>> =
+    LazyLock::new(|| Mutex::new(DriverCore::new(IddCxBackend, config().max_monitors)));
+/// The connected monitors, see [IddCxBackend]
 pub static MONITOR_MODES: LazyLock<Mutex<Vec<MonitorObject>>> =
     LazyLock::new(|| Mutex::new(Vec::new()));
 pub static RECORDING_STATE: LazyLock<Mutex<RecordingState>> =
@@ -56,14 +64,6 @@ impl RecordingState {
     pub fn is_recording(&self, monitor_id: u32) -> bool {
         self.active && (self.monitor_ids.is_empty() || self.monitor_ids.contains(&monitor_id))
     }
-
-    pub fn shm_names(&self) -> Vec<String> {
-        let lock = MONITOR_MODES.lock().unwrap();
-        lock.iter()
-            .filter(|m| self.is_recording(m.data.id))
-            .map(|m| format!("Global\\VDD_Frame_{}", m.data.id))
-            .collect()
-    }
 }
 
 #[derive(Debug)]
@@ -79,10 +79,6 @@ pub struct MonitorObject {
 unsafe impl Sync for MonitorObject {}
 unsafe impl Send for MonitorObject {}
 
-const BUFFER_SIZE: u32 = 4096;
-// EOT
-const EOF: char = '\x04';
-
 /// DEADEND: SendInput from UMDF driver process (Session 0) does not work.
 /// OpenInputDesktop fails with ERROR_INVALID_FUNCTION (0x80070001) because the
 /// UMDF host process has no interactive desktop. SendInput returns 0.
@@ -193,230 +189,229 @@ fn send_wake_keypress_inner() {
     }
 }
 
-// message processor
-async fn process_message(
-    id: usize,
-    server: &mut NamedPipeServer,
-    tx: &Sender<(usize, Vec<Monitor>)>,
-    buf: &[u8],
-    iter: impl Iterator<Item = usize>,
-) -> Result<(), ()> {
-    // process each message in the buffer
-    let mut start = 0;
-    for eidx in iter {
-        let sidx = start;
-        start = eidx + 1;
+/// Events a client subscribed to, see `RequestCommand::Subscribe` and
+/// `RequestCommand::SubscribeLogs`
+#[derive(Default)]
+struct Events {
+    filter: EventFilter,
+    logs: Option<Subscription>,
+    /// Monitors of the last `Changed` event sent, to skip events which don't
+    /// change any of the subscribed monitors
+    last_changed: Option<Vec<Monitor>>,
+}
 
-        crate::swap_chain_processor::trace_log(&format!(
-            "IPC: Processing message bytes [{sidx}..{eidx}] ({} bytes)", eidx - sidx
-        ));
+impl Events {
+    fn subscribe(&mut self, filter: EventFilter) {
+        self.filter = filter;
+        self.last_changed = None;
+    }
 
-        let Ok(msg) = std::str::from_utf8(&buf[sidx..eidx]) else {
-            crate::swap_chain_processor::trace_log(&format!(
-                "IPC: UTF-8 decode failed for bytes [{sidx}..{eidx}]"
-            ));
-            continue;
-        };
+    /// The part of `event` to send the client, if any
+    fn filter(&mut self, event: EventCommand) -> Option<EventCommand> {
+        let event = self.filter.apply(event)?;
 
-        // Strip UTF-8 BOM if present (PowerShell StreamWriter adds it)
-        let msg = msg.trim_start_matches('\u{FEFF}');
-        crate::swap_chain_processor::trace_log(&format!(
-            "IPC: Raw message text ({} chars): {msg}", msg.len()
-        ));
+        if let (EventCommand::Changed(monitors), Some(_)) = (&event, &self.filter.monitor_ids) {
+            if self.last_changed.as_ref() == Some(monitors) {
+                return None;
+            }
+            self.last_changed = Some(monitors.clone());
+        }
 
-        let Ok(command) = serde_json::from_str::<ServerCommand>(msg) else {
-            crate::swap_chain_processor::trace_log(&format!(
-                "IPC: DESERIALIZE FAILED for message: {msg}"
-            ));
-            continue;
-        };
-        crate::swap_chain_processor::trace_log(&format!("IPC: Deserialized command: {command:?}"));
-
-        match command {
-            // driver commands
-            ServerCommand::Driver(cmd) => match cmd {
-                DriverCommand::Notify(monitors) => {
-                    notify(monitors.clone());
-                    _ = tx.send((id, monitors));
-                }
+        Some(event)
+    }
+}
 
-                DriverCommand::Remove(ids) => {
-                    remove(&ids);
+/// Why a client is disconnected
+enum Disconnect {
+    /// The pipe broke, nothing can be sent anymore
+    Broken,
+    /// The client sent something invalid, which it is told before
+    Protocol(ProtocolError),
+}
 
-                    let lock = MONITOR_MODES.lock().unwrap();
-                    let monitors = lock.iter().map(|m| m.data.clone()).collect();
-                    _ = tx.send((id, monitors));
-                }
+/// Write `reply` to the client
+///
+/// A reply which fails to serialize is logged and skipped, only a broken pipe
+/// ends the connection.
+async fn send_reply(
+    server: &mut NamedPipeServer,
+    encoding: Encoding,
+    reply: &ReplyCommand,
+) -> Result<(), Disconnect> {
+    let data = match encoding.encode(reply) {
+        Ok(data) => data,
+        Err(e) => {
+            error!("IPC: failed to serialize reply: {e}");
+            return Ok(());
+        }
+    };
 
-                DriverCommand::RemoveAll => {
-                    remove_all();
-                    _ = tx.send((id, Vec::new()));
-                }
+    server
+        .write_all(&data)
+        .await
+        .map_err(|_| Disconnect::Broken)
+}
 
-                DriverCommand::StartRecording { monitor_ids, output_path, fps } => {
-                    crate::swap_chain_processor::trace_log(&format!(
-                        "IPC: StartRecording monitor_ids={monitor_ids:?} output_path={output_path:?} fps={fps:?}"
-                    ));
+/// Tell the client that its command was refused
+async fn deny(
+    server: &mut NamedPipeServer,
+    encoding: Encoding,
+    denied: PermissionDenied,
+) -> Result<(), Disconnect> {
+    warn!("IPC: {denied}");
 
-                    // Scoped block so MutexGuard is dropped before any .await
-                    let reply = {
-                        let mut state = RECORDING_STATE.lock().unwrap();
-                        crate::swap_chain_processor::trace_log("IPC: StartRecording acquired RECORDING_STATE lock");
+    send_reply(server, encoding, &ReplyCommand::PermissionDenied(denied)).await
+}
 
-                        // Stop any existing recording first
-                        if let Some(old_session) = state.session.take() {
-                            crate::swap_chain_processor::trace_log("IPC: Stopping previous recording");
-                            let _ = old_session.stop();
-                            crate::swap_chain_processor::trace_log("IPC: Previous recording stopped");
-                        }
+// message processor, handles the payload of a single frame
+async fn process_message(
+    id: usize,
+    server: &mut NamedPipeServer,
+    tx: &Sender<(usize, EventCommand)>,
+    encoding: &mut Encoding,
+    access: Access,
+    events: &mut Events,
+    msg: &[u8],
+) -> Result<(), Disconnect> {
+    crate::swap_chain_processor::trace_log(&format!(
+        "IPC: Processing {encoding:?} message ({} bytes)", msg.len()
+    ));
 
-                        state.active = true;
-                        state.monitor_ids = monitor_ids.into_iter().collect();
-                        crate::swap_chain_processor::trace_log(&format!(
-                            "IPC: Set active=true, monitor_ids={:?}", state.monitor_ids
-                        ));
-
-                        // Start MP4 recording if output path provided
-                        if let Some(path) = output_path {
-                            crate::swap_chain_processor::trace_log(&format!(
-                                "IPC: Creating RecordingSession path={path:?} fps={fps:?}"
-                            ));
-                            let config = RecordingConfig {
-                                output_path: path,
-                                fps: fps.unwrap_or(5),
-                            };
-                            state.session = Some(RecordingSession::start(config));
-                            crate::swap_chain_processor::trace_log("IPC: RecordingSession created");
-                        } else {
-                            crate::swap_chain_processor::trace_log("IPC: No output_path — no RecordingSession created");
-                        }
+    // only bytes which can't be decoded at all end the connection, a command
+    // of a newer client is answered and skipped
+    let command = match encoding.decode_command(msg) {
+        Ok(command) => command,
+        Err(e) if e.disconnects() => return Err(Disconnect::Protocol(e)),
+        Err(e) => {
+            warn!("IPC: {e}");
+            return send_reply(server, *encoding, &ReplyCommand::ProtocolError(e)).await;
+        }
+    };
+    crate::swap_chain_processor::trace_log(&format!("IPC: Deserialized command: {command:?}"));
 
-                        crate::swap_chain_processor::trace_log(&format!(
-                            "IPC: RecordingState now active={}, monitors={:?}, has_session={}",
-                            state.active, state.monitor_ids, state.session.is_some()
-                        ));
+    let required = command.required_access();
+    if access < required {
+        let denied = PermissionDenied {
+            command: command.name().to_owned(),
+            reason: DenyReason::Access { required },
+        };
+        return deny(server, *encoding, denied).await;
+    }
 
-                        ReplyCommand::RecordingStarted {
-                            active: state.active,
-                            monitor_ids: state.monitor_ids.iter().copied().collect(),
-                            has_session: state.session.is_some(),
-                        }
-                        // MutexGuard dropped here
-                    };
-
-                    if let Ok(mut data) = serde_json::to_string(&reply) {
-                        data.push(EOF);
-                        crate::swap_chain_processor::trace_log(&format!(
-                            "IPC: Sending RecordingStarted reply ({} bytes)", data.len()
-                        ));
-                        let _ = server.write_all(data.as_bytes()).await;
-                        crate::swap_chain_processor::trace_log("IPC: RecordingStarted reply sent");
-                    } else {
-                        crate::swap_chain_processor::trace_log("IPC: ERROR — failed to serialize RecordingStarted reply");
-                    }
+    match command {
+        ServerCommand::Driver(DriverCommand::SetLogLevel { level, targets }) => {
+            let filter = LogFilter { level, targets };
+            info!("Log filter changed to {filter:?}");
 
-                    // Wake the display by sending a keypress — IddCx only activates
-                    // display paths when the display is awake
-                    send_wake_keypress();
-                }
+            crate::log_filter::set(filter);
+        }
 
-                DriverCommand::StopRecording => {
-                    crate::swap_chain_processor::trace_log("IPC: StopRecording");
-
-                    // Take session out of state in a limited scope so the
-                    // MutexGuard is dropped before any .await
-                    let session = {
-                        let mut state = RECORDING_STATE.lock().unwrap();
-                        state.active = false;
-                        state.monitor_ids.clear();
-                        state.session.take()
-                    };
-
-                    // Stop recording session and ALWAYS send a reply (even with 0 frames)
-                    let reply = if let Some(session) = session {
-                        match session.stop() {
-                            Some(result) => ReplyCommand::RecordingFinished {
-                                path: result.path,
-                                frames: result.frames,
-                                duration_ms: result.duration_ms,
-                            },
-                            None => ReplyCommand::RecordingFinished {
-                                path: String::new(),
-                                frames: 0,
-                                duration_ms: 0,
+        // driver commands
+        ServerCommand::Driver(mut command) => {
+            // only write where the config allows it, the driver runs
+            // with more rights than the client
+            if let DriverCommand::StartRecording { output_path: Some(path), .. } = &mut command {
+                match security::check_recording_path(&config(), path) {
+                    Ok(resolved) => *path = resolved.to_string_lossy().into_owned(),
+                    Err(reason) => {
+                        let denied = PermissionDenied {
+                            command: "StartRecording".to_owned(),
+                            reason: DenyReason::RecordingPath {
+                                path: path.clone(),
+                                reason,
                             },
-                        }
-                    } else {
-                        ReplyCommand::RecordingFinished {
-                            path: String::new(),
-                            frames: 0,
-                            duration_ms: 0,
-                        }
-                    };
-
-                    crate::swap_chain_processor::trace_log(&format!(
-                        "IPC: StopRecording reply: {reply:?}"
-                    ));
-
-                    if let Ok(mut data) = serde_json::to_string(&reply) {
-                        data.push(EOF);
-                        let _ = server.write_all(data.as_bytes()).await;
+                        };
+                        return deny(server, *encoding, denied).await;
                     }
                 }
+            }
 
-                _ => (),
-            },
+            let start_recording = matches!(command, DriverCommand::StartRecording { .. });
 
-            // request commands
-            ServerCommand::Request(RequestCommand::State) => {
-                let mut data = {
-                    let lock = MONITOR_MODES.lock().unwrap();
-                    let monitors = lock.iter().map(|m| m.data.clone()).collect();
-                    let command = ReplyCommand::State(monitors);
+            // the lock is released before any .await
+            let outcome = CORE.lock().unwrap().handle(command);
 
-                    let Ok(serialized) = serde_json::to_string(&command) else {
-                        error!("Command::Request - failed to serialize reply");
-                        break;
-                    };
+            if let Some(event) = outcome.event {
+                _ = tx.send((id, event));
+            }
 
-                    serialized
-                };
+            if let Some(reply) = outcome.reply {
+                crate::swap_chain_processor::trace_log(&format!("IPC: Reply: {reply:?}"));
 
-                data.push(EOF);
+                send_reply(server, *encoding, &reply).await?;
+            }
 
-                if server.write_all(data.as_bytes()).await.is_err() {
-                    // a server error means we should completely stop trying
-                    return Err(());
-                }
+            // Wake the display by sending a keypress — IddCx only activates
+            // display paths when the display is awake
+            if start_recording {
+                send_wake_keypress();
             }
+        }
 
-            ServerCommand::Request(RequestCommand::RecordingState) => {
-                let mut data = {
-                    let state = RECORDING_STATE.lock().unwrap();
-                    let command = ReplyCommand::RecordingState {
-                        active: state.active,
-                        monitor_ids: state.monitor_ids.iter().copied().collect(),
-                        shm_names: state.shm_names(),
-                    };
-
-                    let Ok(serialized) = serde_json::to_string(&command) else {
-                        error!("Command::Request - failed to serialize recording state reply");
-                        break;
-                    };
-
-                    serialized
-                };
+        // request commands
+        ServerCommand::Request(
+            request @ (RequestCommand::State
+            | RequestCommand::RecordingState
+            | RequestCommand::Capabilities),
+        ) => {
+            // the lock is released before any .await
+            let reply = CORE.lock().unwrap().request(&request);
+            if let Some(reply) = reply {
+                send_reply(server, *encoding, &reply).await?;
+            }
+        }
 
-                data.push(EOF);
+        ServerCommand::Request(RequestCommand::Config) => {
+            let reply = ReplyCommand::Config(DriverConfig::clone(&config()));
+            send_reply(server, *encoding, &reply).await?;
+        }
 
-                if server.write_all(data.as_bytes()).await.is_err() {
-                    return Err(());
-                }
-            }
+        ServerCommand::Request(RequestCommand::Stats) => {
+            let reply = ReplyCommand::Stats(crate::stats::snapshot());
+            send_reply(server, *encoding, &reply).await?;
+        }
+
+        ServerCommand::Request(RequestCommand::Health) => {
+            let reply = ReplyCommand::Health(crate::health::snapshot());
+            send_reply(server, *encoding, &reply).await?;
+        }
+
+        ServerCommand::Request(RequestCommand::Logs { since, level }) => {
+            let reply = ReplyCommand::Logs(crate::logs::records(since, level));
+            send_reply(server, *encoding, &reply).await?;
+        }
+
+        ServerCommand::Request(RequestCommand::SubscribeLogs(level)) => {
+            events.logs = level.map(Subscription::new);
+        }
+
+        ServerCommand::Request(RequestCommand::Access) => {
+            send_reply(server, *encoding, &ReplyCommand::Access(access)).await?;
+        }
+
+        ServerCommand::Request(RequestCommand::Subscribe { kinds, monitor_ids }) => {
+            events.subscribe(EventFilter { kinds, monitor_ids });
+        }
+
+        ServerCommand::Request(RequestCommand::SetEncoding(requested)) => {
+            // the answer still uses the old encoding, everything after it
+            // the new one
+            let new = if requested.is_supported() {
+                requested
+            } else {
+                Encoding::Json
+            };
+
+            send_reply(server, *encoding, &ReplyCommand::Encoding(new)).await?;
 
-            // Everything else is an invalid command
-            _ => (),
+            crate::swap_chain_processor::trace_log(&format!(
+                "IPC: Switched encoding from {encoding:?} to {new:?} (requested {requested:?})"
+            ));
+            *encoding = new;
         }
+
+        // Everything else is an invalid command
+        _ => (),
     }
 
     Ok(())
@@ -425,32 +420,34 @@ async fn process_message(
 #[allow(clippy::too_many_lines)]
 pub fn startup() {
     thread::spawn(move || {
-        // These security attributes will allow anyone access, so local account does not need admin privileges to use it
-
-        let mut sd = SECURITY_DESCRIPTOR::default();
-
-        unsafe {
-            InitializeSecurityDescriptor(
-                PSECURITY_DESCRIPTOR(addr_of_mut!(sd).cast()),
-                SECURITY_DESCRIPTOR_REVISION1,
-            )
-            .unwrap();
-        }
-
-        unsafe {
-            SetSecurityDescriptorDacl(
-                PSECURITY_DESCRIPTOR(addr_of_mut!(sd).cast()),
-                true,
-                None,
-                false,
-            )
-            .unwrap();
-        }
+        // only accounts with read or control access may connect, the access
+        // lists are only read once, changing them requires a restart
+        let config = config();
+        let control = Arc::new(security::resolve(&config.control_access));
+        let mut allowed = security::resolve(&config.read_access);
+        allowed.extend(security::resolve(&config.control_access));
+
+        // without the configured accounts, only the owner and SYSTEM may
+        // connect, which is safer than no pipe server or one open to everyone
+        let descriptor = match SecurityDescriptor::allow(&allowed) {
+            Ok(descriptor) => descriptor,
+            Err(e) => {
+                error!("Failed to allow the configured accounts on the pipe: {e}");
+
+                match SecurityDescriptor::allow(&[]) {
+                    Ok(descriptor) => descriptor,
+                    Err(e) => {
+                        error!("Failed to build pipe security descriptor: {e}");
+                        return;
+                    }
+                }
+            }
+        };
 
         let mut sa = SECURITY_ATTRIBUTES {
             #[allow(clippy::cast_possible_truncation)]
             nLength: size_of::<SECURITY_ATTRIBUTES>() as u32,
-            lpSecurityDescriptor: addr_of_mut!(sd).cast(),
+            lpSecurityDescriptor: descriptor.as_ptr(),
             bInheritHandle: false.into(),
         };
 
@@ -460,21 +457,27 @@ pub fn startup() {
                 "=== VDD CANARY 2026-02-27-B === Pipe server starting (tokio async loop, with SendInput wake)"
             );
 
-            let (tx, _rx) = broadcast::channel(1);
+            // the pipe name and buffer size are only read once, changing them requires a restart
+            let pipe_name = format!(r"\\.\pipe\{}", config.pipe_name);
+            let buffer_size = config.buffer_size;
+
+            let (tx, _rx) = broadcast::channel(config.event_capacity as usize);
 
             let mut id = 0usize;
 
+            let _running = crate::health::Running::new(&crate::health::PIPE_SERVER);
+
             loop {
                 let mut server = unsafe {
                     ServerOptions::new()
                         .access_inbound(true)
                         .access_outbound(true)
                         .reject_remote_clients(true)
-                        .in_buffer_size(BUFFER_SIZE)
-                        .out_buffer_size(BUFFER_SIZE)
+                        .in_buffer_size(buffer_size)
+                        .out_buffer_size(buffer_size)
                         // default is unlimited instances
                         .create_with_security_attributes_raw(
-                            r"\\.\pipe\virtualdisplaydriver",
+                            &pipe_name,
                             std::ptr::from_mut::<SECURITY_ATTRIBUTES>(&mut sa).cast(),
                         )
                         .unwrap()
@@ -490,13 +493,24 @@ pub fn startup() {
                     "IPC: Client #{id} connected to pipe"
                 ));
 
-                let mut msg_buf: Vec<u8> = Vec::with_capacity(BUFFER_SIZE as usize);
-                let mut buf = vec![0; BUFFER_SIZE as usize];
+                // the limits are read for every connection
+                let limits = crate::config::config();
+                let mut frames = FrameBuffer::new(limits.max_message_size as usize);
+                let mut rate_limit = RateLimit::new(limits.rate_limit, Instant::now());
+                let mut buf = vec![0; buffer_size as usize];
+                let mut encoding = Encoding::Json;
+                // known once the client sent something, see `security::client_access`
+                let mut access = None;
+                let control = control.clone();
+                let mut events = Events::default();
                 let tx = tx.clone();
                 let mut rx = tx.subscribe();
 
                 let client_id = id;
+                let connected = crate::health::Client::connect();
                 task::spawn(async move {
+                    let _connected = connected;
+
                     loop {
                         tokio::select! {
                             val = server.read(&mut buf) =>  {
@@ -518,32 +532,63 @@ pub fn startup() {
 
                                     Ok(size) => {
                                         crate::swap_chain_processor::trace_log(&format!(
-                                            "IPC: Client #{client_id} read {size} bytes, msg_buf total={}",
-                                            msg_buf.len() + size
+                                            "IPC: Client #{client_id} read {size} bytes, buffered={}",
+                                            frames.len() + size
                                         ));
-                                        msg_buf.extend(&buf[..size]);
+                                        frames.push(&buf[..size]);
                                     }
                                 }
 
-                                // get all eof boundary positions
-                                let eof_iter = msg_buf.iter().enumerate().filter_map(|(i, &byte)| {
-                                    if byte == EOF as u8 {
-                                        Some(i)
-                                    } else {
-                                        None
-                                    }
+                                let access = *access.get_or_insert_with(|| {
+                                    security::client_access(&server, &control)
                                 });
 
-                                if process_message(id, &mut server, &tx, &msg_buf, eof_iter.clone()).await.is_err() {
-                                    break;
-                                }
+                                // process each message, one at a time as each
+                                // one may switch the encoding of the following
+                                // ones
+                                let result = loop {
+                                    let msg = match frames.next(encoding) {
+                                        Ok(Some(msg)) => msg,
+                                        Ok(None) => break Ok(()),
+                                        Err(FrameError::TooLarge(max)) => {
+                                            let max = u32::try_from(max).unwrap_or(u32::MAX);
+                                            let e = ProtocolError::TooLarge { max };
+                                            break Err(Disconnect::Protocol(e));
+                                        }
+                                    };
+
+                                    // a client sending too fast is not read
+                                    // from while it waits
+                                    let wait = rate_limit.take(Instant::now());
+                                    if !wait.is_zero() {
+                                        time::sleep(wait).await;
+                                    }
+
+                                    let processed = process_message(
+                                        id,
+                                        &mut server,
+                                        &tx,
+                                        &mut encoding,
+                                        access,
+                                        &mut events,
+                                        msg,
+                                    )
+                                    .await;
+                                    if processed.is_err() {
+                                        break processed;
+                                    }
+                                };
 
-                                // remove processed messages from buffer
-                                // we can exploit the fact that these are sequential
-                                // so just get the last index and chop off everything before that
-                                if let Some(last) = eof_iter.last() {
-                                    // remove everything up to and including the last EOF
-                                    msg_buf.drain(..=last);
+                                match result {
+                                    Ok(()) => (),
+                                    Err(Disconnect::Broken) => break,
+                                    Err(Disconnect::Protocol(e)) => {
+                                        warn!("IPC: Disconnecting client #{client_id}: {e}");
+
+                                        let reply = ReplyCommand::ProtocolError(e);
+                                        _ = send_reply(&mut server, encoding, &reply).await;
+                                        break;
+                                    }
                                 }
                             },
 
@@ -552,22 +597,41 @@ pub fn startup() {
                                     // ignore if this value was sent for the current client (current client doesn't need notification)
                                     Ok((client_id, _)) if client_id == id => continue,
 
-                                    Ok((_, data)) => EventCommand::Changed(data),
+                                    Ok((_, event)) => event,
 
-                                    Err(RecvError::Lagged(_)) => continue,
+                                    // missed some changes, catch up with the current state
+                                    Err(RecvError::Lagged(_)) => {
+                                        EventCommand::Changed(CORE.lock().unwrap().monitors())
+                                    }
 
                                     // closed
                                     Err(_) => break
                                 };
 
-                                let Ok(mut serialized) = serde_json::to_string(&command) else {
+                                let Some(command) = events.filter(command) else {
+                                    continue;
+                                };
+
+                                let Ok(serialized) = encoding.encode(&command) else {
                                     error!("Command::Request - failed to serialize reply");
                                     break;
                                 };
 
-                                serialized.push(EOF);
+                                if server.write_all(&serialized).await.is_err() {
+                                    break;
+                                }
+                            }
+
+                            record = crate::logs::next(&mut events.logs) => {
+                                let Some(command) = events.filter(EventCommand::Log(record)) else {
+                                    continue;
+                                };
+                                let Ok(serialized) = encoding.encode(&command) else {
+                                    error!("Command::Request - failed to serialize log event");
+                                    break;
+                                };
 
-                                if server.write_all(serialized.as_bytes()).await.is_err() {
+                                if server.write_all(&serialized).await.is_err() {
                                     break;
                                 }
                             }
@@ -585,212 +649,7 @@ pub fn startup() {
     });
 }
 
-/// used to check the validity of a Vec<Monitor>
-/// the validity invariants are:
-/// 1. unique monitor ids
-/// 2. unique monitor modes (width+height must be unique per array element)
-/// 3. unique refresh rates per monitor mode
-fn has_duplicates(monitors: &[Monitor]) -> bool {
-    let mut monitor_iter = monitors.iter();
-    while let Some(monitor) = monitor_iter.next() {
-        let duplicate_id = monitor_iter.clone().any(|b| monitor.id == b.id);
-        if duplicate_id {
-            warn!("Found duplicate monitor id {}", monitor.id);
-            return true;
-        }
-
-        let mut mode_iter = monitor.modes.iter();
-        while let Some(mode) = mode_iter.next() {
-            let duplicate_mode = mode_iter
-                .clone()
-                .any(|m| mode.height == m.height && mode.width == m.width);
-            if duplicate_mode {
-                warn!(
-                    "Found duplicate mode {}x{} on monitor {}",
-                    mode.width, mode.height, monitor.id
-                );
-                return true;
-            }
-
-            let mut refresh_iter = mode.refresh_rates.iter().copied();
-            while let Some(rr) = refresh_iter.next() {
-                let duplicate_rr = refresh_iter.clone().any(|r| rr == r);
-                if duplicate_rr {
-                    warn!(
-                        "Found duplicate refresh rate {rr} on mode {}x{} for monitor {}",
-                        mode.width, mode.height, monitor.id
-                    );
-                    return true;
-                }
-            }
-        }
-    }
-
-    false
-}
-
-/// Notifies driver of new system monitor state
-///
-/// Adds, updates, or removes monitors as needed
-///
-/// Note that updated monitors causes a detach, update, and reattach. (Required for windows to see the changes)
-///
-/// Only detaches/reattaches if required
-/// e.g. only a monitor name update would not detach/arrive a monitor
-fn notify(monitors: Vec<Monitor>) {
-    // Duplicated id's will not cause any issue, however duplicated resolutions/refresh rates are possible
-    // They should all be unique anyways. So warn + noop if the sender sent incorrect data
-    if has_duplicates(&monitors) {
-        warn!("notify(): Duplicate data was detected; update aborted");
-        return;
-    }
-
-    let adapter = ADAPTER.get().unwrap().0.as_ptr();
-
-    let mut lock = MONITOR_MODES.lock().unwrap();
-
-    // Remove monitors from internal list which are missing from the provided list
-
-    lock.retain_mut(|mon| {
-        let id = mon.data.id;
-        let found = monitors.iter().any(|m| m.id == id);
-
-        // if it doesn't exist, then add to removal list
-        if !found {
-            // monitor not found in monitors list, so schedule to remove it
-            if let Some(mut obj) = mon.object.take() {
-                // remove any monitors scheduled for removal
-                let obj = unsafe { obj.as_mut() };
-                if let Err(e) = unsafe { IddCxMonitorDeparture(obj) } {
-                    error!("Failed to remove monitor: {e:?}");
-                }
-            }
-        }
-
-        found
-    });
-
-    let should_arrive = monitors
-        .into_iter()
-        .map(|monitor| {
-            let id = monitor.id;
-
-            let should_arrive;
-
-            let cur_mon = lock.iter_mut().find(|mon| mon.data.id == id);
-
-            if let Some(mon) = cur_mon {
-                let modes_changed = mon.data.modes != monitor.modes;
-
-                #[allow(clippy::nonminimal_bool)]
-                {
-                    should_arrive =
-                        // previously was disabled, and it was just enabled
-                        (!mon.data.enabled && monitor.enabled) ||
-                        // OR monitor is enabled and the display modes changed
-                        (monitor.enabled && modes_changed) ||
-                        // OR monitor is enabled and the monitor was disconnected
-                        (monitor.enabled && mon.object.is_none());
-                }
-
-                // should only detach if modes changed, or if state is false
-                if modes_changed || !monitor.enabled {
-                    if let Some(mut obj) = mon.object.take() {
-                        let obj = unsafe { obj.as_mut() };
-                        if let Err(e) = unsafe { IddCxMonitorDeparture(obj) } {
-                            error!("Failed to remove monitor: {e:?}");
-                        }
-                    }
-                }
-
-                // update monitor data
-                mon.data = monitor;
-            } else {
-                should_arrive = monitor.enabled;
-
-                lock.push(MonitorObject {
-                    object: None,
-                    data: monitor,
-                });
-            }
-
-            (id, should_arrive)
-        })
-        .collect::<Vec<_>>();
-
-    // context.create_monitor locks again, so this avoids deadlock
-    drop(lock);
-
-    let cb = |context: &mut DeviceContext| {
-        // arrive any monitors that need arriving
-        for (id, arrive) in should_arrive {
-            if arrive {
-                if let Err(e) = context.create_monitor(id) {
-                    error!("Failed to create monitor: {e:?}");
-                }
-            }
-        }
-    };
-
-    unsafe {
-        DeviceContext::get_mut(adapter.cast(), cb).unwrap();
-    }
-}
-
-fn remove_all() {
-    let mut lock = MONITOR_MODES.lock().unwrap();
-
-    for monitor in lock.drain(..) {
-        if let Some(mut monitor_object) = monitor.object {
-            let obj = unsafe { monitor_object.as_mut() };
-            if let Err(e) = unsafe { IddCxMonitorDeparture(obj) } {
-                error!("Failed to remove monitor: {e:?}");
-            }
-        }
-    }
-}
-
-fn remove(ids: &[u32]) {
-    let mut lock = MONITOR_MODES.lock().unwrap();
-
-    for &id in ids {
-        lock.retain_mut(|monitor| {
-            if id == monitor.data.id {
-                if let Some(mut monitor_object) = monitor.object.take() {
-                    let obj = unsafe { monitor_object.as_mut() };
-                    if let Err(e) = unsafe { IddCxMonitorDeparture(obj) } {
-                        error!("Failed to remove monitor: {e:?}");
-                    }
-                }
-
-                false
-            } else {
-                true
-            }
-        });
-    }
-}
-
-pub trait FlattenModes {
-    fn flatten(&self) -> impl Iterator<Item = ModeItem>;
-}
-
-#[derive(Copy, Clone)]
-pub struct ModeItem {
-    pub width: Dimen,
-    pub height: Dimen,
-    pub refresh_rate: RefreshRate,
-}
-
-/// Takes a slice of modes and creates a flattened structure that can be iterated over
-impl FlattenModes for Vec<Mode> {
-    fn flatten(&self) -> impl Iterator<Item = ModeItem> {
-        self.iter().flat_map(|m| {
-            m.refresh_rates.iter().map(|&rr| ModeItem {
-                width: m.width,
-                height: m.height,
-                refresh_rate: rr,
-            })
-        })
-    }
+/// Notifies driver of new system monitor state, see [DriverCore]
+pub fn notify(monitors: Vec<Monitor>) {
+    CORE.lock().unwrap().handle(DriverCommand::Notify(monitors));
 }
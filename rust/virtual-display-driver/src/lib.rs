@@ -1,15 +1,23 @@
 mod helpers;
 
+mod backend;
 mod callbacks;
+mod config;
 mod context;
 mod direct_3d_device;
 mod edid;
 mod entry;
+mod health;
 mod ipc;
+mod log_filter;
+mod logs;
 mod panic;
 mod encoder;
+mod pixel_format;
 mod recording;
+mod security;
 mod shared_memory;
+mod stats;
 mod swap_chain_processor;
 
 use wdf_umdf_sys::{NTSTATUS, PUNICODE_STRING, PVOID};
@@ -0,0 +1,94 @@
+use std::{collections::BTreeMap, fs, io, sync::RwLock};
+
+use driver_ipc::{LogFilter, LogLevel};
+use log::{warn, LevelFilter, Metadata};
+
+use crate::config::{config, log_filter_path};
+
+/// Which records are logged, see `DriverCommand::SetLogLevel`. Applies to
+/// `trace_log` as well.
+static FILTER: RwLock<LogFilter> = RwLock::new(LogFilter {
+    level: LogLevel::Info,
+    targets: BTreeMap::new(),
+});
+
+/// Whether a record of `target` at `level` is logged
+pub fn enabled(target: &str, level: LogLevel) -> bool {
+    FILTER.read().unwrap().enabled(target, level)
+}
+
+/// Filter of `DriverLogger`
+pub fn log_enabled(metadata: &Metadata) -> bool {
+    enabled(metadata.target(), crate::logs::log_level(metadata.level()))
+}
+
+/// Apply the filter the driver starts with
+///
+/// This is the one kept by the last `SetLogLevel` if `persist_log_filter` is
+/// configured, otherwise the configured `log_filter`.
+pub fn init() {
+    let config = config();
+
+    let persisted = if config.persist_log_filter {
+        read()
+    } else {
+        None
+    };
+
+    apply(persisted.unwrap_or_else(|| config.log_filter.clone()));
+}
+
+/// Replace the filter
+pub fn apply(filter: LogFilter) {
+    // `log` macros skip records above the max level before asking the logger
+    log::set_max_level(level_filter(filter.max_level()));
+    *FILTER.write().unwrap() = filter;
+}
+
+/// Replace the filter as requested by a client, and keep it for the next
+/// driver start if `persist_log_filter` is configured
+pub fn set(filter: LogFilter) {
+    if config().persist_log_filter {
+        if let Err(e) = write(&filter) {
+            warn!("Failed to persist log filter: {e}");
+        }
+    }
+
+    apply(filter);
+}
+
+fn read() -> Option<LogFilter> {
+    let path = log_filter_path();
+
+    let json = match fs::read_to_string(&path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Failed to read log filter {}: {e}", path.display());
+            return None;
+        }
+    };
+
+    match serde_json::from_str(&json) {
+        Ok(filter) => Some(filter),
+        Err(e) => {
+            warn!("Invalid log filter {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+fn write(filter: &LogFilter) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(filter)?;
+    fs::write(log_filter_path(), json)
+}
+
+fn level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::VecDeque,
+    sync::{LazyLock, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use driver_ipc::{LogLevel, LogRecord};
+use log::{Level, Log, Metadata, Record};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Amount of records kept for `RequestCommand::Logs`
+const CAPACITY: usize = 2048;
+
+static RING: LazyLock<Mutex<Ring>> = LazyLock::new(|| Mutex::new(Ring::new(CAPACITY)));
+/// New records for clients streaming the log
+static RECORDS: LazyLock<broadcast::Sender<LogRecord>> =
+    LazyLock::new(|| broadcast::channel(256).0);
+
+/// The most recent log records
+struct Ring {
+    records: VecDeque<LogRecord>,
+    capacity: usize,
+    next_seq: u64,
+}
+
+impl Ring {
+    fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::with_capacity(capacity),
+            capacity,
+            next_seq: 0,
+        }
+    }
+
+    fn push(
+        &mut self,
+        timestamp_ms: u64,
+        level: LogLevel,
+        target: &str,
+        message: String,
+    ) -> LogRecord {
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+
+        let record = LogRecord {
+            seq: self.next_seq,
+            timestamp_ms,
+            level,
+            target: target.to_owned(),
+            message,
+        };
+        self.next_seq += 1;
+        self.records.push_back(record.clone());
+
+        record
+    }
+
+    fn records(&self, since: Option<u64>, level: Option<LogLevel>) -> Vec<LogRecord> {
+        self.records
+            .iter()
+            .filter(|r| since.map_or(true, |since| r.seq > since))
+            .filter(|r| level.map_or(true, |level| r.level <= level))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Keep a record and send it to the clients streaming the log
+pub fn push(level: LogLevel, target: &str, message: String) {
+    #[allow(clippy::cast_possible_truncation)]
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64);
+
+    let record = RING
+        .lock()
+        .unwrap()
+        .push(timestamp_ms, level, target, message);
+
+    // fails if no client streams the log
+    _ = RECORDS.send(record);
+}
+
+/// The kept records newer than `since` and at least as severe as `level`,
+/// oldest first
+pub fn records(since: Option<u64>, level: Option<LogLevel>) -> Vec<LogRecord> {
+    RING.lock().unwrap().records(since, level)
+}
+
+/// A client streaming new log records, see `RequestCommand::SubscribeLogs`
+pub struct Subscription {
+    level: LogLevel,
+    rx: broadcast::Receiver<LogRecord>,
+}
+
+impl Subscription {
+    pub fn new(level: LogLevel) -> Self {
+        Self {
+            level,
+            rx: RECORDS.subscribe(),
+        }
+    }
+}
+
+/// Wait for the next record the client subscribed to. Never finishes without
+/// a subscription.
+pub async fn next(subscription: &mut Option<Subscription>) -> LogRecord {
+    let Some(subscription) = subscription else {
+        return std::future::pending().await;
+    };
+
+    loop {
+        match subscription.rx.recv().await {
+            Ok(record) if record.level <= subscription.level => return record,
+            // a client too slow to keep up misses records
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return std::future::pending().await,
+        }
+    }
+}
+
+/// The protocol's name of `level`
+pub fn log_level(level: Level) -> LogLevel {
+    match level {
+        Level::Error => LogLevel::Error,
+        Level::Warn => LogLevel::Warn,
+        Level::Info => LogLevel::Info,
+        Level::Debug => LogLevel::Debug,
+        Level::Trace => LogLevel::Trace,
+    }
+}
+
+/// Sink of `DriverLogger` which keeps the records
+pub struct RingLogger;
+
+impl Log for RingLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        push(
+            log_level(record.level()),
+            record.target(),
+            record.args().to_string(),
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ring_keeps_newest_records() {
+        let mut ring = Ring::new(3);
+        for (i, level) in [LogLevel::Info, LogLevel::Error, LogLevel::Debug, LogLevel::Warn]
+            .into_iter()
+            .enumerate()
+        {
+            ring.push(0, level, "test", i.to_string());
+        }
+
+        let seqs = |records: Vec<LogRecord>| records.iter().map(|r| r.seq).collect::<Vec<_>>();
+        assert_eq!(seqs(ring.records(None, None)), [1, 2, 3]);
+        assert_eq!(seqs(ring.records(Some(1), None)), [2, 3]);
+        assert_eq!(seqs(ring.records(None, Some(LogLevel::Warn))), [1, 3]);
+        assert_eq!(ring.records(Some(1), Some(LogLevel::Warn))[0].message, "3");
+    }
+}
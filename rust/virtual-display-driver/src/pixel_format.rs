@@ -0,0 +1,261 @@
+use std::{borrow::Cow, sync::OnceLock};
+
+use windows::Win32::Graphics::Dxgi::Common::{
+    DXGI_FORMAT, DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+    DXGI_FORMAT_B8G8R8X8_UNORM, DXGI_FORMAT_B8G8R8X8_UNORM_SRGB, DXGI_FORMAT_R10G10B10A2_UNORM,
+    DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+};
+
+/// Pixel format of a swap chain surface, as far as frame capture supports it
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 8 bits per channel in BGRA order, the usual SDR desktop format
+    Bgra8,
+    /// 8 bits per channel in RGBA order
+    Rgba8,
+    /// 10 bits per color channel and 2 bits alpha packed into 32 bits, red in
+    /// the lowest bits
+    Rgb10A2,
+    /// `Rgb10A2` holding HDR10, i.e. SMPTE ST 2084 (PQ) encoded
+    /// BT.2020
+    Rgb10A2Pq,
+    /// Linear scRGB with a half float per channel, where 1.0 is SDR white
+    Rgba16Float,
+}
+
+impl PixelFormat {
+    /// The format of a surface of a monitor, with `hdr` if the monitor reports
+    /// HDR10. The surface doesn't tell its color space, so 10-bit surfaces of
+    /// HDR monitors are taken to be HDR10.
+    pub fn from_dxgi(format: DXGI_FORMAT, hdr: bool) -> Option<Self> {
+        match format {
+            DXGI_FORMAT_B8G8R8A8_UNORM
+            | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB
+            | DXGI_FORMAT_B8G8R8X8_UNORM
+            | DXGI_FORMAT_B8G8R8X8_UNORM_SRGB => Some(PixelFormat::Bgra8),
+            DXGI_FORMAT_R8G8B8A8_UNORM | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => {
+                Some(PixelFormat::Rgba8)
+            }
+            DXGI_FORMAT_R10G10B10A2_UNORM if hdr => Some(PixelFormat::Rgb10A2Pq),
+            DXGI_FORMAT_R10G10B10A2_UNORM => Some(PixelFormat::Rgb10A2),
+            DXGI_FORMAT_R16G16B16A16_FLOAT => Some(PixelFormat::Rgba16Float),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> u32 {
+        match self {
+            PixelFormat::Bgra8
+            | PixelFormat::Rgba8
+            | PixelFormat::Rgb10A2
+            | PixelFormat::Rgb10A2Pq => 4,
+            PixelFormat::Rgba16Float => 8,
+        }
+    }
+
+    /// Convert tightly packed pixels to 8-bit BGRA, which is what the encoder
+    /// takes. Extra precision is dropped, and HDR is tone-mapped by clipping
+    /// it to SDR.
+    pub fn to_bgra8(self, data: &[u8]) -> Cow<'_, [u8]> {
+        let bytes_per_pixel = self.bytes_per_pixel() as usize;
+        debug_assert_eq!(data.len() % bytes_per_pixel, 0);
+
+        let convert = |f: fn(&[u8]) -> [u8; 4]| {
+            data.chunks_exact(bytes_per_pixel)
+                .flat_map(f)
+                .collect::<Vec<_>>()
+        };
+
+        match self {
+            PixelFormat::Bgra8 => Cow::Borrowed(data),
+            PixelFormat::Rgba8 => Cow::Owned(convert(|px| [px[2], px[1], px[0], px[3]])),
+            PixelFormat::Rgb10A2 => Cow::Owned(convert(|px| {
+                let px = u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+                // keep the upper 8 of each 10 bits
+                #[allow(clippy::cast_possible_truncation)]
+                let channel = |shift: u32| (px >> (shift + 2)) as u8;
+                #[allow(clippy::cast_possible_truncation)]
+                let alpha = (px >> 30) as u8 * 0x55;
+
+                [channel(20), channel(10), channel(0), alpha]
+            })),
+            PixelFormat::Rgb10A2Pq => Cow::Owned(convert(|px| {
+                let px = u32::from_le_bytes([px[0], px[1], px[2], px[3]]);
+                let channel = |shift: u32| pq_to_linear(px >> shift);
+                let [r, g, b] = bt2020_to_bt709([channel(0), channel(10), channel(20)]);
+                #[allow(clippy::cast_possible_truncation)]
+                let alpha = (px >> 30) as u8 * 0x55;
+
+                [linear_to_srgb(b), linear_to_srgb(g), linear_to_srgb(r), alpha]
+            })),
+            PixelFormat::Rgba16Float => Cow::Owned(convert(|px| {
+                let channel = |i: usize| f16_to_f32(u16::from_le_bytes([px[i], px[i + 1]]));
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let alpha = (channel(6).clamp(0.0, 1.0) * 255.0).round() as u8;
+
+                [
+                    linear_to_srgb(channel(4)),
+                    linear_to_srgb(channel(2)),
+                    linear_to_srgb(channel(0)),
+                    alpha,
+                ]
+            })),
+        }
+    }
+}
+
+/// Convert an IEEE 754 half float to a float
+fn f16_to_f32(bits: u16) -> f32 {
+    let sign = u32::from(bits >> 15) << 31;
+    let exponent = u32::from(bits >> 10) & 0x1F;
+    let mantissa = u32::from(bits) & 0x3FF;
+
+    let bits = match (exponent, mantissa) {
+        (0, 0) => sign,
+        // subnormal, the value is mantissa * 2^-24
+        #[allow(clippy::cast_precision_loss)]
+        (0, _) => return f32::from_bits(sign) + mantissa as f32 * f32::powi(2.0, -24),
+        // infinity or NaN
+        (0x1F, _) => sign | 0x7F80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 127 - 15) << 23) | (mantissa << 13),
+    };
+
+    f32::from_bits(bits)
+}
+
+/// Decode the lower 10 bits of `code` with the SMPTE ST 2084 (PQ) transfer
+/// function. Like in scRGB, 1.0 is SDR white at 80 nits.
+fn pq_to_linear(code: u32) -> f32 {
+    const STEPS: usize = 1024;
+    const M1: f32 = 2610.0 / 16384.0;
+    const M2: f32 = 2523.0 / 4096.0 * 128.0;
+    const C1: f32 = 3424.0 / 4096.0;
+    const C2: f32 = 2413.0 / 4096.0 * 32.0;
+    const C3: f32 = 2392.0 / 4096.0 * 32.0;
+    // PQ covers 0 to 10000 nits
+    const SCALE: f32 = 10000.0 / 80.0;
+
+    static TABLE: OnceLock<Vec<f32>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..STEPS)
+            .map(|i| {
+                #[allow(clippy::cast_precision_loss)]
+                let e = (i as f32 / (STEPS - 1) as f32).powf(1.0 / M2);
+                let linear = ((e - C1).max(0.0) / (C2 - C3 * e)).powf(1.0 / M1);
+
+                linear * SCALE
+            })
+            .collect()
+    });
+
+    table[code as usize % STEPS]
+}
+
+/// Convert linear BT.2020 to linear BT.709, the primaries of sRGB. Colors
+/// outside of BT.709 end up outside of `0..=1`.
+fn bt2020_to_bt709([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        1.6605 * r - 0.5876 * g - 0.0728 * b,
+        -0.1246 * r + 1.1329 * g - 0.0083 * b,
+        -0.0182 * r - 0.1006 * g + 1.1187 * b,
+    ]
+}
+
+/// Encode a linear value with the sRGB transfer function, clipped to `0..=1`
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn linear_to_srgb(value: f32) -> u8 {
+    const STEPS: usize = 4096;
+
+    // a lookup table, powf per channel is too slow for full frames
+    static TABLE: OnceLock<Vec<u8>> = OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        (0..STEPS)
+            .map(|i| {
+                #[allow(clippy::cast_precision_loss)]
+                let linear = i as f32 / (STEPS - 1) as f32;
+                let srgb = if linear <= 0.003_130_8 {
+                    linear * 12.92
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                };
+
+                (srgb * 255.0).round() as u8
+            })
+            .collect()
+    });
+
+    // NaN clamps to 0
+    #[allow(clippy::cast_precision_loss)]
+    let index = (value.clamp(0.0, 1.0) * (STEPS - 1) as f32).round() as usize;
+    table[index.min(STEPS - 1)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bgra8_is_borrowed() {
+        let data = [1, 2, 3, 4];
+        assert!(matches!(
+            PixelFormat::Bgra8.to_bgra8(&data),
+            Cow::Borrowed(&[1, 2, 3, 4])
+        ));
+        assert_eq!(*PixelFormat::Rgba8.to_bgra8(&data), [3, 2, 1, 4]);
+    }
+
+    #[test]
+    fn rgb10a2() {
+        let px = |r: u32, g: u32, b: u32, a: u32| {
+            (r | (g << 10) | (b << 20) | (a << 30)).to_le_bytes()
+        };
+
+        let data = [px(0x3FF, 0x200, 0, 3), px(0x003, 0x004, 0x3FC, 0)].concat();
+        assert_eq!(
+            *PixelFormat::Rgb10A2.to_bgra8(&data),
+            [0, 0x80, 0xFF, 0xFF, 0xFF, 0x01, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    // PQ 0 decodes to 0 exactly
+    #[allow(clippy::float_cmp)]
+    fn rgb10a2_pq() {
+        let px = |r: u32, g: u32, b: u32, a: u32| {
+            (r | (g << 10) | (b << 20) | (a << 30)).to_le_bytes()
+        };
+
+        // 40 nits is linear 0.5, 80 nits SDR white, and brighter is clipped
+        let data = [px(429, 429, 429, 3), px(497, 0, 0, 0), px(1023, 1023, 1023, 3)].concat();
+        assert_eq!(
+            *PixelFormat::Rgb10A2Pq.to_bgra8(&data),
+            [188, 188, 188, 255, 0, 0, 255, 0, 255, 255, 255, 255]
+        );
+
+        assert_eq!(pq_to_linear(0), 0.0);
+        assert!((pq_to_linear(497) - 1.0).abs() < 0.01);
+        assert!((pq_to_linear(1023) - 125.0).abs() < 0.01);
+    }
+
+    #[test]
+    // half floats convert to floats exactly
+    #[allow(clippy::float_cmp)]
+    fn rgba16_float() {
+        // 1.0, 0.5, 0.0 and 2.0 as half floats
+        let (one, half, zero, two) = (0x3C00u16, 0x3800u16, 0u16, 0x4000u16);
+        let px = |r: u16, g: u16, b: u16, a: u16| {
+            [r, g, b, a].into_iter().flat_map(u16::to_le_bytes).collect::<Vec<_>>()
+        };
+
+        let data = [px(one, half, zero, one), px(two, zero, 0xBC00, half)].concat();
+        // linear 0.5 is 188 in sRGB, HDR and negative values are clipped
+        assert_eq!(
+            *PixelFormat::Rgba16Float.to_bgra8(&data),
+            [0, 188, 255, 255, 0, 0, 255, 128]
+        );
+
+        assert_eq!(f16_to_f32(0x3555), 0.333_251_95);
+        assert_eq!(f16_to_f32(0x0001), f32::powi(2.0, -24));
+        assert_eq!(f16_to_f32(0xFC00), f32::NEG_INFINITY);
+    }
+}
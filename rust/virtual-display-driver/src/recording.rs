@@ -4,16 +4,54 @@ use std::thread::{self, JoinHandle};
 use std::time::Instant;
 
 use crossbeam_channel::{Receiver, Sender, TrySendError};
+use driver_ipc::{Orientation, RecordingStats};
 use log::{error, warn};
 
-use crate::encoder::Mp4Encoder;
+use crate::encoder::{rotate_upright, Mp4Encoder};
+use crate::pixel_format::PixelFormat;
 use crate::swap_chain_processor::trace_log;
 
-/// A frame of BGRA pixel data ready for encoding.
+/// A frame of tightly packed pixel data ready for encoding.
 pub struct Frame {
-    pub bgra_data: Vec<u8>,
+    pub data: Vec<u8>,
+    pub format: PixelFormat,
     pub width: u32,
     pub height: u32,
+    /// Orientation of the desktop the frame shows
+    pub orientation: Orientation,
+}
+
+impl Frame {
+    /// The frame in 8-bit BGRA, which the encoder takes
+    fn into_bgra8(self) -> Self {
+        if self.format == PixelFormat::Bgra8 {
+            return self;
+        }
+
+        Self {
+            data: self.format.to_bgra8(&self.data).into_owned(),
+            format: PixelFormat::Bgra8,
+            ..self
+        }
+    }
+
+    /// The BGRA frame as seen on the desktop
+    fn upright(self) -> Self {
+        if self.orientation == Orientation::Landscape {
+            return self;
+        }
+
+        let (data, width, height) =
+            rotate_upright(&self.data, self.width, self.height, self.orientation);
+
+        Self {
+            data,
+            width,
+            height,
+            orientation: Orientation::Landscape,
+            ..self
+        }
+    }
 }
 
 /// Result returned after recording stops.
@@ -27,6 +65,7 @@ pub struct RecordingResult {
 pub struct RecordingConfig {
     pub output_path: String,
     pub fps: u32,
+    pub bitrate: u32,
 }
 
 /// Manages a recording session: channel + encoder thread.
@@ -53,7 +92,13 @@ impl RecordingSession {
         let output_path = config.output_path.clone();
 
         let handle = thread::spawn(move || {
-            encoder_thread_main(rx, &stop_clone, &config.output_path, config.fps)
+            encoder_thread_main(
+                rx,
+                &stop_clone,
+                &config.output_path,
+                config.fps,
+                config.bitrate,
+            )
         });
 
         trace_log(&format!(
@@ -97,6 +142,16 @@ impl RecordingSession {
         }
     }
 
+    /// Current counters of the session, see `RequestCommand::Stats`.
+    pub fn stats(&self) -> RecordingStats {
+        RecordingStats {
+            frames_sent: self.frames_sent.load(Ordering::Relaxed),
+            frames_dropped: self.frames_dropped.load(Ordering::Relaxed),
+            #[allow(clippy::cast_possible_truncation)]
+            duration_ms: self.start_time.elapsed().as_millis() as u64,
+        }
+    }
+
     /// Stop the recording and return the result.
     /// Blocks until the encoder thread finishes and the MP4 is finalized.
     pub fn stop(mut self) -> Option<RecordingResult> {
@@ -147,6 +202,7 @@ fn encoder_thread_main(
     stop: &AtomicBool,
     output_path: &str,
     fps: u32,
+    bitrate: u32,
 ) -> Option<RecordingResult> {
     trace_log(&format!("Encoder thread started: path={output_path}"));
 
@@ -157,8 +213,9 @@ fn encoder_thread_main(
         if stop.load(Ordering::Acquire) {
             // Drain remaining frames in channel before stopping
             while let Ok(frame) = rx.try_recv() {
+                let frame = frame.into_bgra8().upright();
                 if let Some(ref mut enc) = encoder {
-                    if let Err(e) = enc.encode_frame(&frame.bgra_data) {
+                    if let Err(e) = enc.encode_frame(&frame.data) {
                         error!("Encode error during drain: {e}");
                     }
                 }
@@ -169,13 +226,15 @@ fn encoder_thread_main(
         // Wait for a frame with timeout (so we can check stop signal periodically)
         match rx.recv_timeout(std::time::Duration::from_millis(100)) {
             Ok(frame) => {
+                let frame = frame.into_bgra8().upright();
+
                 // Lazy init encoder on first frame (now we know dimensions)
                 if encoder.is_none() {
                     trace_log(&format!(
                         "Encoder init: {}x{} fps={fps} path={output_path}",
                         frame.width, frame.height
                     ));
-                    match Mp4Encoder::new(output_path, frame.width, frame.height, fps) {
+                    match Mp4Encoder::new(output_path, frame.width, frame.height, fps, bitrate) {
                         Ok(enc) => encoder = Some(enc),
                         Err(e) => {
                             error!("Failed to create MP4 encoder: {e}");
@@ -185,7 +244,7 @@ fn encoder_thread_main(
                 }
 
                 if let Some(ref mut enc) = encoder {
-                    if let Err(e) = enc.encode_frame(&frame.bgra_data) {
+                    if let Err(e) = enc.encode_frame(&frame.data) {
                         warn!("Encode error: {e}");
                     }
                 }
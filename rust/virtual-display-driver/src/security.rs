@@ -0,0 +1,294 @@
+//! Access control of the pipe, see `DriverConfig::read_access` and
+//! `DriverConfig::control_access`
+
+use std::{
+    fs,
+    os::windows::{fs::MetadataExt, io::AsRawHandle},
+    path::{Path, PathBuf},
+};
+
+use driver_ipc::{config::error::RecordingPathError, Access, DriverConfig};
+use log::{error, warn};
+use windows::{
+    core::{HSTRING, PCWSTR, PWSTR},
+    Win32::{
+        Foundation::{CloseHandle, LocalFree, BOOL, HANDLE, HLOCAL},
+        Security::{
+            Authorization::{
+                ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+                ConvertStringSidToSidW, SDDL_REVISION_1,
+            },
+            CheckTokenMembership, GetLengthSid, LookupAccountNameW, RevertToSelf,
+            PSECURITY_DESCRIPTOR, PSID, SID_NAME_USE, TOKEN_QUERY,
+        },
+        Storage::FileSystem::FILE_ATTRIBUTE_REPARSE_POINT,
+        System::{
+            Pipes::ImpersonateNamedPipeClient,
+            Threading::{GetCurrentThread, OpenThreadToken},
+        },
+    },
+};
+
+/// A user or group
+pub struct Sid {
+    // u32 for the alignment of the SID's sub authorities
+    buf: Vec<u32>,
+}
+
+impl Sid {
+    /// Look up an account given as SID like `S-1-5-11`, SDDL alias like `AU`
+    /// or account name like `BUILTIN\Users`
+    pub fn resolve(account: &str) -> Option<Self> {
+        let account = HSTRING::from(account);
+
+        let mut sid = PSID::default();
+        if unsafe { ConvertStringSidToSidW(&account, &mut sid) }.is_ok() {
+            let resolved = Self::copy(sid);
+            _ = unsafe { LocalFree(HLOCAL(sid.0)) };
+
+            return Some(resolved);
+        }
+
+        // the first call only reports the sizes
+        let mut sid_len = 0;
+        let mut domain_len = 0;
+        let mut kind = SID_NAME_USE::default();
+        _ = unsafe {
+            LookupAccountNameW(
+                PCWSTR::null(),
+                &account,
+                PSID::default(),
+                &mut sid_len,
+                PWSTR::null(),
+                &mut domain_len,
+                &mut kind,
+            )
+        };
+
+        if sid_len == 0 {
+            return None;
+        }
+
+        let mut resolved = Self {
+            buf: vec![0; (sid_len as usize).div_ceil(4)],
+        };
+        let mut domain = vec![0u16; domain_len as usize];
+        unsafe {
+            LookupAccountNameW(
+                PCWSTR::null(),
+                &account,
+                PSID(resolved.buf.as_mut_ptr().cast()),
+                &mut sid_len,
+                PWSTR(domain.as_mut_ptr()),
+                &mut domain_len,
+                &mut kind,
+            )
+        }
+        .ok()?;
+
+        Some(resolved)
+    }
+
+    /// Copy a valid SID allocated by the system
+    fn copy(sid: PSID) -> Self {
+        let len = unsafe { GetLengthSid(sid) } as usize;
+        let mut buf = vec![0u32; len.div_ceil(4)];
+        unsafe {
+            std::ptr::copy_nonoverlapping(sid.0.cast::<u8>(), buf.as_mut_ptr().cast::<u8>(), len);
+        }
+
+        Self { buf }
+    }
+
+    fn psid(&self) -> PSID {
+        PSID(self.buf.as_ptr().cast_mut().cast())
+    }
+
+    /// The SID as string, e.g. `S-1-5-11`
+    fn to_sddl(&self) -> windows::core::Result<String> {
+        let mut string = PWSTR::null();
+        unsafe { ConvertSidToStringSidW(self.psid(), &mut string)? };
+
+        let sddl = unsafe { string.to_string() };
+        _ = unsafe { LocalFree(HLOCAL(string.0.cast())) };
+
+        Ok(sddl?)
+    }
+}
+
+/// Resolve the accounts of a config entry, skipping unknown and invalid ones
+pub fn resolve(accounts: &[String]) -> Vec<Sid> {
+    accounts
+        .iter()
+        .filter_map(|account| {
+            let Some(sid) = Sid::resolve(account) else {
+                error!("Unknown account {account:?} in pipe access config, ignoring it");
+                return None;
+            };
+
+            if let Err(e) = sid.to_sddl() {
+                error!("Invalid SID of account {account:?} in pipe access config: {e}");
+                return None;
+            }
+
+            Some(sid)
+        })
+        .collect()
+}
+
+/// Security descriptor of the pipe
+pub struct SecurityDescriptor(PSECURITY_DESCRIPTOR);
+
+impl SecurityDescriptor {
+    /// Let `sids` connect to the pipe, and nobody else
+    pub fn allow(sids: &[Sid]) -> windows::core::Result<Self> {
+        // the driver, as owner of the pipe, must be able to create further
+        // instances of it
+        let mut sddl = "D:(A;;GA;;;OW)(A;;GA;;;SY)".to_owned();
+        for sid in sids {
+            sddl += &format!("(A;;GRGW;;;{})", sid.to_sddl()?);
+        }
+
+        let mut descriptor = PSECURITY_DESCRIPTOR::default();
+        unsafe {
+            ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                &HSTRING::from(sddl),
+                SDDL_REVISION_1,
+                &mut descriptor,
+                None,
+            )?;
+        }
+
+        Ok(Self(descriptor))
+    }
+
+    pub fn as_ptr(&self) -> *mut std::ffi::c_void {
+        self.0 .0
+    }
+}
+
+impl Drop for SecurityDescriptor {
+    fn drop(&mut self) {
+        _ = unsafe { LocalFree(HLOCAL(self.0 .0)) };
+    }
+}
+
+/// Access of the client connected to `pipe`, which must have sent a message
+/// already
+///
+/// Only accounts with read access can connect in the first place, so any
+/// client without control access has read access.
+pub fn client_access(pipe: &impl AsRawHandle, control: &[Sid]) -> Access {
+    match is_member(HANDLE(pipe.as_raw_handle()), control) {
+        Ok(true) => Access::Control,
+        Ok(false) => Access::Read,
+        Err(e) => {
+            warn!("Failed to check access of pipe client: {e}");
+            Access::Read
+        }
+    }
+}
+
+/// Whether the client of `pipe` is any of `sids`
+fn is_member(pipe: HANDLE, sids: &[Sid]) -> windows::core::Result<bool> {
+    unsafe { ImpersonateNamedPipeClient(pipe)? };
+
+    let mut token = HANDLE::default();
+    let thread = unsafe { GetCurrentThread() };
+    let opened = unsafe { OpenThreadToken(thread, TOKEN_QUERY, true, &mut token) };
+
+    // never keep running as the client. A panic would only end the task, and
+    // the worker thread would go on running other clients as this one
+    if let Err(e) = unsafe { RevertToSelf() } {
+        error!("Failed to revert impersonation of pipe client: {e}");
+        std::process::abort();
+    }
+    opened?;
+
+    let mut result = Ok(false);
+    for sid in sids {
+        let mut member = BOOL::default();
+        if let Err(e) = unsafe { CheckTokenMembership(token, sid.psid(), &mut member) } {
+            result = Err(e);
+            break;
+        }
+
+        if member.as_bool() {
+            result = Ok(true);
+            break;
+        }
+    }
+
+    _ = unsafe { CloseHandle(token) };
+
+    result
+}
+
+/// Check that a recording may be written to `path`, see
+/// `DriverConfig::check_recording_path`
+///
+/// Also resolves links in the directories, so a link inside a recording
+/// directory can't point outside of it, and refuses a file which is a link
+/// itself. Creates missing directories, but only once the deepest existing one
+/// is known to be inside the recording directory.
+pub fn check_recording_path(config: &DriverConfig, path: &str) -> Result<PathBuf, String> {
+    let dir = config
+        .check_recording_path(path)
+        .map_err(|e| e.to_string())?;
+
+    let path = Path::new(path);
+    let parent = path
+        .parent()
+        .ok_or_else(|| RecordingPathError::NotAbsolute.to_string())?;
+
+    let canonical = |path: &Path| {
+        fs::canonicalize(path).map_err(|e| format!("failed to resolve {}: {e}", path.display()))
+    };
+    let create_dir_all = |path: &Path| {
+        fs::create_dir_all(path).map_err(|e| format!("failed to create directory: {e}"))
+    };
+
+    // the recording directory itself comes from the config, not the client
+    create_dir_all(Path::new(dir))?;
+    let dir = canonical(Path::new(dir))?;
+
+    let existing = parent
+        .ancestors()
+        .find(|ancestor| ancestor.exists())
+        .ok_or_else(|| RecordingPathError::NotAbsolute.to_string())?;
+    let missing = parent.strip_prefix(existing).unwrap_or(Path::new(""));
+
+    let existing = canonical(existing)?;
+    if !existing.starts_with(&dir) {
+        return Err(RecordingPathError::OutsideDirs.to_string());
+    }
+
+    // below the resolved directory, so the client's path is not followed again
+    create_dir_all(&existing.join(missing))?;
+
+    // a link may have been put in place in the meantime
+    let parent = canonical(&existing.join(missing))?;
+    if !parent.starts_with(&dir) {
+        return Err(RecordingPathError::OutsideDirs.to_string());
+    }
+
+    // the driver would write through a link wherever it points
+    let file = parent.join(path.file_name().unwrap_or_default());
+    if let Ok(metadata) = fs::symlink_metadata(&file) {
+        if metadata.file_attributes() & FILE_ATTRIBUTE_REPARSE_POINT.0 != 0 {
+            return Err(format!("{} is a link", path.display()));
+        }
+    }
+
+    Ok(strip_verbatim(&file))
+}
+
+/// `path` without the `\\?\` prefix `fs::canonicalize` adds, which clients
+/// and logs should not see
+fn strip_verbatim(path: &Path) -> PathBuf {
+    // UNC paths are no recording paths, and can't simply drop the prefix
+    match path.to_str().and_then(|path| path.strip_prefix(r"\\?\")) {
+        Some(path) if !path.starts_with(r"UNC\") => PathBuf::from(path),
+        _ => path.to_owned(),
+    }
+}
@@ -54,15 +54,17 @@ impl SharedMemoryWriter {
     /// Create a new shared memory region for frame capture.
     ///
     /// `monitor_id` is used to name the mapping: `Global\VDD_Frame_{monitor_id}`
+    ///
+    /// `frame_count` is the number of frame slots in the ring buffer
     pub fn new(
         monitor_id: u32,
         width: u32,
         height: u32,
         stride: u32,
         format: u32,
+        frame_count: u32,
     ) -> Result<Self, &'static str> {
         let frame_size = (stride * height) as usize;
-        let frame_count = 2u32; // double-buffered ring
         let total_size = HEADER_SIZE + frame_size * frame_count as usize;
 
         let name = format!("Global\\VDD_Frame_{monitor_id}");
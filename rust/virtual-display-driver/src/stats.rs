@@ -0,0 +1,100 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, LazyLock, Mutex,
+    },
+};
+
+use driver_ipc::{MonitorStats, Stats, Surface, SwapChainHealth};
+
+use crate::ipc::{MONITOR_MODES, RECORDING_STATE};
+
+/// Counters of every monitor which received a swap chain
+static SWAP_CHAIN_STATS: LazyLock<Mutex<HashMap<u32, Arc<SwapChainStats>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Counters of the swap chains of a single monitor, updated by its
+/// `SwapChainProcessor` thread
+#[derive(Debug, Default)]
+pub struct SwapChainStats {
+    pub frames_acquired: AtomicU64,
+    pub frames_captured: AtomicU64,
+    pub pending_waits: AtomicU64,
+    pub swap_chain_errors: AtomicU64,
+    /// Whether the swap chain thread processes frames, see `health::Running`
+    pub running: AtomicBool,
+    surface: Mutex<Option<Surface>>,
+}
+
+impl SwapChainStats {
+    /// Get the counters of a monitor, creating them on its first swap chain
+    pub fn for_monitor(monitor_id: u32) -> Arc<Self> {
+        SWAP_CHAIN_STATS
+            .lock()
+            .unwrap()
+            .entry(monitor_id)
+            .or_default()
+            .clone()
+    }
+
+    pub fn set_surface(&self, surface: Surface) {
+        *self.surface.lock().unwrap() = Some(surface);
+    }
+
+    fn snapshot(&self, id: u32) -> MonitorStats {
+        MonitorStats {
+            id,
+            frames_acquired: self.frames_acquired.load(Ordering::Relaxed),
+            frames_captured: self.frames_captured.load(Ordering::Relaxed),
+            pending_waits: self.pending_waits.load(Ordering::Relaxed),
+            swap_chain_errors: self.swap_chain_errors.load(Ordering::Relaxed),
+            surface: *self.surface.lock().unwrap(),
+        }
+    }
+}
+
+/// Map the counters of all monitors which still exist, sorted by id
+fn collect<T>(f: impl Fn(u32, &SwapChainStats) -> T) -> Vec<T> {
+    let ids = MONITOR_MODES
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|m| m.data.id)
+        .collect::<Vec<_>>();
+
+    let stats = SWAP_CHAIN_STATS.lock().unwrap();
+    let mut existing = stats
+        .iter()
+        .filter(|(id, _)| ids.contains(*id))
+        .collect::<Vec<_>>();
+    existing.sort_by_key(|(&id, _)| id);
+
+    existing.into_iter().map(|(&id, stats)| f(id, stats)).collect()
+}
+
+/// Whether the swap chain threads of all monitors which still exist run
+pub fn swap_chains() -> Vec<SwapChainHealth> {
+    collect(|id, stats| SwapChainHealth {
+        id,
+        alive: stats.running.load(Ordering::Relaxed),
+    })
+}
+
+/// Collect the counters of all monitors which still exist, and of the active
+/// recording
+pub fn snapshot() -> Stats {
+    let monitors = collect(|id, stats| stats.snapshot(id));
+
+    let recording = RECORDING_STATE
+        .lock()
+        .unwrap()
+        .session
+        .as_ref()
+        .map(crate::recording::RecordingSession::stats);
+
+    Stats {
+        monitors,
+        recording,
+    }
+}
@@ -7,6 +7,7 @@ use std::{
     thread::{self, JoinHandle},
 };
 
+use driver_ipc::{LogLevel, Orientation, Surface};
 use log::{debug, error, info};
 use wdf_umdf::{
     IddCxSwapChainFinishedProcessingFrame, IddCxSwapChainReleaseAndAcquireBuffer,
@@ -25,7 +26,7 @@ use windows::{
                 ID3D11Texture2D, D3D11_CPU_ACCESS_READ, D3D11_MAP_READ,
                 D3D11_MAPPED_SUBRESOURCE, D3D11_TEXTURE2D_DESC, D3D11_USAGE_STAGING,
             },
-            Dxgi::IDXGIDevice,
+            Dxgi::{Common::DXGI_FORMAT, IDXGIDevice},
         },
         System::Threading::{
             AvRevertMmThreadCharacteristics, AvSetMmThreadCharacteristicsW, WaitForSingleObject,
@@ -34,18 +35,35 @@ use windows::{
 };
 
 use crate::{
+    config::config,
     direct_3d_device::Direct3DDevice,
+    health::Running,
     helpers::Sendable,
     ipc::RECORDING_STATE,
+    pixel_format::PixelFormat,
     shared_memory::SharedMemoryWriter,
+    stats::SwapChainStats,
 };
 
+/// Append a message to the trace log, as an info record of the target
+/// `trace_log`
 pub fn trace_log(msg: &str) {
     use std::io::Write;
+
+    if !crate::log_filter::enabled("trace_log", LogLevel::Info) {
+        return;
+    }
+
+    // also readable over IPC, where the temp directory is out of reach
+    crate::logs::push(LogLevel::Info, "trace_log", msg.to_owned());
+
+    let Some(path) = config().trace_log_path.clone() else {
+        return;
+    };
     if let Ok(mut f) = std::fs::OpenOptions::new()
         .create(true)
         .append(true)
-        .open(r"C:\Windows\Temp\VDD_trace.log")
+        .open(path)
     {
         let _ = writeln!(f, "[{:?}] {msg}", std::time::SystemTime::now());
     }
@@ -53,6 +71,10 @@ pub fn trace_log(msg: &str) {
 
 pub struct SwapChainProcessor {
     monitor_id: u32,
+    /// Orientation of the desktop, frames are rendered rotated by it
+    orientation: Orientation,
+    /// Whether the monitor reports HDR10, see `PixelFormat::from_dxgi`
+    hdr: bool,
     terminate: Arc<AtomicBool>,
     thread: Option<JoinHandle<()>>,
 }
@@ -66,12 +88,16 @@ struct CaptureResources {
     shm_writer: SharedMemoryWriter,
     width: u32,
     height: u32,
+    format: DXGI_FORMAT,
+    pixel_format: PixelFormat,
 }
 
 impl SwapChainProcessor {
-    pub fn new(monitor_id: u32) -> Self {
+    pub fn new(monitor_id: u32, orientation: Orientation, hdr: bool) -> Self {
         Self {
             monitor_id,
+            orientation,
+            hdr,
             terminate: Arc::new(AtomicBool::new(false)),
             thread: None,
         }
@@ -87,6 +113,8 @@ impl SwapChainProcessor {
         let swap_chain = unsafe { Sendable::new(swap_chain) };
         let terminate = self.terminate.clone();
         let monitor_id = self.monitor_id;
+        let orientation = self.orientation;
+        let hdr = self.hdr;
 
         let join_handle = thread::spawn(move || {
             trace_log(&format!("SwapChainProcessor thread started for monitor {monitor_id}"));
@@ -105,6 +133,8 @@ impl SwapChainProcessor {
                 *available_buffer_event,
                 &terminate,
                 monitor_id,
+                orientation,
+                hdr,
             );
 
             let res = unsafe { WdfObjectDelete(*swap_chain as WDFOBJECT) };
@@ -129,6 +159,8 @@ impl SwapChainProcessor {
         available_buffer_event: HANDLE,
         terminate: &AtomicBool,
         monitor_id: u32,
+        orientation: Orientation,
+        hdr: bool,
     ) {
         let dxgi_device = device.device.cast::<IDXGIDevice>();
         let Ok(dxgi_device) = dxgi_device else {
@@ -148,6 +180,9 @@ impl SwapChainProcessor {
         }
         trace_log(&format!("SwapChain device set OK for monitor {monitor_id}"));
 
+        let stats = SwapChainStats::for_monitor(monitor_id);
+        let _running = Running::new(&stats.running);
+        let mut last_surface = std::ptr::null_mut();
         let mut capture: Option<CaptureResources> = None;
         let mut was_recording = false;
         let mut frame_count: u64 = 0;
@@ -173,6 +208,7 @@ impl SwapChainProcessor {
             const E_PENDING: u32 = 0x8000_000A;
             if u32::from(hr) == E_PENDING {
                 pending_count += 1;
+                stats.pending_waits.fetch_add(1, Ordering::Relaxed);
                 let wait_result =
                     unsafe { WaitForSingleObject(WHANDLE(available_buffer_event.cast()), 16).0 };
 
@@ -196,6 +232,12 @@ impl SwapChainProcessor {
                 break;
             } else if hr.is_success() {
                 frame_count += 1;
+                stats.frames_acquired.fetch_add(1, Ordering::Relaxed);
+                // the OS cycles through a few surfaces, only look at new ones
+                if buffer.MetaData.pSurface != last_surface {
+                    last_surface = buffer.MetaData.pSurface;
+                    Self::update_surface(&buffer, &stats);
+                }
                 if frame_count <= 3 || frame_count % 100 == 0 {
                     trace_log(&format!(
                         "run_core: acquired frame #{frame_count} for monitor {monitor_id}"
@@ -212,7 +254,18 @@ impl SwapChainProcessor {
                     if !was_recording {
                         trace_log(&format!("First recording frame for monitor {monitor_id}"));
                     }
-                    Self::capture_frame(device, &buffer, swap_chain, monitor_id, &mut capture);
+                    let captured = Self::capture_frame(
+                        device,
+                        &buffer,
+                        swap_chain,
+                        monitor_id,
+                        orientation,
+                        hdr,
+                        &mut capture,
+                    );
+                    if captured {
+                        stats.frames_captured.fetch_add(1, Ordering::Relaxed);
+                    }
 
                     if !was_recording {
                         info!("Started recording monitor {monitor_id}");
@@ -222,6 +275,7 @@ impl SwapChainProcessor {
                     // Not recording — just release the frame immediately
                     let hr = unsafe { IddCxSwapChainFinishedProcessingFrame(swap_chain) };
                     if hr.is_err() {
+                        stats.swap_chain_errors.fetch_add(1, Ordering::Relaxed);
                         break;
                     }
 
@@ -238,6 +292,7 @@ impl SwapChainProcessor {
                     "run_core: swap chain error hr=0x{:08X} for monitor {monitor_id}, exiting loop",
                     u32::from(hr)
                 ));
+                stats.swap_chain_errors.fetch_add(1, Ordering::Relaxed);
                 break;
             }
         }
@@ -247,19 +302,48 @@ impl SwapChainProcessor {
         ));
     }
 
+    /// Remember size and format of the acquired surface for `RequestCommand::Stats`
+    fn update_surface(buffer: &IDARG_OUT_RELEASEANDACQUIREBUFFER, stats: &SwapChainStats) {
+        let surface_ptr = buffer.MetaData.pSurface;
+        if surface_ptr.is_null() {
+            return;
+        }
+
+        // same as in capture_frame, the surface reference is owned by IddCx
+        let unknown = ManuallyDrop::new(unsafe {
+            windows::core::IUnknown::from_raw(surface_ptr.cast())
+        });
+        let Ok(texture) = unknown.cast::<ID3D11Texture2D>() else {
+            return;
+        };
+
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { texture.GetDesc(&mut desc) };
+
+        stats.set_surface(Surface {
+            width: desc.Width,
+            height: desc.Height,
+            format: desc.Format.0 as u32,
+        });
+    }
+
+    /// Copy the acquired frame to shared memory and hand it to the recording
+    /// session. Returns whether the frame reached the shared memory.
     fn capture_frame(
         device: &Direct3DDevice,
         buffer: &IDARG_OUT_RELEASEANDACQUIREBUFFER,
         swap_chain: IDDCX_SWAPCHAIN,
         monitor_id: u32,
+        orientation: Orientation,
+        hdr: bool,
         capture: &mut Option<CaptureResources>,
-    ) {
+    ) -> bool {
         // 1. Get the IDXGIResource from the swap chain buffer
         let surface_ptr = buffer.MetaData.pSurface;
         if surface_ptr.is_null() {
             trace_log(&format!("capture_frame: surface_ptr is null for monitor {monitor_id}"));
             let _ = unsafe { IddCxSwapChainFinishedProcessingFrame(swap_chain) };
-            return;
+            return false;
         }
 
         // Cast the IUnknown surface to ID3D11Texture2D via QueryInterface.
@@ -273,7 +357,7 @@ impl SwapChainProcessor {
             Err(e) => {
                 error!("Failed to cast surface to ID3D11Texture2D: {e:?}");
                 let _ = unsafe { IddCxSwapChainFinishedProcessingFrame(swap_chain) };
-                return;
+                return false;
             }
         };
 
@@ -281,12 +365,18 @@ impl SwapChainProcessor {
         let mut desc = D3D11_TEXTURE2D_DESC::default();
         unsafe { texture.GetDesc(&mut desc) };
 
-        // 2. Ensure capture resources exist and match current dimensions
+        // 2. Ensure capture resources exist and match current dimensions and format
         let resources = match capture {
-            Some(res) if res.width == desc.Width && res.height == desc.Height => res,
+            Some(res)
+                if res.width == desc.Width
+                    && res.height == desc.Height
+                    && res.format == desc.Format =>
+            {
+                res
+            }
             _ => {
                 // Create or recreate staging texture + shared memory
-                match Self::create_capture_resources(device, &desc, monitor_id) {
+                match Self::create_capture_resources(device, &desc, monitor_id, hdr) {
                     Ok(res) => {
                         *capture = Some(res);
                         capture.as_mut().unwrap()
@@ -294,7 +384,7 @@ impl SwapChainProcessor {
                     Err(e) => {
                         error!("Failed to create capture resources: {e}");
                         let _ = unsafe { IddCxSwapChainFinishedProcessingFrame(swap_chain) };
-                        return;
+                        return false;
                     }
                 }
             }
@@ -310,7 +400,7 @@ impl SwapChainProcessor {
         // 4. Release the swap chain frame ASAP
         let hr = unsafe { IddCxSwapChainFinishedProcessingFrame(swap_chain) };
         if hr.is_err() {
-            return;
+            return false;
         }
 
         // 5. Map the staging texture and copy to shared memory (slower CPU read)
@@ -328,7 +418,7 @@ impl SwapChainProcessor {
         match map_result {
             Ok(()) => {
                 let src_stride = mapped.RowPitch as usize;
-                let dst_stride = (desc.Width * 4) as usize;
+                let dst_stride = (desc.Width * resources.pixel_format.bytes_per_pixel()) as usize;
                 let height = desc.Height as usize;
 
                 if src_stride == dst_stride {
@@ -344,9 +434,11 @@ impl SwapChainProcessor {
                     let state = RECORDING_STATE.lock().unwrap();
                     if let Some(ref session) = state.session {
                         session.try_send_frame(crate::recording::Frame {
-                            bgra_data: data.to_vec(),
+                            data: data.to_vec(),
+                            format: resources.pixel_format,
                             width: desc.Width,
                             height: desc.Height,
+                            orientation,
                         });
                     }
                 } else {
@@ -367,9 +459,11 @@ impl SwapChainProcessor {
                     let state = RECORDING_STATE.lock().unwrap();
                     if let Some(ref session) = state.session {
                         session.try_send_frame(crate::recording::Frame {
-                            bgra_data: frame_buf,
+                            data: frame_buf,
+                            format: resources.pixel_format,
                             width: desc.Width,
                             height: desc.Height,
+                            orientation,
                         });
                     }
                 }
@@ -377,9 +471,12 @@ impl SwapChainProcessor {
                 unsafe {
                     device.device_context.Unmap(&resources.staging_texture, 0);
                 }
+
+                true
             }
             Err(e) => {
                 error!("Failed to map staging texture: {e:?}");
+                false
             }
         }
     }
@@ -388,7 +485,11 @@ impl SwapChainProcessor {
         device: &Direct3DDevice,
         desc: &D3D11_TEXTURE2D_DESC,
         monitor_id: u32,
+        hdr: bool,
     ) -> Result<CaptureResources, String> {
+        let pixel_format = PixelFormat::from_dxgi(desc.Format, hdr)
+            .ok_or_else(|| format!("Unsupported surface format {}", desc.Format.0))?;
+
         // Create staging texture
         let staging_desc = D3D11_TEXTURE2D_DESC {
             Width: desc.Width,
@@ -417,11 +518,19 @@ impl SwapChainProcessor {
             texture.ok_or("CreateTexture2D succeeded but texture is None")?
         };
 
-        let stride = desc.Width * 4; // BGRA = 4 bytes per pixel
+        // tightly packed rows, e.g. 4 bytes per pixel for BGRA or R10G10B10A2
+        let stride = desc.Width * pixel_format.bytes_per_pixel();
         let format = desc.Format.0 as u32;
 
-        let shm_writer = SharedMemoryWriter::new(monitor_id, desc.Width, desc.Height, stride, format)
-            .map_err(|e| format!("SharedMemoryWriter::new failed: {e}"))?;
+        let shm_writer = SharedMemoryWriter::new(
+            monitor_id,
+            desc.Width,
+            desc.Height,
+            stride,
+            format,
+            config().shm_slots,
+        )
+        .map_err(|e| format!("SharedMemoryWriter::new failed: {e}"))?;
 
         trace_log(&format!(
             "Created capture resources for monitor {monitor_id}: {}x{} stride={stride} format={format} shm={}",
@@ -441,6 +550,8 @@ impl SwapChainProcessor {
             shm_writer,
             width: desc.Width,
             height: desc.Height,
+            format: desc.Format,
+            pixel_format,
         })
     }
 }